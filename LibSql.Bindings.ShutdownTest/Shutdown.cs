@@ -0,0 +1,32 @@
+using System.Net;
+using System.Net.Sockets;
+using LibSql.Bindings;
+
+namespace LibSql.Bindings.ShutdownTest;
+
+// Shutting the runtime down can't be undone, this runs in its own test process
+public class ShutdownTest
+{
+    [Fact]
+    public async Task PendingCallsFailOnShutdown()
+    {
+        // Accepts connections but never answers, the query stays pending
+        using var listener = new TcpListener(IPAddress.Loopback, 0);
+        listener.Start();
+        var port = ((IPEndPoint)listener.LocalEndpoint).Port;
+        using var db = await Database.OpenRemote($"http://127.0.0.1:{port}", "");
+        using var connection = db.Connect();
+
+        var query = connection.Query("SELECT 1");
+        await Task.Delay(TimeSpan.FromMilliseconds(200));
+        Runtime.Shutdown(TimeSpan.FromMilliseconds(100));
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            () => query.WaitAsync(TimeSpan.FromSeconds(10))
+        );
+        Assert.Equal(LibSqlErrorKind.Misuse, error.Kind);
+
+        error = await Assert.ThrowsAsync<LibSqlException>(() => connection.Query("SELECT 1"));
+        Assert.Equal(LibSqlErrorKind.Misuse, error.Kind);
+    }
+}
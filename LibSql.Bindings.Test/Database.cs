@@ -9,6 +9,7 @@ public class DatabaseTest : IAsyncLifetime, IClassFixture<DatabaseContainer>
     public Database remoteDb = null!;
     public Database replicaDb = null!;
     public Connection memoryConnection = null!;
+    private string url = null!;
 
     public DatabaseTest(DatabaseContainer databaseContainer)
     {
@@ -18,7 +19,7 @@ public class DatabaseTest : IAsyncLifetime, IClassFixture<DatabaseContainer>
     public async Task InitializeAsync()
     {
         await _databaseContainer.libsqlContainer.StartAsync();
-        var url =
+        url =
             $"http://{_databaseContainer.libsqlContainer.Hostname}:{_databaseContainer.libsqlContainer.GetMappedPublicPort(8080)}";
 
         memoryDb = await Database.OpenLocalFile(":memory:");
@@ -54,7 +55,9 @@ public class DatabaseTest : IAsyncLifetime, IClassFixture<DatabaseContainer>
         var replicated = await replicaDb.Sync();
 
         // Assert.Equal(1, replicated.FramesSynced);
-        Assert.Equal(2, replicated.FrameNo);
+        Assert.True(replicated.HasFrameNo);
+        Assert.Equal((ulong)2, replicated.FrameNo);
+        Assert.Equal(replicated.FrameNo, replicaDb.ReplicationIndex());
 
         // await memoryConnection.Execute("DROP TABLE test_replicated");
     }
@@ -71,6 +74,252 @@ public class DatabaseTest : IAsyncLifetime, IClassFixture<DatabaseContainer>
         Assert.Equal(3, rows.ColumnCount());
     }
 
+    [Fact]
+    public async Task OpenLocalReadOnly()
+    {
+        var writable = await Database.OpenLocal(new LocalConfig { DbPath = "readonly.db" });
+        await writable.Connect().Execute("CREATE TABLE IF NOT EXISTS items (name TEXT)");
+
+        var db = await Database.OpenLocal(
+            new LocalConfig { DbPath = "readonly.db", Flags = OpenFlags.ReadOnly }
+        );
+        var connection = db.Connect();
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            () => connection.Execute("INSERT INTO items VALUES ('a')")
+        );
+        Assert.Equal(8, error.Code); // SQLITE_READONLY
+    }
+
+    [Fact]
+    public async Task OpenLocalEncrypted()
+    {
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            () =>
+                Database.OpenLocal(
+                    new LocalConfig
+                    {
+                        DbPath = "encrypted.db",
+                        EncryptionKey = new byte[16],
+                        Cipher = Cipher.Aes256Cbc,
+                    }
+                )
+        );
+        Assert.Equal(LibSqlErrorKind.InvalidArgument, error.Kind);
+
+        var key = Enumerable.Range(0, 32).Select(i => (byte)i).ToArray();
+        var config = new LocalConfig
+        {
+            DbPath = "encrypted.db",
+            EncryptionKey = key,
+            Cipher = Cipher.Aes256Cbc,
+        };
+        using (var db = await Database.OpenLocal(config))
+        {
+            using var connection = db.Connect();
+            await connection.Execute("CREATE TABLE IF NOT EXISTS secrets (id INTEGER)");
+            await connection.Execute("DELETE FROM secrets");
+            await connection.Execute("INSERT INTO secrets VALUES (42)");
+        }
+
+        using var reopened = await Database.OpenLocal(config);
+        var rows = await reopened.Connect().Query("SELECT id FROM secrets");
+        var row = await rows.GetNextRow();
+        Assert.Equal(42, row!.GetInt(0));
+    }
+
+    [Fact]
+    public async Task BuildLocal()
+    {
+        using var builder = DatabaseBuilder.Local(":memory:");
+
+        var error = Assert.Throws<LibSqlException>(() => builder.Namespace("ns"));
+        Assert.Equal(LibSqlErrorKind.NotSupported, error.Kind);
+
+        var db = await builder.Build();
+        var rows = await db.Connect().Query("SELECT 1");
+        Assert.Equal(1, rows.ColumnCount());
+    }
+
+    [Fact]
+    public void BuilderProxy()
+    {
+        using var builder = DatabaseBuilder.Remote("http://localhost:8080", "");
+        builder.Proxy("http://localhost:3128").Header("traceparent", "00-abc-def-01");
+
+        var error = Assert.Throws<LibSqlException>(() => builder.Proxy("socks5://localhost:1080"));
+        Assert.Equal(LibSqlErrorKind.InvalidArgument, error.Kind);
+        error = Assert.Throws<LibSqlException>(() => builder.Header("bad header", "value"));
+        Assert.Equal(LibSqlErrorKind.InvalidArgument, error.Kind);
+    }
+
+    [Fact]
+    public async Task BuilderHeadersOverHttp2()
+    {
+        using var builder = DatabaseBuilder.Remote(url, "");
+        builder.Header("traceparent", "00-abc-def-01").Http2();
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(() => builder.Build());
+        Assert.Equal(LibSqlErrorKind.NotSupported, error.Kind);
+    }
+
+    [Fact]
+    public async Task OpenReplicaFromSnapshot()
+    {
+        foreach (var file in Directory.GetFiles(".", "snapshot-*"))
+            File.Delete(file);
+
+        ulong? frameNo;
+        using (var source = await Database.OpenSync("snapshot-source.db", url, "", true))
+        {
+            await source.Connect().Execute("CREATE TABLE IF NOT EXISTS snapshot (id)");
+            await source.Sync();
+            frameNo = source.ReplicationIndex();
+        }
+        foreach (var file in Directory.GetFiles(".", "snapshot-source.db*"))
+            File.Copy(file, file.Replace("snapshot-source.db", "snapshot-copy.db"));
+
+        using var replica = await Database.OpenWithConfig(
+            new DbConfig
+            {
+                DbPath = "snapshot-replica.db",
+                PrimaryUrl = url,
+                AuthToken = "",
+                SnapshotPath = "snapshot-copy.db",
+            }
+        );
+
+        // Resumes from the frame of the snapshot rather than from scratch
+        Assert.Equal(frameNo, replica.ReplicationIndex());
+    }
+
+    [Fact]
+    public async Task BackgroundSyncCallbackReplacedFromItself()
+    {
+        using var db = await Database.OpenWithConfig(
+            new DbConfig
+            {
+                DbPath = "sync-callback.db",
+                PrimaryUrl = url,
+                AuthToken = "",
+                SyncInterval = 1,
+            }
+        );
+        var replaced = new TaskCompletionSource<LibSqlException?>();
+        db.SetBackgroundSyncCallback(
+            (_, _) => db.SetBackgroundSyncCallback((_, error) => replaced.TrySetResult(error))
+        );
+
+        Assert.Null(await replaced.Task.WaitAsync(TimeSpan.FromSeconds(10)));
+        db.SetBackgroundSyncCallback(null);
+    }
+
+    [Fact]
+    public async Task CloseWithOpenHandles()
+    {
+        var db = await Database.OpenLocalFile(":memory:");
+        var connection = db.Connect();
+
+        var error = Assert.Throws<LibSqlException>(() => db.Close());
+        Assert.Equal(LibSqlErrorKind.Busy, error.Kind);
+        error = Assert.Throws<LibSqlException>(
+            () => db.Close(CloseMode.Wait, TimeSpan.FromMilliseconds(50))
+        );
+        Assert.Equal(LibSqlErrorKind.Timeout, error.Kind);
+
+        db.Close(CloseMode.Force);
+        error = await Assert.ThrowsAsync<LibSqlException>(() => connection.Execute("SELECT 1"));
+        Assert.Equal(LibSqlErrorKind.Misuse, error.Kind);
+        connection.Dispose();
+    }
+
+    [Fact]
+    public async Task PooledConnections()
+    {
+        using var db = await Database.OpenLocalFile("pool.db");
+        using var pool = db.CreatePool(
+            new PoolConfig
+            {
+                MinSize = 1,
+                MaxSize = 1,
+                HealthCheck = "SELECT 1",
+                AcquireTimeout = TimeSpan.FromMilliseconds(100),
+            }
+        );
+        Assert.Equal(1, pool.Stats().Idle);
+
+        var connection = await pool.Acquire();
+        await connection.Execute("CREATE TABLE IF NOT EXISTS pooled (id)");
+        var error = await Assert.ThrowsAsync<LibSqlException>(() => pool.Acquire());
+        Assert.Equal(LibSqlErrorKind.Timeout, error.Kind);
+
+        pool.Release(connection);
+        var stats = pool.Stats();
+        Assert.Equal(1, stats.Open);
+        Assert.Equal(0, stats.Acquired);
+
+        // Functions registered on a pooled connection don't outlive its release
+        connection = await pool.Acquire();
+        connection.CreateScalarFunction("pooled", 0, _ => 1L);
+        pool.Release(connection);
+        connection = await pool.Acquire();
+        await Assert.ThrowsAsync<LibSqlException>(() => connection.Query("SELECT pooled()"));
+        pool.Release(connection);
+
+        // And neither do its collations
+        connection = await pool.Acquire();
+        connection.CreateCollation("pooled", string.CompareOrdinal);
+        pool.Release(connection);
+        connection = await pool.Acquire();
+        await Assert.ThrowsAsync<LibSqlException>(
+            () => connection.Query("SELECT id FROM pooled ORDER BY id COLLATE pooled")
+        );
+        pool.Release(connection);
+
+        // Disposing an acquired connection gives it back too
+        connection = await pool.Acquire();
+        connection.Dispose();
+        connection = await pool.Acquire();
+        pool.Release(connection);
+        Assert.Equal(1, pool.Stats().Open);
+    }
+
+    [Fact]
+    public async Task PoolHealthCheck()
+    {
+        using var db = await Database.OpenLocalFile(":memory:");
+        // Temporary tables only exist on the connection creating them
+        using var pool = db.CreatePool(
+            new PoolConfig
+            {
+                MinSize = 1,
+                MaxSize = 1,
+                HealthCheck = "SELECT 1 FROM temp.healthy",
+            }
+        );
+
+        // The connection opened with the pool fails the check, a new one is handed out
+        var connection = await pool.Acquire();
+        await connection.Execute("CREATE TEMP TABLE healthy (id)");
+        pool.Release(connection);
+
+        // Which passes it once released
+        connection = await pool.Acquire();
+        await connection.Query("SELECT * FROM temp.healthy");
+        await connection.Execute("DROP TABLE temp.healthy");
+        pool.Release(connection);
+
+        // And gets replaced after failing it
+        connection = await pool.Acquire();
+        await Assert.ThrowsAsync<LibSqlException>(
+            () => connection.Query("SELECT * FROM temp.healthy")
+        );
+        pool.Release(connection);
+        var stats = pool.Stats();
+        Assert.Equal(1, stats.Open);
+        Assert.Equal(1, stats.Idle);
+    }
+
     [Fact]
     public async Task OpenRemote()
     {
@@ -164,4 +413,294 @@ public class DatabaseTest : IAsyncLifetime, IClassFixture<DatabaseContainer>
         //
         // await stmt.Query();
     }
+
+    [Fact]
+    public async Task LocalBatch()
+    {
+        var connection = memoryDb.Connect();
+        var batch = await connection.ExecuteBatch(
+            "CREATE TABLE batched (id); INSERT INTO batched VALUES (1); SELECT id FROM batched"
+        );
+
+        // One entry per statement, without rows for those returning none
+        var results = batch.ToList();
+        Assert.Equal(3, results.Count);
+        var row = await results[2]!.GetNextRow();
+        Assert.Equal(1, row!.GetInt(0));
+    }
+
+    [Fact]
+    public async Task LocalScalarFunction()
+    {
+        var connection = memoryDb.Connect();
+        connection.CreateScalarFunction(
+            "describe",
+            -1,
+            args => string.Join(", ", args.Select(arg => arg?.GetType().Name ?? "null")),
+            deterministic: true
+        );
+        connection.CreateScalarFunction(
+            "fail",
+            0,
+            _ => throw new InvalidOperationException("it failed")
+        );
+
+        var rows = await connection.Query("SELECT describe(1, 2.5, 'text', x'01', NULL)");
+        var row = await rows.GetNextRow();
+        Assert.Equal("Int64, Double, String, Byte[], null", row!.GetString(0));
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            async () => await (await connection.Query("SELECT fail()")).GetNextRow()
+        );
+        Assert.Contains("it failed", error.Message);
+
+        var remoteError = Assert.Throws<LibSqlException>(
+            () => remoteDb.Connect().CreateScalarFunction("describe", 1, args => args[0])
+        );
+        Assert.Equal(LibSqlErrorKind.NotSupported, remoteError.Kind);
+    }
+
+    [Fact]
+    public async Task TransactionScalarFunction()
+    {
+        var connection = memoryDb.Connect();
+        var transaction = await connection.Transaction(TransactionType.DEFERRED);
+
+        // Registered on the connection the transaction was created from
+        transaction.RetrieveConnection().CreateScalarFunction("answer", 0, _ => 42L);
+        await transaction.Commit();
+
+        var rows = await connection.Query("SELECT answer()");
+        var row = await rows.GetNextRow();
+        Assert.Equal(42, row!.GetInt(0));
+    }
+
+    private class Sum : IWindowAggregate
+    {
+        private long _sum;
+
+        public void Step(object?[] args) => _sum += (long)args[0]!;
+
+        public void Inverse(object?[] args) => _sum -= (long)args[0]!;
+
+        public object? Value() => _sum;
+
+        public object? Final() => _sum;
+    }
+
+    [Fact]
+    public async Task LocalAggregateFunctions()
+    {
+        var connection = memoryDb.Connect();
+        connection.CreateAggregateFunction("total_of", 1, () => new Sum());
+        connection.CreateWindowFunction("moving_sum", 1, () => new Sum());
+        await connection.Execute("CREATE TABLE readings (sensor, value)");
+        await connection.Execute(
+            "INSERT INTO readings VALUES (1, 1), (1, 2), (2, 10), (2, 20), (2, 30)"
+        );
+
+        var rows = await connection.Query(
+            "SELECT total_of(value) FROM readings GROUP BY sensor ORDER BY sensor"
+        );
+        Assert.Equal(3, (await rows.GetNextRow())!.GetInt(0));
+        Assert.Equal(60, (await rows.GetNextRow())!.GetInt(0));
+
+        rows = await connection.Query("SELECT total_of(value) FROM readings WHERE value > 100");
+        Assert.Equal(0, (await rows.GetNextRow())!.GetInt(0));
+
+        rows = await connection.Query(
+            "SELECT moving_sum(value) OVER (ORDER BY value ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) FROM readings"
+        );
+        var sums = new List<long>();
+        while (await rows.GetNextRow() is { } row)
+            sums.Add(row.GetInt(0));
+        Assert.Equal(new long[] { 1, 3, 12, 30, 50 }, sums);
+    }
+
+    [Fact]
+    public async Task LocalCollations()
+    {
+        var connection = memoryDb.Connect();
+        await connection.Execute("CREATE TABLE names (name)");
+        await connection.Execute("INSERT INTO names VALUES ('b'), ('a'), ('c')");
+        connection.CreateCollation(
+            "reverse",
+            (left, right) => string.CompareOrdinal(right, left)
+        );
+        var needed = new List<string>();
+        connection.SetCollationNeeded(name =>
+        {
+            needed.Add(name);
+            connection.CreateCollation(name, string.CompareOrdinal);
+        });
+
+        var rows = await connection.Query("SELECT name FROM names ORDER BY name COLLATE reverse");
+        Assert.Equal("c", (await rows.GetNextRow())!.GetString(0));
+
+        rows = await connection.Query("SELECT name FROM names ORDER BY name COLLATE lazy");
+        Assert.Equal("a", (await rows.GetNextRow())!.GetString(0));
+        Assert.Equal(new[] { "lazy" }, needed);
+
+        connection.SetCollationNeeded(null);
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            () => connection.Query("SELECT name FROM names ORDER BY name COLLATE other")
+        );
+        Assert.Contains("no such collation sequence", error.Message);
+    }
+
+    [Fact]
+    public async Task LocalHooks()
+    {
+        var connection = memoryDb.Connect();
+        await connection.Execute("CREATE TABLE cached (value)");
+        var changes = new List<RowChange>();
+        var allowCommit = true;
+        var rollbacks = 0;
+        connection.SetUpdateHook(changes.Add);
+        connection.SetCommitHook(() => allowCommit);
+        connection.SetRollbackHook(() => rollbacks++);
+
+        await connection.Execute("INSERT INTO cached VALUES (1)");
+        await connection.Execute("UPDATE cached SET value = 2 WHERE rowid = 1");
+        Assert.Equal(
+            new[]
+            {
+                new RowChange(UpdateOperation.Insert, "main", "cached", 1),
+                new RowChange(UpdateOperation.Update, "main", "cached", 1),
+            },
+            changes
+        );
+
+        allowCommit = false;
+        await Assert.ThrowsAsync<LibSqlException>(
+            () => connection.Execute("DELETE FROM cached WHERE rowid = 1")
+        );
+        Assert.Equal(1, rollbacks);
+        Assert.Equal(UpdateOperation.Delete, changes[2].Operation);
+
+        connection.SetUpdateHook(null);
+        connection.SetCommitHook(null);
+        // A DELETE without WHERE truncates without reporting rows, this one would report
+        await connection.Execute("INSERT INTO cached VALUES (3)");
+        await connection.Execute("DELETE FROM cached WHERE 1");
+        Assert.Equal(3, changes.Count);
+    }
+
+    [Fact]
+    public async Task LocalAuthorizer()
+    {
+        var connection = memoryDb.Connect();
+        await connection.Execute("CREATE TABLE tenants (name, secret)");
+        await connection.Execute("INSERT INTO tenants VALUES ('a', 'hunter2')");
+        connection.SetAuthorizer(context =>
+            context switch
+            {
+                { Action: AuthorizerAction.Attach } => AuthorizerResult.Deny,
+                { Action: AuthorizerAction.Pragma, Arg2: not null } => AuthorizerResult.Deny,
+                { Action: AuthorizerAction.Read, Arg1: "tenants", Arg2: "secret" } =>
+                    AuthorizerResult.Ignore,
+                _ => AuthorizerResult.Allow,
+            }
+        );
+
+        var rows = await connection.Query("SELECT name, secret FROM tenants");
+        var row = await rows.GetNextRow();
+        Assert.Equal("a", row!.GetString(0));
+        Assert.Equal(ColumnType.NULL, rows.ColumnType(row, 1));
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            () => connection.Execute("ATTACH ':memory:' AS other")
+        );
+        Assert.Equal(23, error.Code); // SQLITE_AUTH
+        error = await Assert.ThrowsAsync<LibSqlException>(
+            () => connection.Execute("PRAGMA user_version = 3")
+        );
+        Assert.Equal(23, error.Code);
+
+        connection.SetAuthorizer(null);
+        await connection.Execute("PRAGMA user_version = 3");
+    }
+
+    [Fact]
+    public async Task LocalConcurrentQueries()
+    {
+        var connection = memoryDb.Connect();
+
+        var queries = Enumerable
+            .Range(0, 20)
+            .Select(async i =>
+            {
+                var rows = await connection.Query("SELECT ? * 2", i);
+                return (await rows.GetNextRow())!.GetInt(0);
+            });
+
+        Assert.Equal(Enumerable.Range(0, 20).Select(i => i * 2L), await Task.WhenAll(queries));
+    }
+
+    [Fact]
+    public async Task LocalFailedQuery()
+    {
+        var query = memoryConnection.Query("SELECT * FROM missing");
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(() => query);
+        Assert.Equal(LibSqlErrorKind.Sqlite, error.Kind);
+        Assert.Contains("no such table", error.Message);
+        Assert.True(query.IsFaulted);
+    }
+
+    [Fact]
+    public async Task LocalConstraintError()
+    {
+        await memoryConnection.Execute("CREATE TABLE uniques (name TEXT UNIQUE)");
+        await memoryConnection.Execute("INSERT INTO uniques VALUES ('a')");
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            () => memoryConnection.Execute("INSERT INTO uniques VALUES ('a')")
+        );
+
+        Assert.Equal(LibSqlErrorKind.Sqlite, error.Kind);
+        Assert.Equal(19, error.Code); // SQLITE_CONSTRAINT
+        Assert.Equal(2067, error.ExtendedCode); // SQLITE_CONSTRAINT_UNIQUE
+    }
+
+    [Fact]
+    public async Task LocalCancelled()
+    {
+        using var cts = new CancellationTokenSource(TimeSpan.FromMilliseconds(200));
+
+        // Never ends by itself, the running statement gets interrupted
+        await Assert.ThrowsAnyAsync<OperationCanceledException>(
+            () =>
+                memoryConnection.Execute(
+                    "SELECT count(*) FROM (WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT x FROM c)",
+                    cts.Token
+                )
+        );
+
+        // The first row comes right away, fetching the next one gets interrupted
+        var rows = await memoryConnection.Query(
+            "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT x FROM c WHERE x = 1 OR x < 0"
+        );
+        Assert.NotNull(await rows.GetNextRow());
+        using var fetching = new CancellationTokenSource(TimeSpan.FromMilliseconds(200));
+        await Assert.ThrowsAnyAsync<OperationCanceledException>(
+            () => rows.GetNextRow(fetching.Token)
+        );
+    }
+
+    [Fact]
+    public async Task LocalTimeout()
+    {
+        var connection = memoryDb.Connect();
+        connection.SetDefaultTimeout(TimeSpan.FromMilliseconds(200));
+
+        var error = await Assert.ThrowsAsync<LibSqlException>(
+            () =>
+                connection.Execute(
+                    "SELECT count(*) FROM (WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT x FROM c)"
+                )
+        );
+
+        Assert.Equal(LibSqlErrorKind.Timeout, error.Kind);
+    }
 }
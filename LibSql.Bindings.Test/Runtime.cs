@@ -0,0 +1,41 @@
+using System.Runtime.CompilerServices;
+using LibSql.Bindings;
+
+namespace LibSql.Bindings.Test;
+
+internal static class RuntimeSetup
+{
+    // Runs before any test touches the library, configuring the runtime must succeed then
+    [ModuleInitializer]
+    internal static void Configure()
+    {
+        Runtime.Configure(new RuntimeConfig { WorkerThreads = 2, ThreadNamePrefix = "libsql-test" });
+    }
+}
+
+public class RuntimeTest
+{
+    [Fact]
+    public async Task ConfigureOnceStarted()
+    {
+        using var db = await Database.OpenLocalFile(":memory:");
+
+        var error = Assert.Throws<LibSqlException>(
+            () => Runtime.Configure(new RuntimeConfig { WorkerThreads = 1 })
+        );
+        Assert.Equal(LibSqlErrorKind.Misuse, error.Kind);
+    }
+
+    [Fact]
+    public void ConfigStructTooSmall()
+    {
+        // Smaller than the first version of the struct, checked before anything else
+        var raw = new RuntimeConfigRaw { StructSize = (nuint)IntPtr.Size };
+
+        var errorCode = Runtime.libsql_runtime_config(in raw, out var err);
+
+        var error = Assert.Throws<LibSqlException>(() => Utils.HandleError(errorCode, err));
+        Assert.Equal(LibSqlErrorKind.InvalidArgument, error.Kind);
+        Assert.Contains("struct_size", error.Message);
+    }
+}
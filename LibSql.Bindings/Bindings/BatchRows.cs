@@ -13,12 +13,20 @@ public partial class BatchRows : IEnumerable<Rows?>
 
     public IEnumerator<Rows?> GetEnumerator()
     {
-        var status = 0;
-        while (status == 0)
+        while (true)
         {
-            var out_rows = nint.Zero;
-            status = libsql_next_stmt_row_batchrows(_batchRowsHandle, out out_rows);
+            var errorCode = libsql_next_stmt_row_batchrows(
+                _batchRowsHandle,
+                out var out_rows,
+                out var done,
+                out var err
+            );
+            Utils.HandleError(errorCode, err);
 
+            if (done != 0)
+            {
+                yield break;
+            }
             if (out_rows == nint.Zero)
             {
                 yield return null;
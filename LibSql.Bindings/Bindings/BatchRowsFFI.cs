@@ -21,7 +21,9 @@ public partial class BatchRows
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_next_stmt_row_batchrows(
         BatchRowsHandle batchRowsHandle,
-        out IntPtr out_rows
+        out IntPtr out_rows,
+        out byte out_done,
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_free_batchrows")]
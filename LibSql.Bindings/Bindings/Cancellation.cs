@@ -0,0 +1,57 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+internal class CancelTokenHandle : LibSqlSafeHandle
+{
+    public CancelTokenHandle()
+        : base()
+    {
+        Cancellation.libsql_make_cancel_token(out var token);
+        SetHandle(token);
+    }
+
+    protected override bool ReleaseHandle()
+    {
+        Cancellation.libsql_free_cancel_token(handle);
+        return true;
+    }
+}
+
+// Links a CancellationToken to a native cancel token for the duration of a call
+internal static partial class Cancellation
+{
+    internal static async Task<T> Run<T>(
+        CancellationToken cancellationToken,
+        Func<IntPtr, Task<T>> call
+    )
+    {
+        cancellationToken.ThrowIfCancellationRequested();
+        if (!cancellationToken.CanBeCanceled)
+            return await call(IntPtr.Zero);
+
+        using var token = new CancelTokenHandle();
+        using var registration = cancellationToken.Register(() => libsql_cancel(token));
+        try
+        {
+            return await call(token.DangerousGetHandle());
+        }
+        catch (LibSqlException e) when (e.Kind == LibSqlErrorKind.Cancelled)
+        {
+            throw new OperationCanceledException(e.Message, e, cancellationToken);
+        }
+    }
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_make_cancel_token")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_make_cancel_token(out IntPtr out_token);
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_cancel")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_cancel(CancelTokenHandle token);
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_free_cancel_token")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_free_cancel_token(IntPtr token);
+}
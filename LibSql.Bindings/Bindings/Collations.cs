@@ -0,0 +1,85 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+using System.Text;
+
+namespace LibSql.Bindings;
+
+public partial class Connection
+{
+    // Registers a collation used with COLLATE name, e.g. culture-aware sorting with
+    // StringComparer.Create(culture, false).Compare. Exceptions make the texts compare equal.
+    // Only local and replica connections have collations, others throw
+    // LibSqlErrorKind.NotSupported
+    public unsafe void CreateCollation(string name, Comparison<string> compare)
+    {
+        var handle = GCHandle.Alloc(compare);
+        delegate* unmanaged[Cdecl]<IntPtr, byte*, int, byte*, int, int> onCompare = &OnCompare;
+        delegate* unmanaged[Cdecl]<IntPtr, void> onDestroy = &OnDestroy;
+        // The handle is freed by OnDestroy, also when creating the collation fails
+        var errorCode = libsql_create_collation(
+            _connection,
+            name,
+            (IntPtr)onCompare,
+            GCHandle.ToIntPtr(handle),
+            (IntPtr)onDestroy,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+    }
+
+    // Called with the name of a collation a statement uses but which isn't registered, to register
+    // it with CreateCollation. null removes it
+    public unsafe void SetCollationNeeded(Action<string>? handler)
+    {
+        var handle = handler is null ? default : GCHandle.Alloc(handler);
+        delegate* unmanaged[Cdecl]<IntPtr, IntPtr, void> onNeeded = &OnCollationNeeded;
+        delegate* unmanaged[Cdecl]<IntPtr, void> onDestroy = &OnDestroy;
+        var errorCode = libsql_set_collation_needed(
+            _connection,
+            handler is null ? IntPtr.Zero : (IntPtr)onNeeded,
+            handler is null ? IntPtr.Zero : GCHandle.ToIntPtr(handle),
+            handler is null ? IntPtr.Zero : (IntPtr)onDestroy,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static unsafe int OnCompare(
+        IntPtr userData,
+        byte* left,
+        int leftLen,
+        byte* right,
+        int rightLen
+    )
+    {
+        try
+        {
+            var compare = (Comparison<string>)GCHandle.FromIntPtr(userData).Target!;
+            return compare(
+                Encoding.UTF8.GetString(left, leftLen),
+                Encoding.UTF8.GetString(right, rightLen)
+            );
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+            return 0;
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnCollationNeeded(IntPtr userData, IntPtr name)
+    {
+        try
+        {
+            var handler = (Action<string>)GCHandle.FromIntPtr(userData).Target!;
+            handler(Marshal.PtrToStringUTF8(name)!);
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side, the statement fails without the
+            // collation
+        }
+    }
+}
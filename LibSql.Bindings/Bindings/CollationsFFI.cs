@@ -0,0 +1,32 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+public partial class Connection
+{
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_create_collation",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_create_collation(
+        ConnectionHandle conn,
+        string name,
+        IntPtr compare,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_set_collation_needed")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_collation_needed(
+        ConnectionHandle conn,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+}
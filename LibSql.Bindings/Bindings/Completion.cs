@@ -0,0 +1,67 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+// Bridges the `*_async` functions of the rust lib to tasks. The user data given to the native
+// call is a GCHandle to the TaskCompletionSource that the callback completes, from a thread of
+// the rust runtime.
+internal static unsafe class Completion
+{
+    internal delegate int Start(IntPtr callback, IntPtr userData, out IntPtr err);
+
+    internal static Task<IntPtr> HandleTask(Start start)
+    {
+        delegate* unmanaged[Cdecl]<IntPtr, IntPtr, IntPtr, void> callback = &OnHandle;
+        return Run<IntPtr>(start, (IntPtr)callback);
+    }
+
+    internal static Task<ulong> RowsChangedTask(Start start)
+    {
+        delegate* unmanaged[Cdecl]<IntPtr, ulong, IntPtr, void> callback = &OnRowsChanged;
+        return Run<ulong>(start, (IntPtr)callback);
+    }
+
+    internal static Task<Replicated> ReplicatedTask(Start start)
+    {
+        delegate* unmanaged[Cdecl]<IntPtr, Replicated, IntPtr, void> callback = &OnReplicated;
+        return Run<Replicated>(start, (IntPtr)callback);
+    }
+
+    private static Task<T> Run<T>(Start start, IntPtr callback)
+    {
+        var tcs = new TaskCompletionSource<T>(TaskCreationOptions.RunContinuationsAsynchronously);
+        var userData = GCHandle.ToIntPtr(GCHandle.Alloc(tcs));
+        var errorCode = start(callback, userData, out var err);
+        if (errorCode != 0)
+        {
+            // The callback is never invoked when the call itself fails
+            GCHandle.FromIntPtr(userData).Free();
+            Utils.HandleError(errorCode, err);
+        }
+        return tcs.Task;
+    }
+
+    private static void Complete<T>(IntPtr userData, T result, IntPtr err)
+    {
+        var gcHandle = GCHandle.FromIntPtr(userData);
+        var tcs = (TaskCompletionSource<T>)gcHandle.Target!;
+        gcHandle.Free();
+        if (err != IntPtr.Zero)
+            tcs.SetException(Utils.IntoExceptionAndFree(err));
+        else
+            tcs.SetResult(result);
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnHandle(IntPtr userData, IntPtr result, IntPtr err) =>
+        Complete(userData, result, err);
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnRowsChanged(IntPtr userData, ulong result, IntPtr err) =>
+        Complete(userData, result, err);
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnReplicated(IntPtr userData, Replicated result, IntPtr err) =>
+        Complete(userData, result, err);
+}
@@ -61,127 +61,190 @@ public partial class Connection : IDisposable
 
     public async Task<Statements> Prepare(string sql)
     {
-        return await Task.Run(() =>
-        {
-            nint err = nint.Zero;
-            nint statements = nint.Zero;
-            var errorCode = libsql_prepare(_connection, sql, out statements, out err);
-            Utils.HandleError(errorCode, err);
+        var statements = await Completion.HandleTask(
+            (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                libsql_prepare_async(_connection, sql, callback, userData, out err)
+        );
+        return new Statements(new StatementsHandle(statements));
+    }
 
-            return new Statements(new StatementsHandle(statements));
-        });
+    public Task<Rows> Query(string sql, CancellationToken cancellationToken = default)
+    {
+        return Cancellation.Run(
+            cancellationToken,
+            async token =>
+            {
+                var rows = await Completion.HandleTask(
+                    (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                        libsql_query_async(_connection, sql, token, callback, userData, out err)
+                );
+                return new Rows(new RowsHandle(rows));
+            }
+        );
     }
 
-    public async Task<Rows> Query(string sql)
+    public Task<Rows> Query(string sql, params object?[] positionalValues)
     {
-        return await Task.Run(() =>
-        {
-            nint err = nint.Zero;
-            nint rows = nint.Zero;
-            var errorCode = libsql_query(_connection, sql, out rows, out err);
-            Utils.HandleError(errorCode, err);
-            return new Rows(new RowsHandle(rows));
-        });
+        return Query(sql, positionalValues, CancellationToken.None);
     }
 
-    public async Task<Rows> Query(string sql, params object?[] positionalValues)
+    public Task<Rows> Query(
+        string sql,
+        object?[] positionalValues,
+        CancellationToken cancellationToken
+    )
     {
-        return await Task.Run(() =>
-        {
-            var posVals = new PositionalValues(positionalValues);
-            nint rows = nint.Zero;
-            IntPtr err;
-            var errorCode = libsql_query_positional(
-                _connection,
-                sql,
-                posVals._positionalValues.DangerousGetHandle(),
-                out rows,
-                out err
-            );
-            Utils.HandleError(errorCode, err);
-            return new Rows(new RowsHandle(rows));
-        });
+        return Cancellation.Run(
+            cancellationToken,
+            async token =>
+            {
+                var posVals = new PositionalValues(positionalValues);
+                var rows = await Completion.HandleTask(
+                    (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                        libsql_query_positional_async(
+                            _connection,
+                            sql,
+                            posVals._positionalValues.DangerousGetHandle(),
+                            token,
+                            callback,
+                            userData,
+                            out err
+                        )
+                );
+                return new Rows(new RowsHandle(rows));
+            }
+        );
     }
 
-    public async Task<Rows> Query(string sql, params (string, object?)[] namedValues)
+    public Task<Rows> Query(string sql, params (string, object?)[] namedValues)
     {
-        return await Task.Run(() =>
-        {
-            var namedVals = new NamedValues(namedValues);
-            nint rows = nint.Zero;
-            IntPtr err;
-            var errorCode = libsql_query_named(
-                _connection,
-                sql,
-                namedVals._namedValuesHandle.DangerousGetHandle(),
-                out rows,
-                out err
-            );
-            Utils.HandleError(errorCode, err);
-            return new Rows(new RowsHandle(rows));
-        });
+        return Query(sql, namedValues, CancellationToken.None);
     }
 
-    public async Task<BatchRows> ExecuteBatch(string sql)
+    public Task<Rows> Query(
+        string sql,
+        (string, object?)[] namedValues,
+        CancellationToken cancellationToken
+    )
     {
-        return await Task.Run(() =>
-        {
-            var err = nint.Zero;
-            var batchRows = nint.Zero;
-            var errorCode = libsql_execute_batch(_connection, sql, out batchRows, out err);
-            Utils.HandleError(errorCode, err);
-            return new BatchRows(new BatchRowsHandle(batchRows));
-        });
+        return Cancellation.Run(
+            cancellationToken,
+            async token =>
+            {
+                var namedVals = new NamedValues(namedValues);
+                var rows = await Completion.HandleTask(
+                    (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                        libsql_query_named_async(
+                            _connection,
+                            sql,
+                            namedVals._namedValuesHandle.DangerousGetHandle(),
+                            token,
+                            callback,
+                            userData,
+                            out err
+                        )
+                );
+                return new Rows(new RowsHandle(rows));
+            }
+        );
     }
 
-    public async Task<ulong> Execute(string sql)
+    public Task<BatchRows> ExecuteBatch(string sql, CancellationToken cancellationToken = default)
     {
-        return await Task.Run(() =>
-        {
-            nint err = nint.Zero;
-            ulong rowsChange = 0;
-            var errorCode = libsql_execute_none(_connection, sql, out rowsChange, out err);
-            Utils.HandleError(errorCode, err);
-            return rowsChange;
-        });
+        return Cancellation.Run(
+            cancellationToken,
+            async token =>
+            {
+                var batchRows = await Completion.HandleTask(
+                    (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                        libsql_execute_batch_async(
+                            _connection,
+                            sql,
+                            token,
+                            callback,
+                            userData,
+                            out err
+                        )
+                );
+                return new BatchRows(new BatchRowsHandle(batchRows));
+            }
+        );
     }
 
-    public async Task<ulong> Execute(string sql, params object?[] positionalValues)
+    public Task<ulong> Execute(string sql, CancellationToken cancellationToken = default)
     {
-        return await Task.Run(() =>
-        {
-            var posVals = new PositionalValues(positionalValues);
-            ulong rowChanges = 0;
-            IntPtr err;
-            var errorCode = libsql_execute_positional(
-                _connection,
-                sql,
-                posVals._positionalValues.DangerousGetHandle(),
-                out rowChanges,
-                out err
-            );
-            Utils.HandleError(errorCode, err);
-            return rowChanges;
-        });
+        return Cancellation.Run(
+            cancellationToken,
+            token =>
+                Completion.RowsChangedTask(
+                    (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                        libsql_execute_async(_connection, sql, token, callback, userData, out err)
+                )
+        );
     }
 
-    public async Task<ulong> Execute(string sql, params (string, object?)[] namedValues)
+    public Task<ulong> Execute(string sql, params object?[] positionalValues)
     {
-        return await Task.Run(() =>
-        {
-            var namedVals = new NamedValues(namedValues);
-            ulong rowChanges = 0;
-            IntPtr err;
-            var errorCode = libsql_execute_named(
-                _connection,
-                sql,
-                namedVals._namedValuesHandle.DangerousGetHandle(),
-                out rowChanges,
-                out err
-            );
-            Utils.HandleError(errorCode, err);
-            return rowChanges;
-        });
+        return Execute(sql, positionalValues, CancellationToken.None);
+    }
+
+    public Task<ulong> Execute(
+        string sql,
+        object?[] positionalValues,
+        CancellationToken cancellationToken
+    )
+    {
+        return Cancellation.Run(
+            cancellationToken,
+            token =>
+            {
+                var posVals = new PositionalValues(positionalValues);
+                return Completion.RowsChangedTask(
+                    (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                        libsql_execute_positional_async(
+                            _connection,
+                            sql,
+                            posVals._positionalValues.DangerousGetHandle(),
+                            token,
+                            callback,
+                            userData,
+                            out err
+                        )
+                );
+            }
+        );
+    }
+
+    public Task<ulong> Execute(string sql, params (string, object?)[] namedValues)
+    {
+        return Execute(sql, namedValues, CancellationToken.None);
+    }
+
+    public Task<ulong> Execute(
+        string sql,
+        (string, object?)[] namedValues,
+        CancellationToken cancellationToken
+    )
+    {
+        return Cancellation.Run(
+            cancellationToken,
+            token =>
+            {
+                var namedVals = new NamedValues(namedValues);
+                return Completion.RowsChangedTask(
+                    (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                        libsql_execute_named_async(
+                            _connection,
+                            sql,
+                            namedVals._namedValuesHandle.DangerousGetHandle(),
+                            token,
+                            callback,
+                            userData,
+                            out err
+                        )
+                );
+            }
+        );
     }
 
     public ulong Changes()
@@ -193,4 +256,12 @@ public partial class Connection : IDisposable
     {
         return libsql_last_insert_rowid(_connection);
     }
+
+    // Statements running longer fail with LibSqlErrorKind.Timeout, null removes the timeout
+    public void SetDefaultTimeout(TimeSpan? timeout)
+    {
+        var timeoutMs = timeout is null ? 0 : (ulong)Math.Max(1, timeout.Value.TotalMilliseconds);
+        var errorCode = libsql_set_default_timeout(_connection, timeoutMs, out var err);
+        Utils.HandleError(errorCode, err);
+    }
 }
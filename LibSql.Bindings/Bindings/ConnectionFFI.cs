@@ -19,6 +19,12 @@ internal class ConnectionHandle : SafeHandle
 
     public override bool IsInvalid => handle == IntPtr.Zero;
 
+    // Set once given back to its pool, so it isn't disconnected when released
+    internal void MarkReleased()
+    {
+        SetHandle(IntPtr.Zero);
+    }
+
     protected override bool ReleaseHandle()
     {
         Connection.libsql_disconnect(handle);
@@ -38,7 +44,7 @@ public partial class Connection
         SafeHandle conn,
         string path,
         string? entry_point,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_transaction_with_behavior")]
@@ -47,12 +53,12 @@ public partial class Connection
         SafeHandle conn,
         out IntPtr out_transaction,
         int transaction_behavior,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_reset")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
-    internal static partial int libsql_reset(SafeHandle conn, out IntPtr out_err_msg);
+    internal static partial int libsql_reset(SafeHandle conn, out IntPtr out_err);
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_disconnect")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
@@ -68,7 +74,21 @@ public partial class Connection
         SafeHandle conn,
         string sql,
         out IntPtr out_stmt,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_prepare_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_prepare_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -80,8 +100,24 @@ public partial class Connection
     internal static partial int libsql_query(
         SafeHandle conn,
         string sql,
+        IntPtr cancel_token,
         out IntPtr out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_query_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_query_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -94,8 +130,25 @@ public partial class Connection
         SafeHandle conn,
         string sql,
         IntPtr in_positional_values,
+        IntPtr cancel_token,
         out IntPtr out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_query_positional_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_query_positional_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr in_positional_values,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -108,8 +161,25 @@ public partial class Connection
         SafeHandle conn,
         string sql,
         IntPtr in_named_values,
+        IntPtr cancel_token,
         out IntPtr out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_query_named_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_query_named_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr in_named_values,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -121,8 +191,24 @@ public partial class Connection
     internal static partial int libsql_execute_none(
         SafeHandle conn,
         string sql,
+        IntPtr cancel_token,
         out ulong out_rows_change, // CULong we discard the 32bits platforms, although this is far from being critical
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_execute_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_execute_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -135,8 +221,25 @@ public partial class Connection
         SafeHandle conn,
         string sql,
         IntPtr in_positional_values,
+        IntPtr cancel_token,
         out ulong out_rows_change,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_execute_positional_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_execute_positional_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr in_positional_values,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -149,8 +252,25 @@ public partial class Connection
         SafeHandle conn,
         string sql,
         IntPtr in_named_values,
+        IntPtr cancel_token,
         out ulong out_rows_change,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_execute_named_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_execute_named_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr in_named_values,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -162,8 +282,24 @@ public partial class Connection
     internal static partial int libsql_execute_batch(
         SafeHandle conn,
         string sql,
+        IntPtr cancel_token,
         out IntPtr out_batch_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_execute_batch_async",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_execute_batch_async(
+        SafeHandle conn,
+        string sql,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_changes")]
@@ -177,4 +313,12 @@ public partial class Connection
     )]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial long libsql_last_insert_rowid(ConnectionHandle conn);
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_set_default_timeout")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_default_timeout(
+        ConnectionHandle conn,
+        ulong timeout_ms,
+        out IntPtr out_err
+    );
 }
@@ -0,0 +1,98 @@
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+// Null timeouts never expire, a null HealthCheck hands out idle connections without checking them
+public struct PoolConfig
+{
+    public int MinSize;
+    public int MaxSize;
+    // Idle connections above MinSize are disconnected after this long
+    public TimeSpan? IdleTimeout;
+    // Query run on an idle connection before handing it out, it's replaced if it fails
+    public string? HealthCheck;
+    // How long Acquire waits when MaxSize connections are acquired
+    public TimeSpan? AcquireTimeout;
+
+    internal PoolConfigRaw GetRaw()
+    {
+        return new PoolConfigRaw
+        {
+            StructSize = (nuint)Marshal.SizeOf<PoolConfigRaw>(),
+            MinSize = MinSize,
+            MaxSize = MaxSize,
+            IdleTimeoutMs = (ulong)(IdleTimeout?.TotalMilliseconds ?? 0),
+            HealthCheck = Marshal.StringToCoTaskMemUTF8(HealthCheck),
+            AcquireTimeoutMs = (ulong)(AcquireTimeout?.TotalMilliseconds ?? 0),
+        };
+    }
+}
+
+[StructLayout(LayoutKind.Sequential)]
+public struct PoolStats
+{
+    // Connections alive, idle or acquired
+    public int Open;
+    public int Idle;
+    public int Acquired;
+}
+
+// Connections of a database kept open between uses. Acquired connections are given back with
+// Release, disposing one releases it too, those still acquired when the pool is disposed become
+// regular connections
+public partial class ConnectionPool : IDisposable
+{
+    internal PoolHandle _pool;
+
+    internal ConnectionPool(PoolHandle pool)
+    {
+        _pool = pool;
+    }
+
+    public void Dispose()
+    {
+        _pool.Dispose();
+    }
+
+    public Task<Connection> Acquire(CancellationToken cancellationToken = default)
+    {
+        return Cancellation.Run(
+            cancellationToken,
+            token =>
+                Task.Run(() =>
+                {
+                    var errorCode = libsql_pool_acquire(_pool, token, out var conn, out var err);
+                    Utils.HandleError(errorCode, err);
+                    return new Connection(new ConnectionHandle(conn));
+                })
+        );
+    }
+
+    // Rolls back a transaction left open and resets the connection, which can't be used afterwards
+    public void Release(Connection connection)
+    {
+        var handle = connection._connection;
+        var added = false;
+        handle.DangerousAddRef(ref added);
+        try
+        {
+            var errorCode = libsql_pool_release(_pool, handle.DangerousGetHandle(), out var err);
+            // The native handle is given back even if this fails
+            handle.MarkReleased();
+            Utils.HandleError(errorCode, err);
+        }
+        finally
+        {
+            if (added)
+                handle.DangerousRelease();
+            handle.Dispose();
+        }
+    }
+
+    public PoolStats Stats()
+    {
+        var errorCode = libsql_pool_stats(_pool, out var stats, out var err);
+        Utils.HandleError(errorCode, err);
+        return stats;
+    }
+}
@@ -0,0 +1,68 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+[StructLayout(LayoutKind.Sequential)]
+internal struct PoolConfigRaw
+{
+    public nuint StructSize;
+    public int MinSize;
+    public int MaxSize;
+    public ulong IdleTimeoutMs;
+    public IntPtr HealthCheck;
+    public ulong AcquireTimeoutMs;
+}
+
+internal class PoolHandle : LibSqlSafeHandle
+{
+    public PoolHandle(nint ptr)
+        : base(ptr) { }
+
+    protected override bool ReleaseHandle()
+    {
+        ConnectionPool.libsql_pool_free(handle);
+        return true;
+    }
+}
+
+public partial class ConnectionPool
+{
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_pool_new")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_pool_new(
+        DatabaseHandle db,
+        in PoolConfigRaw config,
+        out IntPtr out_pool,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_pool_acquire")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_pool_acquire(
+        PoolHandle pool,
+        IntPtr cancel_token,
+        out IntPtr out_conn,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_pool_release")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_pool_release(
+        PoolHandle pool,
+        IntPtr conn,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_pool_stats")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_pool_stats(
+        PoolHandle pool,
+        out PoolStats out_stats,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_pool_free")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_pool_free(IntPtr pool);
+}
@@ -1,3 +1,4 @@
+using System.Runtime.CompilerServices;
 using System.Runtime.InteropServices;
 
 namespace LibSql.Bindings;
@@ -8,21 +9,93 @@ public struct DbConfig
     public string? PrimaryUrl;
     public string? AuthToken;
     public bool ReadYourWrites;
-    public string? EncryptionKey;
+    // Raw key, its length must match the cipher (32 bytes for Aes256Cbc)
+    public byte[]? EncryptionKey;
+    public Cipher Cipher;
     public int SyncInterval;
     public bool WithWebpki;
+    // http://host:port of an HTTP CONNECT proxy
+    public string? ProxyUrl;
+    // Sent with every request, e.g. tracing headers
+    public IReadOnlyDictionary<string, string>? Headers;
+    // Copy of a closed replica (its file and the metadata files next to it) to seed DbPath with
+    // when it doesn't exist yet, the replica then resumes syncing from the snapshot's frame
+    public string? SnapshotPath;
 
     internal DbConfigRaw GetRaw()
     {
         return new DbConfigRaw
         {
+            StructSize = (nuint)Marshal.SizeOf<DbConfigRaw>(),
             DbPath = Marshal.StringToCoTaskMemUTF8(DbPath),
             PrimaryUrl = Marshal.StringToCoTaskMemUTF8(PrimaryUrl),
             AuthToken = Marshal.StringToCoTaskMemUTF8(AuthToken),
             ReadYourWrites = (byte)(ReadYourWrites ? 1 : 0),
-            EncryptionKey = Marshal.StringToCoTaskMemUTF8(EncryptionKey),
+            EncryptionKey = Utils.BytesToCoTaskMem(EncryptionKey),
+            EncryptionKeyLen = EncryptionKey?.Length ?? 0,
+            Cipher = (int)Cipher,
             SyncInterval = SyncInterval,
             WithWebpki = (byte)(WithWebpki ? 1 : 0),
+            ProxyUrl = Marshal.StringToCoTaskMemUTF8(ProxyUrl),
+            Headers = Utils.HeadersToCoTaskMem(Headers),
+            HeadersLen = Headers?.Count ?? 0,
+            SnapshotPath = Marshal.StringToCoTaskMemUTF8(SnapshotPath),
+        };
+    }
+}
+
+[Flags]
+public enum OpenFlags
+{
+    // Read-write and create the file if needed
+    Default = 0,
+    ReadOnly = 0x1,
+    ReadWrite = 0x2,
+    Create = 0x4,
+    NoMutex = 0x8000,
+}
+
+// How Database.Close handles the connections, pools, transactions, statements and rows still open
+public enum CloseMode
+{
+    // Throws a LibSqlException of kind Busy listing them
+    FailIfBusy = 0,
+    // Waits for them to be disposed
+    Wait = 1,
+    // Closes anyway, using them afterwards throws and they can only be disposed
+    Force = 2,
+    // The database is dropped with the last of them, used when disposing
+    Defer = 3,
+}
+
+// Default picks the native default cipher
+public enum Cipher
+{
+    Default = 0,
+    Aes256Cbc = 1,
+}
+
+public struct LocalConfig
+{
+    public string? DbPath;
+    // Raw key, its length must match the cipher (32 bytes for Aes256Cbc)
+    public byte[]? EncryptionKey;
+    public Cipher Cipher;
+    public OpenFlags Flags;
+    // DbPath is ignored, it can't be encrypted nor opened with flags
+    public bool InMemory;
+
+    internal LocalConfigRaw GetRaw()
+    {
+        return new LocalConfigRaw
+        {
+            StructSize = (nuint)Marshal.SizeOf<LocalConfigRaw>(),
+            DbPath = Marshal.StringToCoTaskMemUTF8(DbPath),
+            EncryptionKey = Utils.BytesToCoTaskMem(EncryptionKey),
+            EncryptionKeyLen = EncryptionKey?.Length ?? 0,
+            Cipher = (int)Cipher,
+            Flags = (int)Flags,
+            InMemory = (byte)(InMemory ? 1 : 0),
         };
     }
 }
@@ -30,17 +103,52 @@ public struct DbConfig
 [StructLayout(LayoutKind.Sequential)]
 public unsafe struct Replicated
 {
-    public int FrameNo;
-    public int FramesSynced;
+    // 0 while HasFrameNo is false
+    public ulong FrameNo;
+    public ulong FramesSynced;
+    private byte _hasFrameNo;
+
+    // False when the replica didn't receive any frame yet
+    public bool HasFrameNo => _hasFrameNo != 0;
+}
+
+// Bytes received from the primary so far, Frames is only known once Done
+[StructLayout(LayoutKind.Sequential)]
+public struct SyncProgress
+{
+    public ulong Bytes;
+    public ulong Frames;
+    private byte _done;
+
+    public bool Done => _done != 0;
+}
+
+[StructLayout(LayoutKind.Sequential)]
+internal struct SyncStatusRaw
+{
+    public byte Periodic;
+    public byte Syncing;
+    public ulong LastSuccessMs;
+    public ulong LastErrorMs;
+    public IntPtr LastError;
 }
 
+public record SyncStatus(
+    // The periodic sync of the sync interval is scheduled
+    bool Periodic,
+    bool Syncing,
+    DateTimeOffset? LastSuccess,
+    DateTimeOffset? LastErrorTime,
+    string? LastError
+);
+
 // We are specifying the call conv, due to windows defautl to stdconv, havent check
 
 public partial class Database : IDisposable
 {
     internal DatabaseHandle _database;
 
-    private Database(DatabaseHandle database)
+    internal Database(DatabaseHandle database)
     {
         _database = database;
     }
@@ -50,17 +158,151 @@ public partial class Database : IDisposable
         _database.Dispose();
     }
 
-    public async Task<Replicated> Sync()
+    // Called from a native thread after each periodic sync of the sync interval, with the error
+    // when it failed. It can replace or remove itself. null removes it
+    public unsafe void SetBackgroundSyncCallback(Action<Replicated, LibSqlException?>? callback)
     {
-        return await Task.Run(() =>
+        var handle = callback is null ? default : GCHandle.Alloc(callback);
+        delegate* unmanaged[Cdecl]<IntPtr, Replicated, IntPtr, void> onSync = &OnBackgroundSync;
+        delegate* unmanaged[Cdecl]<IntPtr, void> onDestroy = &Connection.OnDestroy;
+        // The handle is freed by OnDestroy once the callback is replaced and done running, also
+        // when setting it fails
+        var errorCode = libsql_set_sync_callback(
+            _database,
+            callback is null ? IntPtr.Zero : (IntPtr)onSync,
+            callback is null ? IntPtr.Zero : GCHandle.ToIntPtr(handle),
+            callback is null ? IntPtr.Zero : (IntPtr)onDestroy,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnBackgroundSync(IntPtr userData, Replicated replicated, IntPtr err)
+    {
+        try
         {
-            IntPtr err;
-            Replicated replicate;
-            var errorCode = libsql_sync(_database, out replicate, out err);
-            Utils.HandleError(errorCode, err);
+            var error = err == IntPtr.Zero ? null : Utils.IntoExceptionAndFree(err);
+            var target = GCHandle.FromIntPtr(userData).Target!;
+            ((Action<Replicated, LibSqlException?>)target)(replicated, error);
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+        }
+    }
 
-            return replicate;
-        });
+    public Task<Replicated> Sync(CancellationToken cancellationToken = default)
+    {
+        return Cancellation.Run(
+            cancellationToken,
+            async token =>
+            {
+                // The database is used until the callback runs, keep it from being released meanwhile
+                var added = false;
+                _database.DangerousAddRef(ref added);
+                try
+                {
+                    return await Completion.ReplicatedTask(
+                        (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                            libsql_sync_async(_database, token, callback, userData, out err)
+                    );
+                }
+                finally
+                {
+                    if (added)
+                        _database.DangerousRelease();
+                }
+            }
+        );
+    }
+
+    // Blocks a thread pool thread for the whole sync, progress is reported from a native thread
+    public Task<Replicated> Sync(
+        IProgress<SyncProgress> progress,
+        CancellationToken cancellationToken = default
+    )
+    {
+        return Cancellation.Run(
+            cancellationToken,
+            token =>
+                Task.Run(() =>
+                {
+                    var handle = GCHandle.Alloc(progress);
+                    try
+                    {
+                        unsafe
+                        {
+                            delegate* unmanaged[Cdecl]<IntPtr, SyncProgress*, void> callback =
+                                &OnSyncProgress;
+                            var errorCode = libsql_sync_with_progress(
+                                _database,
+                                token,
+                                (IntPtr)callback,
+                                GCHandle.ToIntPtr(handle),
+                                out var replicated,
+                                out var err
+                            );
+                            Utils.HandleError(errorCode, err);
+                            return replicated;
+                        }
+                    }
+                    finally
+                    {
+                        handle.Free();
+                    }
+                })
+        );
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static unsafe void OnSyncProgress(IntPtr userData, SyncProgress* progress)
+    {
+        try
+        {
+            var target = (IProgress<SyncProgress>)GCHandle.FromIntPtr(userData).Target!;
+            target.Report(*progress);
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+        }
+    }
+
+    // Only remote replicas and synced databases keep it, others throw LibSqlErrorKind.NotSupported
+    public SyncStatus GetSyncStatus()
+    {
+        var errorCode = libsql_sync_status(_database, out var raw, out var err);
+        Utils.HandleError(errorCode, err);
+        string? lastError = null;
+        if (raw.LastError != IntPtr.Zero)
+        {
+            lastError = Marshal.PtrToStringUTF8(raw.LastError);
+            Utils.libsql_free_string(raw.LastError);
+        }
+        return new SyncStatus(
+            raw.Periodic != 0,
+            raw.Syncing != 0,
+            FromUnixMs(raw.LastSuccessMs),
+            FromUnixMs(raw.LastErrorMs),
+            lastError
+        );
+    }
+
+    private static DateTimeOffset? FromUnixMs(ulong ms) =>
+        ms == 0 ? null : DateTimeOffset.FromUnixTimeMilliseconds((long)ms);
+
+    // Null when the replica didn't receive any frame yet
+    public ulong? ReplicationIndex()
+    {
+        var errorCode = libsql_replication_index(
+            _database,
+            out var frameNo,
+            out var hasFrameNo,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+        return hasFrameNo != 0 ? frameNo : null;
     }
 
     public static async Task<Database> OpenSync(
@@ -68,7 +310,7 @@ public partial class Database : IDisposable
         string primaryUrl,
         string authToken,
         bool readYourWrites,
-        string? encryptionKey = null
+        byte[]? encryptionKey = null
     )
     {
         return await OpenWithConfig(
@@ -90,7 +332,7 @@ public partial class Database : IDisposable
         string primaryUrl,
         string authToken,
         bool readYourWrites,
-        string encryptionKey
+        byte[] encryptionKey
     )
     {
         return await OpenWithConfig(
@@ -119,23 +361,45 @@ public partial class Database : IDisposable
         });
     }
 
+    public static async Task<Database> OpenLocal(LocalConfig config)
+    {
+        return await Task.Run(() =>
+        {
+            var configRaw = config.GetRaw();
+            IntPtr db;
+            IntPtr err;
+            var errorCode = libsql_open_local_with_config(in configRaw, out db, out err);
+            Marshal.FreeCoTaskMem(configRaw.DbPath);
+            Marshal.FreeCoTaskMem(configRaw.EncryptionKey);
+            Utils.HandleError(errorCode, err);
+            return new Database(new DatabaseHandle(db));
+        });
+    }
+
     public static async Task<Database> OpenRemote(
         string url,
         string authToken,
-        bool withWebpki = false
+        bool withWebpki = false,
+        string? proxyUrl = null,
+        IReadOnlyDictionary<string, string>? headers = null
     )
     {
         return await Task.Run(() =>
         {
             IntPtr db;
             IntPtr err;
+            var headersRaw = Utils.HeadersToCoTaskMem(headers);
             var errorCode = libsql_open_remote(
                 url,
                 authToken,
                 withWebpki,
+                proxyUrl,
+                headersRaw,
+                headers?.Count ?? 0,
                 out db,
                 out err
             );
+            Utils.FreeHeaders(headersRaw, headers?.Count ?? 0);
             Utils.HandleError(errorCode, err);
             return new Database(new DatabaseHandle(db));
         });
@@ -148,11 +412,14 @@ public partial class Database : IDisposable
             var configRaw = config.GetRaw();
             IntPtr db;
             IntPtr err;
-            var errorCode = libsql_open_sync_with_config(configRaw, out db, out err);
+            var errorCode = libsql_open_sync_with_config(in configRaw, out db, out err);
             Marshal.FreeCoTaskMem(configRaw.DbPath);
             Marshal.FreeCoTaskMem(configRaw.PrimaryUrl);
             Marshal.FreeCoTaskMem(configRaw.AuthToken);
             Marshal.FreeCoTaskMem(configRaw.EncryptionKey);
+            Marshal.FreeCoTaskMem(configRaw.ProxyUrl);
+            Utils.FreeHeaders(configRaw.Headers, configRaw.HeadersLen);
+            Marshal.FreeCoTaskMem(configRaw.SnapshotPath);
             Utils.HandleError(errorCode, err);
             return new Database(new DatabaseHandle(db));
         });
@@ -167,8 +434,49 @@ public partial class Database : IDisposable
         return new Connection(new ConnectionHandle(conn));
     }
 
-    private void Close()
+    // Opens MinSize connections right away
+    public ConnectionPool CreatePool(PoolConfig config)
     {
-        _database.Close();
+        var raw = config.GetRaw();
+        try
+        {
+            var errorCode = ConnectionPool.libsql_pool_new(
+                _database,
+                in raw,
+                out var pool,
+                out var err
+            );
+            Utils.HandleError(errorCode, err);
+            return new ConnectionPool(new PoolHandle(pool));
+        }
+        finally
+        {
+            Marshal.FreeCoTaskMem(raw.HealthCheck);
+        }
+    }
+
+    // Throws if the database can't be closed, it stays usable then. A null timeout waits for as long
+    // as it takes in CloseMode.Wait
+    public void Close(CloseMode mode = CloseMode.FailIfBusy, TimeSpan? timeout = null)
+    {
+        var added = false;
+        _database.DangerousAddRef(ref added);
+        try
+        {
+            var errorCode = libsql_close(
+                _database.DangerousGetHandle(),
+                (int)mode,
+                (ulong)(timeout?.TotalMilliseconds ?? 0),
+                out var err
+            );
+            Utils.HandleError(errorCode, err);
+            _database.MarkClosed();
+        }
+        finally
+        {
+            if (added)
+                _database.DangerousRelease();
+        }
+        _database.Dispose();
     }
 }
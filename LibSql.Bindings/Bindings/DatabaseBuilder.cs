@@ -0,0 +1,229 @@
+using System.Text;
+
+namespace LibSql.Bindings;
+
+// Opens any kind of database. Setting an option the kind of database doesn't support throws a
+// LibSqlException with LibSqlErrorKind.NotSupported.
+public partial class DatabaseBuilder : IDisposable
+{
+    internal BuilderHandle _builder;
+
+    private DatabaseBuilder(BuilderHandle builder)
+    {
+        _builder = builder;
+    }
+
+    public void Dispose()
+    {
+        _builder.Dispose();
+    }
+
+    // dbPath may be ":memory:"
+    public static DatabaseBuilder Local(string dbPath)
+    {
+        var errorCode = libsql_builder_new_local(dbPath, out var builder, out var err);
+        Utils.HandleError(errorCode, err);
+        return new DatabaseBuilder(new BuilderHandle(builder));
+    }
+
+    public static DatabaseBuilder Remote(string url, string authToken)
+    {
+        var errorCode = libsql_builder_new_remote(url, authToken, out var builder, out var err);
+        Utils.HandleError(errorCode, err);
+        return new DatabaseBuilder(new BuilderHandle(builder));
+    }
+
+    public static DatabaseBuilder RemoteReplica(string dbPath, string url, string authToken)
+    {
+        var errorCode = libsql_builder_new_remote_replica(
+            dbPath,
+            url,
+            authToken,
+            out var builder,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+        return new DatabaseBuilder(new BuilderHandle(builder));
+    }
+
+    // Local database accepting writes while offline, pushed to the remote one on sync
+    public static DatabaseBuilder Synced(string dbPath, string url, string authToken)
+    {
+        var errorCode = libsql_builder_new_synced(
+            dbPath,
+            url,
+            authToken,
+            out var builder,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+        return new DatabaseBuilder(new BuilderHandle(builder));
+    }
+
+    public DatabaseBuilder Namespace(string ns)
+    {
+        var errorCode = libsql_builder_set_namespace(_builder, ns, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    public DatabaseBuilder Version(string version)
+    {
+        var errorCode = libsql_builder_set_version(_builder, version, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    public DatabaseBuilder WithWebpki(bool withWebpki = true)
+    {
+        var errorCode = libsql_builder_set_webpki(_builder, withWebpki, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // Trust the certificate authorities of the operating system
+    public DatabaseBuilder NativeRoots(bool nativeRoots = true)
+    {
+        var errorCode = libsql_builder_set_native_roots(_builder, nativeRoots, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // PEM encoded certificates, e.g. an internal CA. Can be called several times
+    public unsafe DatabaseBuilder AddRootCertificates(string pem)
+    {
+        var bytes = Encoding.UTF8.GetBytes(pem);
+        fixed (byte* pemPtr = bytes)
+        {
+            var errorCode = libsql_builder_add_root_certs(
+                _builder,
+                (IntPtr)pemPtr,
+                bytes.Length,
+                out var err
+            );
+            Utils.HandleError(errorCode, err);
+        }
+        return this;
+    }
+
+    // PEM encoded certificate chain and private key for mTLS
+    public unsafe DatabaseBuilder ClientCertificate(string certPem, string keyPem)
+    {
+        var cert = Encoding.UTF8.GetBytes(certPem);
+        var key = Encoding.UTF8.GetBytes(keyPem);
+        fixed (byte* certPtr = cert)
+        fixed (byte* keyPtr = key)
+        {
+            var errorCode = libsql_builder_set_client_cert(
+                _builder,
+                (IntPtr)certPtr,
+                cert.Length,
+                (IntPtr)keyPtr,
+                key.Length,
+                out var err
+            );
+            Utils.HandleError(errorCode, err);
+        }
+        return this;
+    }
+
+    public DatabaseBuilder Http2(bool http2 = true)
+    {
+        var errorCode = libsql_builder_set_http2(_builder, http2, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // Accepts any server certificate, only meant for local test servers
+    public DatabaseBuilder SkipTlsVerification(bool skip = true)
+    {
+        var errorCode = libsql_builder_set_skip_tls_verification(_builder, skip, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // Tunnel through the HTTP CONNECT proxy at http://host:port, null connects directly
+    public DatabaseBuilder Proxy(string? url)
+    {
+        var errorCode = libsql_builder_set_proxy(_builder, url, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // Sent with every request, e.g. tracing headers. Can be called several times, not with Http2
+    public DatabaseBuilder Header(string name, string value)
+    {
+        var errorCode = libsql_builder_add_header(_builder, name, value, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // Seeds a remote replica from a copy of a closed one when its path doesn't exist yet
+    public DatabaseBuilder Snapshot(string? snapshotPath)
+    {
+        var errorCode = libsql_builder_set_snapshot(_builder, snapshotPath, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // null disables the periodic sync
+    public DatabaseBuilder SyncInterval(TimeSpan? interval)
+    {
+        var intervalMs = interval is null ? 0 : (ulong)Math.Max(1, interval.Value.TotalMilliseconds);
+        var errorCode = libsql_builder_set_sync_interval(_builder, intervalMs, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    public DatabaseBuilder ReadYourWrites(bool readYourWrites = true)
+    {
+        var errorCode = libsql_builder_set_read_your_writes(_builder, readYourWrites, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    public DatabaseBuilder RemoteWrites(bool remoteWrites = true)
+    {
+        var errorCode = libsql_builder_set_remote_writes(_builder, remoteWrites, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // Raw key, its length must match the cipher (32 bytes for Aes256Cbc)
+    public unsafe DatabaseBuilder Encryption(byte[] key, Cipher cipher = Cipher.Default)
+    {
+        if (key.Length == 0)
+            throw new ArgumentException("The encryption key can't be empty", nameof(key));
+
+        fixed (byte* keyPtr = key)
+        {
+            var errorCode = libsql_builder_set_encryption(
+                _builder,
+                (IntPtr)keyPtr,
+                key.Length,
+                (int)cipher,
+                out var err
+            );
+            Utils.HandleError(errorCode, err);
+        }
+        return this;
+    }
+
+    public DatabaseBuilder Flags(OpenFlags flags)
+    {
+        var errorCode = libsql_builder_set_flags(_builder, (int)flags, out var err);
+        Utils.HandleError(errorCode, err);
+        return this;
+    }
+
+    // The builder can be reused afterwards
+    public async Task<Database> Build()
+    {
+        return await Task.Run(() =>
+        {
+            var errorCode = libsql_builder_build(_builder, out var db, out var err);
+            Utils.HandleError(errorCode, err);
+            return new Database(new DatabaseHandle(db));
+        });
+    }
+}
@@ -0,0 +1,239 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+internal class BuilderHandle : LibSqlSafeHandle
+{
+    public BuilderHandle(nint ptr)
+        : base(ptr) { }
+
+    protected override bool ReleaseHandle()
+    {
+        DatabaseBuilder.libsql_free_builder(handle);
+        return true;
+    }
+}
+
+public partial class DatabaseBuilder
+{
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_new_local",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_new_local(
+        string path,
+        out IntPtr out_builder,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_new_remote",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_new_remote(
+        string url,
+        string auth_token,
+        out IntPtr out_builder,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_new_remote_replica",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_new_remote_replica(
+        string path,
+        string url,
+        string auth_token,
+        out IntPtr out_builder,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_new_synced",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_new_synced(
+        string path,
+        string url,
+        string auth_token,
+        out IntPtr out_builder,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_set_namespace",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_namespace(
+        BuilderHandle builder,
+        string ns,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_set_version",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_version(
+        BuilderHandle builder,
+        string version,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_webpki")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_webpki(
+        BuilderHandle builder,
+        [MarshalAs(UnmanagedType.U1)] bool with_webpki,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_native_roots")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_native_roots(
+        BuilderHandle builder,
+        [MarshalAs(UnmanagedType.U1)] bool native_roots,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_add_root_certs")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_add_root_certs(
+        BuilderHandle builder,
+        IntPtr pem,
+        int pem_len,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_client_cert")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_client_cert(
+        BuilderHandle builder,
+        IntPtr cert_pem,
+        int cert_pem_len,
+        IntPtr key_pem,
+        int key_pem_len,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_http2")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_http2(
+        BuilderHandle builder,
+        [MarshalAs(UnmanagedType.U1)] bool http2,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_skip_tls_verification")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_skip_tls_verification(
+        BuilderHandle builder,
+        [MarshalAs(UnmanagedType.U1)] bool skip,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_sync_interval")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_sync_interval(
+        BuilderHandle builder,
+        ulong interval_ms,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_read_your_writes")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_read_your_writes(
+        BuilderHandle builder,
+        [MarshalAs(UnmanagedType.U1)] bool read_your_writes,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_set_proxy",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_proxy(
+        BuilderHandle builder,
+        string? url,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_add_header",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_add_header(
+        BuilderHandle builder,
+        string name,
+        string value,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_builder_set_snapshot",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_snapshot(
+        BuilderHandle builder,
+        string? snapshot_path,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_remote_writes")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_remote_writes(
+        BuilderHandle builder,
+        [MarshalAs(UnmanagedType.U1)] bool remote_writes,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_encryption")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_encryption(
+        BuilderHandle builder,
+        IntPtr key,
+        int key_len,
+        int cipher,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_set_flags")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_set_flags(
+        BuilderHandle builder,
+        int flags,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_builder_build")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_builder_build(
+        BuilderHandle builder,
+        out IntPtr out_db,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_free_builder")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_free_builder(IntPtr builder);
+}
@@ -6,13 +6,34 @@ namespace LibSql.Bindings;
 [StructLayout(LayoutKind.Sequential)]
 public unsafe struct DbConfigRaw
 {
+    // Size of this struct, lets the native side tell which fields it knows about
+    public nuint StructSize;
     public IntPtr DbPath;
     public IntPtr PrimaryUrl;
     public IntPtr AuthToken;
     public byte ReadYourWrites;
     public IntPtr EncryptionKey;
+    public int EncryptionKeyLen;
+    public int Cipher;
     public int SyncInterval;
     public byte WithWebpki;
+    public IntPtr ProxyUrl;
+    public IntPtr Headers;
+    public int HeadersLen;
+    public IntPtr SnapshotPath;
+}
+
+[StructLayout(LayoutKind.Sequential)]
+public unsafe struct LocalConfigRaw
+{
+    // Size of this struct, lets the native side tell which fields it knows about
+    public nuint StructSize;
+    public IntPtr DbPath;
+    public IntPtr EncryptionKey;
+    public int EncryptionKeyLen;
+    public int Cipher;
+    public int Flags;
+    public byte InMemory;
 }
 
 internal class DatabaseHandle : SafeHandle
@@ -25,10 +46,20 @@ internal class DatabaseHandle : SafeHandle
 
     public override bool IsInvalid => IntPtr.Zero == handle;
 
+    // Set once closed explicitly, so the handle isn't closed again when released
+    internal void MarkClosed()
+    {
+        SetHandle(IntPtr.Zero);
+    }
+
     protected override bool ReleaseHandle()
     {
-        Database.libsql_close(handle);
-        return true;
+        // Connections, statements or rows may outlive the database object, it's dropped with the
+        // last of them
+        var errorCode = Database.libsql_close(handle, (int)CloseMode.Defer, 0, out var err);
+        if (errorCode != 0 && err != IntPtr.Zero)
+            Utils.libsql_free_error(err);
+        return errorCode == 0;
     }
 }
 
@@ -42,8 +73,57 @@ public partial class Database
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_sync(
         DatabaseHandle db,
+        IntPtr cancel_token,
+        out Replicated out_replicated,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_sync_async")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_sync_async(
+        DatabaseHandle db,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_sync_with_progress")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_sync_with_progress(
+        DatabaseHandle db,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
         out Replicated out_replicated,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_sync_status")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_sync_status(
+        DatabaseHandle db,
+        out SyncStatusRaw out_status,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_set_sync_callback")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_sync_callback(
+        DatabaseHandle db,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_replication_index")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_replication_index(
+        DatabaseHandle db,
+        out ulong out_frame_no,
+        out byte out_has_frame_no,
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -54,7 +134,7 @@ public partial class Database
     internal static partial int libsql_open_file(
         string url,
         out IntPtr out_db,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -67,27 +147,43 @@ public partial class Database
         string url,
         string auth_token,
         [MarshalAs(UnmanagedType.U1)] bool with_webpki,
+        string? proxy_url,
+        IntPtr headers,
+        int headers_len,
         out IntPtr out_db,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_open_sync_with_config")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_open_sync_with_config(
-        DbConfigRaw config,
+        in DbConfigRaw config,
+        out IntPtr out_db,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_open_local_with_config")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_open_local_with_config(
+        in LocalConfigRaw config,
         out IntPtr out_db,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_close")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
-    internal static partial void libsql_close(IntPtr db);
+    internal static partial int libsql_close(
+        IntPtr db,
+        int mode,
+        ulong timeout_ms,
+        out IntPtr out_err
+    );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_connect")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_connect(
         DatabaseHandle db,
         out IntPtr out_conn,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 }
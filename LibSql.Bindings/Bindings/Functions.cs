@@ -0,0 +1,301 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+using System.Text;
+
+namespace LibSql.Bindings;
+
+// State of one group of an aggregate function, created on its first row
+public interface IAggregate
+{
+    void Step(object?[] args);
+
+    // Called once per group, on a new instance if the group has no rows
+    object? Final();
+}
+
+// State of a window, which rows enter with Step and leave with Inverse
+public interface IWindowAggregate : IAggregate
+{
+    void Inverse(object?[] args);
+
+    // Result of the window as it is, called before more rows enter or leave it
+    object? Value();
+}
+
+public partial class Connection
+{
+    // Registers a function called on the thread running the statement. Its arguments are long,
+    // double, string, byte[] or null, and so is its result, which may also be an int, float or
+    // bool. Exceptions fail the statement with their message. An argCount of -1 takes any number
+    // of arguments. Only local and replica connections have functions, others throw
+    // LibSqlErrorKind.NotSupported
+    public unsafe void CreateScalarFunction(
+        string name,
+        int argCount,
+        Func<object?[], object?> function,
+        bool deterministic = false
+    )
+    {
+        var handle = GCHandle.Alloc(function);
+        delegate* unmanaged[Cdecl]<IntPtr, IntPtr, int, ValueRaw*, void> onCall = &OnScalarCall;
+        delegate* unmanaged[Cdecl]<IntPtr, void> onDestroy = &OnDestroy;
+        // The handle is freed by OnDestroy, also when creating the function fails
+        var errorCode = libsql_create_scalar_function(
+            _connection,
+            name,
+            argCount,
+            deterministic,
+            (IntPtr)onCall,
+            GCHandle.ToIntPtr(handle),
+            (IntPtr)onDestroy,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static unsafe void OnScalarCall(IntPtr userData, IntPtr ctx, int argc, ValueRaw* argv)
+    {
+        try
+        {
+            var function = (Func<object?[], object?>)GCHandle.FromIntPtr(userData).Target!;
+            SetResult(ctx, function(ReadArgs(argc, argv)));
+        }
+        catch (Exception e)
+        {
+            // Exceptions can't unwind into the native side
+            SetError(ctx, e.Message);
+        }
+    }
+
+    // Registers an aggregate function, create makes the state of each group. Arguments and
+    // results are those of scalar functions
+    public void CreateAggregateFunction(
+        string name,
+        int argCount,
+        Func<IAggregate> create,
+        bool deterministic = false
+    )
+    {
+        CreateAggregate(name, argCount, create, deterministic, window: false);
+    }
+
+    // Registers an aggregate function also usable as a window function, with OVER
+    public void CreateWindowFunction(
+        string name,
+        int argCount,
+        Func<IWindowAggregate> create,
+        bool deterministic = false
+    )
+    {
+        CreateAggregate(name, argCount, create, deterministic, window: true);
+    }
+
+    private unsafe void CreateAggregate(
+        string name,
+        int argCount,
+        Func<IAggregate> create,
+        bool deterministic,
+        bool window
+    )
+    {
+        var handle = GCHandle.Alloc(create);
+        delegate* unmanaged[Cdecl]<IntPtr, IntPtr, IntPtr*, int, ValueRaw*, void> onStep =
+            &OnAggregateStep;
+        delegate* unmanaged[Cdecl]<IntPtr, IntPtr, IntPtr, void> onFinal = &OnAggregateFinal;
+        delegate* unmanaged[Cdecl]<IntPtr, void> onDestroy = &OnDestroy;
+        int errorCode;
+        IntPtr err;
+        if (window)
+        {
+            delegate* unmanaged[Cdecl]<IntPtr, IntPtr, IntPtr, void> onValue = &OnAggregateValue;
+            delegate* unmanaged[Cdecl]<IntPtr, IntPtr, IntPtr*, int, ValueRaw*, void> onInverse =
+                &OnAggregateInverse;
+            errorCode = libsql_create_window_function(
+                _connection,
+                name,
+                argCount,
+                deterministic,
+                (IntPtr)onStep,
+                (IntPtr)onFinal,
+                (IntPtr)onValue,
+                (IntPtr)onInverse,
+                GCHandle.ToIntPtr(handle),
+                (IntPtr)onDestroy,
+                out err
+            );
+        }
+        else
+        {
+            errorCode = libsql_create_aggregate_function(
+                _connection,
+                name,
+                argCount,
+                deterministic,
+                (IntPtr)onStep,
+                (IntPtr)onFinal,
+                GCHandle.ToIntPtr(handle),
+                (IntPtr)onDestroy,
+                out err
+            );
+        }
+        Utils.HandleError(errorCode, err);
+    }
+
+    // The state of a group is a handle to its IAggregate, freed by the final call
+    private static IAggregate GroupState(IntPtr state)
+    {
+        return (IAggregate)GCHandle.FromIntPtr(state).Target!;
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static unsafe void OnAggregateStep(
+        IntPtr userData,
+        IntPtr ctx,
+        IntPtr* state,
+        int argc,
+        ValueRaw* argv
+    )
+    {
+        try
+        {
+            if (*state == IntPtr.Zero)
+            {
+                var create = (Func<IAggregate>)GCHandle.FromIntPtr(userData).Target!;
+                *state = GCHandle.ToIntPtr(GCHandle.Alloc(create()));
+            }
+            GroupState(*state).Step(ReadArgs(argc, argv));
+        }
+        catch (Exception e)
+        {
+            SetError(ctx, e.Message);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static unsafe void OnAggregateInverse(
+        IntPtr userData,
+        IntPtr ctx,
+        IntPtr* state,
+        int argc,
+        ValueRaw* argv
+    )
+    {
+        try
+        {
+            ((IWindowAggregate)GroupState(*state)).Inverse(ReadArgs(argc, argv));
+        }
+        catch (Exception e)
+        {
+            SetError(ctx, e.Message);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnAggregateValue(IntPtr userData, IntPtr ctx, IntPtr state)
+    {
+        try
+        {
+            // A window no row entered yet has no state
+            var aggregate =
+                state == IntPtr.Zero
+                    ? ((Func<IAggregate>)GCHandle.FromIntPtr(userData).Target!)()
+                    : GroupState(state);
+            SetResult(ctx, ((IWindowAggregate)aggregate).Value());
+        }
+        catch (Exception e)
+        {
+            SetError(ctx, e.Message);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnAggregateFinal(IntPtr userData, IntPtr ctx, IntPtr state)
+    {
+        try
+        {
+            IAggregate aggregate;
+            if (state == IntPtr.Zero)
+            {
+                aggregate = ((Func<IAggregate>)GCHandle.FromIntPtr(userData).Target!)();
+            }
+            else
+            {
+                var handle = GCHandle.FromIntPtr(state);
+                aggregate = (IAggregate)handle.Target!;
+                handle.Free();
+            }
+            SetResult(ctx, aggregate.Final());
+        }
+        catch (Exception e)
+        {
+            SetError(ctx, e.Message);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static void OnDestroy(IntPtr userData)
+    {
+        GCHandle.FromIntPtr(userData).Free();
+    }
+
+    internal static unsafe object?[] ReadArgs(int argc, ValueRaw* argv)
+    {
+        var args = new object?[argc];
+        for (var i = 0; i < argc; i++)
+        {
+            var arg = argv[i];
+            args[i] = (ColumnType)arg.ValueType switch
+            {
+                ColumnType.INT => arg.IntValue,
+                ColumnType.FLOAT => arg.FloatValue,
+                ColumnType.TEXT => Marshal.PtrToStringUTF8(arg.Bytes.ptr, arg.Bytes.len),
+                ColumnType.BLOB => arg.Bytes.GetSpan().ToArray(),
+                _ => null,
+            };
+        }
+        return args;
+    }
+
+    internal static unsafe void SetResult(IntPtr ctx, object? result)
+    {
+        switch (result)
+        {
+            case null:
+                libsql_result_null(ctx);
+                break;
+            case long value:
+                libsql_result_int(ctx, value);
+                break;
+            case int value:
+                libsql_result_int(ctx, value);
+                break;
+            case bool value:
+                libsql_result_int(ctx, value ? 1 : 0);
+                break;
+            case double value:
+                libsql_result_float(ctx, value);
+                break;
+            case float value:
+                libsql_result_float(ctx, value);
+                break;
+            case string value:
+                var text = Encoding.UTF8.GetBytes(value);
+                fixed (byte* ptr = text)
+                    libsql_result_text(ctx, (IntPtr)ptr, text.Length);
+                break;
+            case byte[] value:
+                fixed (byte* ptr = value)
+                    libsql_result_blob(ctx, (IntPtr)ptr, value.Length);
+                break;
+            default:
+                SetError(ctx, "Unsupported result type: " + result.GetType());
+                break;
+        }
+    }
+
+    internal static void SetError(IntPtr ctx, string message)
+    {
+        libsql_result_error(ctx, message);
+    }
+}
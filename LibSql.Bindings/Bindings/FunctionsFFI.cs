@@ -0,0 +1,100 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+// Argument of a function, only valid during the call
+[StructLayout(LayoutKind.Sequential)]
+internal struct ValueRaw
+{
+    internal int ValueType;
+    internal long IntValue;
+    internal double FloatValue;
+    internal BlobRaw Bytes;
+}
+
+public partial class Connection
+{
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_create_scalar_function",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_create_scalar_function(
+        ConnectionHandle conn,
+        string name,
+        int n_args,
+        [MarshalAs(UnmanagedType.U1)] bool deterministic,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_create_aggregate_function",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_create_aggregate_function(
+        ConnectionHandle conn,
+        string name,
+        int n_args,
+        [MarshalAs(UnmanagedType.U1)] bool deterministic,
+        IntPtr step,
+        IntPtr final_,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_create_window_function",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_create_window_function(
+        ConnectionHandle conn,
+        string name,
+        int n_args,
+        [MarshalAs(UnmanagedType.U1)] bool deterministic,
+        IntPtr step,
+        IntPtr final_,
+        IntPtr value,
+        IntPtr inverse,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_result_null")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_result_null(IntPtr ctx);
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_result_int")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_result_int(IntPtr ctx, long value);
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_result_float")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_result_float(IntPtr ctx, double value);
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_result_text")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_result_text(IntPtr ctx, IntPtr ptr, int len);
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_result_blob")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_result_blob(IntPtr ctx, IntPtr ptr, int len);
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_result_error",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_result_error(IntPtr ctx, string message);
+}
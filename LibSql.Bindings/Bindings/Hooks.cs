@@ -0,0 +1,219 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+public enum UpdateOperation
+{
+    Delete = 9,
+    Insert = 18,
+    Update = 23,
+}
+
+public record RowChange(UpdateOperation Operation, string Database, string Table, long RowId);
+
+// SQLite's authorizer action codes
+public enum AuthorizerAction
+{
+    CreateIndex = 1,
+    CreateTable = 2,
+    CreateTempIndex = 3,
+    CreateTempTable = 4,
+    CreateTempTrigger = 5,
+    CreateTempView = 6,
+    CreateTrigger = 7,
+    CreateView = 8,
+    Delete = 9,
+    DropIndex = 10,
+    DropTable = 11,
+    DropTempIndex = 12,
+    DropTempTable = 13,
+    DropTempTrigger = 14,
+    DropTempView = 15,
+    DropTrigger = 16,
+    DropView = 17,
+    Insert = 18,
+    Pragma = 19,
+    Read = 20,
+    Select = 21,
+    Transaction = 22,
+    Update = 23,
+    Attach = 24,
+    Detach = 25,
+    AlterTable = 26,
+    Reindex = 27,
+    Analyze = 28,
+    CreateVtable = 29,
+    DropVtable = 30,
+    Function = 31,
+    Savepoint = 32,
+    Recursive = 33,
+}
+
+public enum AuthorizerResult
+{
+    Allow = 0,
+    // Fails the statement being prepared
+    Deny = 1,
+    // Reads the column as NULL, or skips the action
+    Ignore = 2,
+}
+
+// The arguments depend on the action, e.g. the table and column names for Read, or the pragma
+// and its value for Pragma
+public record AuthorizerContext(
+    AuthorizerAction Action,
+    string? Arg1,
+    string? Arg2,
+    string? Database,
+    string? TriggerOrView
+);
+
+// Hooks run on the thread running the statement and must not use the connection. Setting one
+// replaces the previous one, null removes it, and they are removed when the connection is
+// disconnected. Only local and replica connections have hooks, others throw
+// LibSqlErrorKind.NotSupported
+public partial class Connection
+{
+    // Called after each row of a rowid table is inserted, updated or deleted, except for rows
+    // deleted all at once by a DELETE without WHERE clause
+    public unsafe void SetUpdateHook(Action<RowChange>? handler)
+    {
+        delegate* unmanaged[Cdecl]<IntPtr, int, IntPtr, IntPtr, long, void> onUpdate = &OnUpdate;
+        SetHook(libsql_set_update_hook, handler, (IntPtr)onUpdate);
+    }
+
+    // Called before each transaction commits, returning false rolls it back instead. Exceptions
+    // let it commit
+    public unsafe void SetCommitHook(Func<bool>? handler)
+    {
+        delegate* unmanaged[Cdecl]<IntPtr, int> onCommit = &OnCommit;
+        SetHook(libsql_set_commit_hook, handler, (IntPtr)onCommit);
+    }
+
+    // Called after each transaction rolls back
+    public unsafe void SetRollbackHook(Action? handler)
+    {
+        delegate* unmanaged[Cdecl]<IntPtr, void> onRollback = &OnRollback;
+        SetHook(libsql_set_rollback_hook, handler, (IntPtr)onRollback);
+    }
+
+    // Called for each action of the statements prepared from then on, e.g. to sandbox
+    // user-supplied SQL. Exceptions deny the action
+    public unsafe void SetAuthorizer(Func<AuthorizerContext, AuthorizerResult>? authorizer)
+    {
+        delegate* unmanaged[Cdecl]<IntPtr, int, IntPtr, IntPtr, IntPtr, IntPtr, int> onAuthorize =
+            &OnAuthorize;
+        SetHook(libsql_set_authorizer, authorizer, (IntPtr)onAuthorize);
+    }
+
+    private delegate int SetHookFn(
+        ConnectionHandle conn,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    private unsafe void SetHook(SetHookFn set, Delegate? handler, IntPtr callback)
+    {
+        var handle = handler is null ? default : GCHandle.Alloc(handler);
+        delegate* unmanaged[Cdecl]<IntPtr, void> onDestroy = &OnDestroy;
+        // The handle is freed by OnDestroy once the hook is replaced or removed, also when setting
+        // it fails
+        var errorCode = set(
+            _connection,
+            handler is null ? IntPtr.Zero : callback,
+            handler is null ? IntPtr.Zero : GCHandle.ToIntPtr(handle),
+            handler is null ? IntPtr.Zero : (IntPtr)onDestroy,
+            out var err
+        );
+        Utils.HandleError(errorCode, err);
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnUpdate(
+        IntPtr userData,
+        int op,
+        IntPtr dbName,
+        IntPtr tableName,
+        long rowId
+    )
+    {
+        try
+        {
+            var handler = (Action<RowChange>)GCHandle.FromIntPtr(userData).Target!;
+            handler(
+                new RowChange(
+                    (UpdateOperation)op,
+                    Marshal.PtrToStringUTF8(dbName)!,
+                    Marshal.PtrToStringUTF8(tableName)!,
+                    rowId
+                )
+            );
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static int OnCommit(IntPtr userData)
+    {
+        try
+        {
+            var handler = (Func<bool>)GCHandle.FromIntPtr(userData).Target!;
+            return handler() ? 0 : 1;
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+            return 0;
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnRollback(IntPtr userData)
+    {
+        try
+        {
+            ((Action)GCHandle.FromIntPtr(userData).Target!)();
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static int OnAuthorize(
+        IntPtr userData,
+        int action,
+        IntPtr arg1,
+        IntPtr arg2,
+        IntPtr dbName,
+        IntPtr triggerOrView
+    )
+    {
+        try
+        {
+            var authorizer = (Func<AuthorizerContext, AuthorizerResult>)
+                GCHandle.FromIntPtr(userData).Target!;
+            return (int)authorizer(
+                new AuthorizerContext(
+                    (AuthorizerAction)action,
+                    Marshal.PtrToStringUTF8(arg1),
+                    Marshal.PtrToStringUTF8(arg2),
+                    Marshal.PtrToStringUTF8(dbName),
+                    Marshal.PtrToStringUTF8(triggerOrView)
+                )
+            );
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+            return (int)AuthorizerResult.Deny;
+        }
+    }
+}
@@ -0,0 +1,47 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+public partial class Connection
+{
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_set_update_hook")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_update_hook(
+        ConnectionHandle conn,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_set_commit_hook")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_commit_hook(
+        ConnectionHandle conn,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_set_rollback_hook")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_rollback_hook(
+        ConnectionHandle conn,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_set_authorizer")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_authorizer(
+        ConnectionHandle conn,
+        IntPtr callback,
+        IntPtr user_data,
+        IntPtr destroy,
+        out IntPtr out_err
+    );
+}
@@ -0,0 +1,106 @@
+using System.Collections.Concurrent;
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+using Microsoft.Extensions.Logging;
+
+namespace LibSql.Bindings;
+
+// Forwards the tracing events of the rust lib to Microsoft.Extensions.Logging, the tracing target
+// (e.g. "libsql::replication") is used as the logger category.
+public static unsafe partial class Logging
+{
+    private sealed class Sink(ILoggerFactory factory)
+    {
+        private readonly ConcurrentDictionary<string, ILogger> _loggers = new();
+
+        public void Log(LogLevel level, string target, string message) =>
+            _loggers.GetOrAdd(target, factory.CreateLogger).Log(level, "{Message}", message);
+    }
+
+    private static readonly object _lock = new();
+    private static IntPtr _sink = IntPtr.Zero;
+
+    // Only events at or above minLevel whose target starts with one of targetPrefixes are
+    // forwarded, no prefixes forwards every target. Fails with LibSqlErrorKind.Misuse when the
+    // native internal tracing was enabled before.
+    public static void SetLoggerFactory(
+        ILoggerFactory factory,
+        LogLevel minLevel = LogLevel.Information,
+        params string[] targetPrefixes
+    )
+    {
+        if (minLevel > LogLevel.Error)
+            throw new ArgumentOutOfRangeException(nameof(minLevel));
+
+        lock (_lock)
+        {
+            var sink = GCHandle.ToIntPtr(GCHandle.Alloc(new Sink(factory)));
+            delegate* unmanaged[Cdecl]<int, IntPtr, IntPtr, IntPtr, void> callback = &OnLog;
+            var errorCode = libsql_set_log_callback(
+                (IntPtr)callback,
+                sink,
+                (int)minLevel,
+                targetPrefixes.Length == 0 ? null : string.Join(',', targetPrefixes),
+                out var err
+            );
+            if (errorCode != 0)
+            {
+                GCHandle.FromIntPtr(sink).Free();
+                Utils.HandleError(errorCode, err);
+            }
+            // The previous sink is no longer called once the native call returns
+            FreeSink();
+            _sink = sink;
+        }
+    }
+
+    public static void ClearLoggerFactory()
+    {
+        lock (_lock)
+        {
+            var errorCode = libsql_set_log_callback(IntPtr.Zero, IntPtr.Zero, 0, null, out var err);
+            Utils.HandleError(errorCode, err);
+            FreeSink();
+        }
+    }
+
+    private static void FreeSink()
+    {
+        if (_sink != IntPtr.Zero)
+            GCHandle.FromIntPtr(_sink).Free();
+        _sink = IntPtr.Zero;
+    }
+
+    // The native levels share the values of LogLevel.Trace to LogLevel.Error
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnLog(int level, IntPtr target, IntPtr message, IntPtr userData)
+    {
+        try
+        {
+            var sink = (Sink)GCHandle.FromIntPtr(userData).Target!;
+            sink.Log(
+                (LogLevel)level,
+                Marshal.PtrToStringUTF8(target) ?? "",
+                Marshal.PtrToStringUTF8(message) ?? ""
+            );
+        }
+        catch
+        {
+            // Exceptions can't unwind into the native side
+        }
+    }
+
+    [LibraryImport(
+        Utils.__DllName,
+        EntryPoint = "libsql_set_log_callback",
+        StringMarshalling = StringMarshalling.Utf8
+    )]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_set_log_callback(
+        IntPtr callback,
+        IntPtr user_data,
+        int max_level,
+        string? target_filter,
+        out IntPtr out_err
+    );
+}
@@ -27,7 +27,7 @@ public partial class Row
         RowHandle row,
         int col,
         out IntPtr out_value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_get_int")]
@@ -36,7 +36,7 @@ public partial class Row
         RowHandle row,
         int col,
         out long out_value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_get_float")]
@@ -45,7 +45,7 @@ public partial class Row
         RowHandle row,
         int col,
         out double out_value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_get_blob")]
@@ -54,6 +54,6 @@ public partial class Row
         RowHandle row,
         int col,
         out BlobRaw out_blob,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 }
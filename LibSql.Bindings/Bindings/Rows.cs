@@ -21,20 +21,33 @@ public partial class Rows : IDisposable, IAsyncEnumerable<Row>
     }
 
     // Return rows until null or error
-    public async Task<Row?> GetNextRow()
+    public Task<Row?> GetNextRow(CancellationToken cancellationToken = default)
     {
-        return await Task.Run(() =>
-        {
-            IntPtr err;
-            IntPtr row;
-            var errorCode = libsql_next_row(_rows, out row, out err);
-            Utils.HandleError(errorCode, err);
-
-            if (row == IntPtr.Zero)
-                return null;
+        return Cancellation.Run(
+            cancellationToken,
+            async token =>
+            {
+                // The rows are used until the callback runs, keep them from being released meanwhile
+                var added = false;
+                _rows.DangerousAddRef(ref added);
+                try
+                {
+                    var row = await Completion.HandleTask(
+                        (IntPtr callback, IntPtr userData, out IntPtr err) =>
+                            libsql_next_row_async(_rows, token, callback, userData, out err)
+                    );
+                    if (row == IntPtr.Zero)
+                        return null;
 
-            return new Row(new RowHandle(row));
-        });
+                    return new Row(new RowHandle(row));
+                }
+                finally
+                {
+                    if (added)
+                        _rows.DangerousRelease();
+                }
+            }
+        );
     }
 
     public int ColumnCount()
@@ -79,11 +92,10 @@ public partial class Rows : IDisposable, IAsyncEnumerable<Row>
         CancellationToken cancellationToken = default
     )
     {
-        cancellationToken.ThrowIfCancellationRequested(); // IDK about this approach
         var ended = false;
         while (!ended)
         {
-            var row = await GetNextRow();
+            var row = await GetNextRow(cancellationToken);
             if (row is null)
             {
                 ended = true;
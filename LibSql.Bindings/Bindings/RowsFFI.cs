@@ -21,8 +21,19 @@ public partial class Rows : IDisposable
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_next_row(
         RowsHandle rows,
+        IntPtr cancel_token,
         out IntPtr out_row,
-        out IntPtr out_err_msg
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_next_row_async")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_next_row_async(
+        RowsHandle rows,
+        IntPtr cancel_token,
+        IntPtr callback,
+        IntPtr user_data,
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_column_count")]
@@ -35,7 +46,7 @@ public partial class Rows : IDisposable
         RowsHandle rows,
         int col,
         out IntPtr out_name,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_column_type")]
@@ -45,7 +56,7 @@ public partial class Rows : IDisposable
         IntPtr row_,
         int col,
         out int out_type,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_free_rows")]
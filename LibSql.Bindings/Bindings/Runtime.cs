@@ -0,0 +1,67 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace LibSql.Bindings;
+
+// Zero values keep the tokio defaults
+public struct RuntimeConfig
+{
+    public int WorkerThreads;
+    public int MaxBlockingThreads;
+    public string? ThreadNamePrefix;
+    public bool CurrentThread;
+}
+
+[StructLayout(LayoutKind.Sequential)]
+internal struct RuntimeConfigRaw
+{
+    public nuint StructSize;
+    public int WorkerThreads;
+    public int MaxBlockingThreads;
+    public IntPtr ThreadNamePrefix;
+    public byte CurrentThread;
+}
+
+// The native runtime every call runs on, it starts with the first call into the library
+public static partial class Runtime
+{
+    // Must be called before anything else, fails with LibSqlErrorKind.Misuse once it started
+    public static void Configure(RuntimeConfig config)
+    {
+        var raw = new RuntimeConfigRaw
+        {
+            StructSize = (nuint)Marshal.SizeOf<RuntimeConfigRaw>(),
+            WorkerThreads = config.WorkerThreads,
+            MaxBlockingThreads = config.MaxBlockingThreads,
+            ThreadNamePrefix = Marshal.StringToCoTaskMemUTF8(config.ThreadNamePrefix),
+            CurrentThread = (byte)(config.CurrentThread ? 1 : 0),
+        };
+        try
+        {
+            var errorCode = libsql_runtime_config(in raw, out var err);
+            Utils.HandleError(errorCode, err);
+        }
+        finally
+        {
+            Marshal.FreeCoTaskMem(raw.ThreadNamePrefix);
+        }
+    }
+
+    // Pending and later calls throw with LibSqlErrorKind.Misuse
+    public static void Shutdown(TimeSpan timeout)
+    {
+        var errorCode = libsql_shutdown_runtime((ulong)timeout.TotalMilliseconds, out var err);
+        Utils.HandleError(errorCode, err);
+    }
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_runtime_config")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_runtime_config(
+        in RuntimeConfigRaw config,
+        out IntPtr out_err
+    );
+
+    [LibraryImport(Utils.__DllName, EntryPoint = "libsql_shutdown_runtime")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_shutdown_runtime(ulong timeout_ms, out IntPtr out_err);
+}
@@ -22,7 +22,7 @@ public partial class Statements
     internal static partial int libsql_query_stmt(
         StatementsHandle statements,
         out IntPtr out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_query_stmt_positional")]
@@ -31,7 +31,7 @@ public partial class Statements
         StatementsHandle statements,
         IntPtr positional,
         out IntPtr out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_query_stmt_named")]
@@ -40,7 +40,7 @@ public partial class Statements
         StatementsHandle statements,
         IntPtr named,
         out IntPtr out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_execute_stmt")]
@@ -48,7 +48,7 @@ public partial class Statements
     internal static partial int libsql_execute_stmt(
         StatementsHandle statements,
         out ulong out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_execute_stmt_positional")]
@@ -57,7 +57,7 @@ public partial class Statements
         StatementsHandle statements,
         IntPtr positional,
         out ulong out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_execute_stmt_named")]
@@ -66,19 +66,19 @@ public partial class Statements
         StatementsHandle statements,
         IntPtr named,
         out ulong out_rows,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_run_stmt")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
-    internal static partial int libsql_run_stmt(StatementsHandle statements, out IntPtr out_err_msg);
+    internal static partial int libsql_run_stmt(StatementsHandle statements, out IntPtr out_err);
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_run_stmt_positional")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_run_stmt_positional(
         StatementsHandle statements,
         IntPtr positional,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_run_stmt_named")]
@@ -86,16 +86,16 @@ public partial class Statements
     internal static partial int libsql_run_stmt_named(
         StatementsHandle statements,
         IntPtr named,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_reset_stmt")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
-    internal static partial int libsql_reset_stmt(StatementsHandle statements, out IntPtr out_err_msg);
+    internal static partial int libsql_reset_stmt(StatementsHandle statements, out IntPtr out_err);
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_finalize_stmt")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
-    internal static partial int libsql_finalize_stmt(StatementsHandle statements, out IntPtr out_err_msg);
+    internal static partial int libsql_finalize_stmt(StatementsHandle statements, out IntPtr out_err);
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_free_stmt")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
@@ -25,14 +25,14 @@ public partial class Transaction
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_rollback_transaction(
         TransactionHandle transaction,
-        out IntPtr out_msg_err
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_commit_transaction")]
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial int libsql_commit_transaction(
         TransactionHandle transaction,
-        out IntPtr out_msg_err
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_connection_transaction")]
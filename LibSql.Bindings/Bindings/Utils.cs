@@ -25,14 +25,81 @@ internal partial class Utils
     [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
     internal static partial void libsql_free_string(IntPtr str);
 
-    internal static void HandleError(int errorCode, IntPtr errMsg)
+    [LibraryImport(__DllName, EntryPoint = "libsql_error_kind")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_error_kind(IntPtr err);
+
+    [LibraryImport(__DllName, EntryPoint = "libsql_error_code")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_error_code(IntPtr err);
+
+    [LibraryImport(__DllName, EntryPoint = "libsql_error_extended_code")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial int libsql_error_extended_code(IntPtr err);
+
+    [LibraryImport(__DllName, EntryPoint = "libsql_error_message")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial IntPtr libsql_error_message(IntPtr err);
+
+    [LibraryImport(__DllName, EntryPoint = "libsql_free_error")]
+    [UnmanagedCallConv(CallConvs = new[] { typeof(CallConvCdecl) })]
+    internal static partial void libsql_free_error(IntPtr err);
+
+    // "Name: value" strings in a native array, to be released with FreeHeaders
+    internal static IntPtr HeadersToCoTaskMem(IReadOnlyDictionary<string, string>? headers)
     {
-        if (errorCode != 0 && errMsg != IntPtr.Zero)
+        if (headers is null || headers.Count == 0)
+            return IntPtr.Zero;
+        var array = Marshal.AllocCoTaskMem(IntPtr.Size * headers.Count);
+        var i = 0;
+        foreach (var (name, value) in headers)
         {
-            var error = Marshal.PtrToStringUTF8(errMsg);
-            Utils.libsql_free_string(errMsg);
-            throw new LibSqlException(error);
+            var header = Marshal.StringToCoTaskMemUTF8($"{name}: {value}");
+            Marshal.WriteIntPtr(array, IntPtr.Size * i++, header);
         }
+        return array;
+    }
+
+    internal static void FreeHeaders(IntPtr headers, int count)
+    {
+        if (headers == IntPtr.Zero)
+            return;
+        for (var i = 0; i < count; i++)
+        {
+            Marshal.FreeCoTaskMem(Marshal.ReadIntPtr(headers, IntPtr.Size * i));
+        }
+        Marshal.FreeCoTaskMem(headers);
+    }
+
+    // Null stays null, to be released with Marshal.FreeCoTaskMem
+    internal static IntPtr BytesToCoTaskMem(byte[]? bytes)
+    {
+        if (bytes is null)
+            return IntPtr.Zero;
+        var ptr = Marshal.AllocCoTaskMem(Math.Max(1, bytes.Length));
+        Marshal.Copy(bytes, 0, ptr, bytes.Length);
+        return ptr;
+    }
+
+    internal static void HandleError(int errorCode, IntPtr err)
+    {
+        if (errorCode == 0)
+            return;
+
+        if (err == IntPtr.Zero)
+            throw new LibSqlException((LibSqlErrorKind)errorCode, 0, 0, null);
+
+        throw IntoExceptionAndFree(err);
+    }
+
+    internal static LibSqlException IntoExceptionAndFree(IntPtr err)
+    {
+        var kind = (LibSqlErrorKind)libsql_error_kind(err);
+        var code = libsql_error_code(err);
+        var extendedCode = libsql_error_extended_code(err);
+        var message = Marshal.PtrToStringUTF8(libsql_error_message(err));
+        libsql_free_error(err);
+        return new LibSqlException(kind, code, extendedCode, message);
     }
 
     internal static string IntoStringAndFree(IntPtr str)
@@ -138,7 +205,7 @@ internal partial class PositionalValues
         PositionalValuesHandle pos_values,
         uint idx,
         long value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_positional_bind_float")]
@@ -147,7 +214,7 @@ internal partial class PositionalValues
         PositionalValuesHandle pos_values,
         uint idx,
         double value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_positional_bind_null")]
@@ -155,7 +222,7 @@ internal partial class PositionalValues
     internal static partial int libsql_positional_bind_null(
         PositionalValuesHandle pos_values,
         uint idx,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -168,7 +235,7 @@ internal partial class PositionalValues
         PositionalValuesHandle pos_values,
         uint idx,
         string value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(Utils.__DllName, EntryPoint = "libsql_positional_bind_blob")]
@@ -178,7 +245,7 @@ internal partial class PositionalValues
         uint idx,
         IntPtr value,
         int value_len,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 }
 
@@ -282,7 +349,7 @@ internal partial class NamedValues
         NamedValuesHandle named_vals,
         string name,
         long value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -295,7 +362,7 @@ internal partial class NamedValues
         NamedValuesHandle named_vals,
         string name,
         double value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -307,7 +374,7 @@ internal partial class NamedValues
     internal static partial int libsql_named_bind_null(
         NamedValuesHandle named_vals,
         string name,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -320,7 +387,7 @@ internal partial class NamedValues
         NamedValuesHandle named_vals,
         string name,
         string value,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 
     [LibraryImport(
@@ -334,15 +401,51 @@ internal partial class NamedValues
         string name,
         IntPtr value,
         int value_len,
-        out IntPtr out_err_msg
+        out IntPtr out_err
     );
 }
 
+// Mirrors the LIBSQL_ERR_* constants of the rust lib
+public enum LibSqlErrorKind
+{
+    Unknown = 1,
+    InvalidArgument = 2,
+    Sqlite = 3,
+    Connection = 4,
+    Sync = 5,
+    NotSupported = 6,
+    Misuse = 7,
+    TypeMismatch = 8,
+    OutOfRange = 9,
+    Panic = 10,
+    Poisoned = 11,
+    InvalidHandle = 12,
+    Cancelled = 13,
+    Timeout = 14,
+    Busy = 15,
+}
+
 [Serializable]
 public class LibSqlException : Exception
 {
+    public LibSqlErrorKind Kind { get; } = LibSqlErrorKind.Unknown;
+
+    // SQLite primary result code (SQLITE_BUSY, SQLITE_CONSTRAINT...), 0 if the error isn't from SQLite
+    public int Code { get; }
+
+    // SQLite extended result code (SQLITE_CONSTRAINT_UNIQUE...), 0 if the error isn't from SQLite
+    public int ExtendedCode { get; }
+
     public LibSqlException() { }
 
+    public LibSqlException(LibSqlErrorKind kind, int code, int extendedCode, string? message)
+        : this(message)
+    {
+        Kind = kind;
+        Code = code;
+        ExtendedCode = extendedCode;
+    }
+
     public LibSqlException(string? message)
         : base(message ?? "LibSql Bindings: error + marshalling") { }
 
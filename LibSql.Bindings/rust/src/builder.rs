@@ -0,0 +1,630 @@
+// A database builder handle covering every mode of `libsql::Builder`. libsql's builder changes
+// type with its mode, so the handle keeps the mode and the options apart and builds the typed
+// builder in `libsql_builder_build`. Setting an option the mode doesn't support fails with
+// `LIBSQL_ERR_NOT_SUPPORTED` right away.
+
+use std::{
+    ffi::{c_char, c_int, c_ulonglong, CStr},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::connector::{connector, HttpOptions};
+use crate::error::{libsql_error, set_err};
+use crate::guard::{catch_panic, guard, guard_free};
+use crate::handles::into_handle;
+use crate::raw;
+use crate::snapshot;
+use crate::sync::{self, Tracker};
+use crate::tls::TlsOptions;
+use crate::types::LIBSQL_ERR_NOT_SUPPORTED;
+use crate::{encryption_config, get_mut_ref, get_ref, open_flags, RT};
+
+enum Mode {
+    Local {
+        path: String,
+    },
+    Remote {
+        url: String,
+        auth_token: String,
+    },
+    RemoteReplica {
+        path: String,
+        url: String,
+        auth_token: String,
+    },
+    Synced {
+        path: String,
+        url: String,
+        auth_token: String,
+    },
+}
+
+impl Mode {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Local { .. } => "local databases",
+            Mode::Remote { .. } => "remote databases",
+            Mode::RemoteReplica { .. } => "remote replicas",
+            Mode::Synced { .. } => "synced databases",
+        }
+    }
+
+    /// Path of the local database, if the mode has one.
+    fn path(&self) -> Option<&str> {
+        match self {
+            Mode::Local { path } | Mode::RemoteReplica { path, .. } | Mode::Synced { path, .. } => {
+                Some(path)
+            }
+            Mode::Remote { .. } => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    namespace: Option<String>,
+    version: Option<String>,
+    tls: TlsOptions,
+    http: HttpOptions,
+    sync_interval: Option<Duration>,
+    read_your_writes: Option<bool>,
+    remote_writes: Option<bool>,
+    encryption: Option<libsql::EncryptionConfig>,
+    snapshot: Option<String>,
+    flags: Option<libsql::OpenFlags>,
+}
+
+pub struct libsql_builder {
+    mode: Mode,
+    options: Options,
+}
+
+impl libsql_builder {
+    fn unsupported(&self, option: &str) -> libsql_error {
+        libsql_error::new(
+            LIBSQL_ERR_NOT_SUPPORTED,
+            format!(
+                "The {option} option is not supported by {}",
+                self.mode.name()
+            ),
+        )
+    }
+
+    /// Remote replicas and synced databases come with the tracker of their syncs.
+    async fn build(&self) -> Result<(libsql::Database, Option<Arc<Tracker>>), libsql_error> {
+        let options = &self.options;
+        let tracker = match self.mode {
+            Mode::RemoteReplica { .. } | Mode::Synced { .. } => {
+                Some(Tracker::new(options.sync_interval))
+            }
+            _ => None,
+        };
+        let received = tracker.as_ref().map(|tracker| tracker.received());
+        let connector = connector(&options.tls, &options.http, received)?;
+        let db = match &self.mode {
+            Mode::Local { path } => {
+                let mut builder = libsql::Builder::new_local(path);
+                if let Some(flags) = options.flags {
+                    builder = builder.flags(flags);
+                }
+                if let Some(encryption) = &options.encryption {
+                    builder = builder.encryption_config(encryption.clone());
+                }
+                builder.build().await
+            }
+            Mode::Remote { url, auth_token } => {
+                let mut builder = libsql::Builder::new_remote(url.clone(), auth_token.clone());
+                if let Some(namespace) = &options.namespace {
+                    builder = builder.namespace(namespace);
+                }
+                if let Some(version) = &options.version {
+                    builder = builder.version(version.clone());
+                }
+                if let Some(connector) = connector {
+                    builder = builder.connector(connector);
+                }
+                builder.build().await
+            }
+            Mode::RemoteReplica {
+                path,
+                url,
+                auth_token,
+            } => {
+                if let Some(snapshot) = &options.snapshot {
+                    snapshot::seed(snapshot, path)?;
+                }
+                let mut builder =
+                    libsql::Builder::new_remote_replica(path, url.clone(), auth_token.clone());
+                if let Some(namespace) = &options.namespace {
+                    builder = builder.namespace(namespace);
+                }
+                if let Some(version) = &options.version {
+                    builder = builder.version(version.clone());
+                }
+                if let Some(connector) = connector {
+                    builder = builder.connector(connector);
+                }
+                if let Some(callback) = options.http.request_callback() {
+                    builder = builder.http_request_callback(callback);
+                }
+                if let Some(read_your_writes) = options.read_your_writes {
+                    builder = builder.read_your_writes(read_your_writes);
+                }
+                if let Some(encryption) = &options.encryption {
+                    builder = builder.encryption_config(encryption.clone());
+                }
+                builder.build().await
+            }
+            Mode::Synced {
+                path,
+                url,
+                auth_token,
+            } => {
+                let mut builder =
+                    libsql::Builder::new_synced_database(path, url.clone(), auth_token.clone());
+                if let Some(version) = &options.version {
+                    builder = builder.version(version.clone());
+                }
+                if let Some(connector) = connector {
+                    builder = builder.connector(connector);
+                }
+                if let Some(read_your_writes) = options.read_your_writes {
+                    builder = builder.read_your_writes(read_your_writes);
+                }
+                if let Some(remote_writes) = options.remote_writes {
+                    builder = builder.remote_writes(remote_writes);
+                }
+                builder.build().await
+            }
+        };
+        db.map(|db| (db, tracker))
+            .map_err(|e| libsql_error::from_libsql("Error building the database", e))
+    }
+}
+
+unsafe fn to_string(s: *const c_char, what: &str) -> Result<String, libsql_error> {
+    if s.is_null() {
+        return Err(libsql_error::invalid_argument(format!("Missing {what}")));
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Ok(s.to_string()),
+        Err(e) => Err(libsql_error::invalid_argument(format!("Wrong {what}: {e}"))),
+    }
+}
+
+unsafe fn new_builder(
+    mode: Result<Mode, libsql_error>,
+    out_builder: *mut *const libsql_builder,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    match mode {
+        Ok(mode) => {
+            *out_builder = into_handle(libsql_builder {
+                mode,
+                options: Options::default(),
+            });
+            0
+        }
+        Err(e) => set_err(e, out_err),
+    }
+}
+
+/// Runs `f` over the builder if its mode supports `option`.
+unsafe fn set_option(
+    builder: *const libsql_builder,
+    option: &str,
+    supported: fn(&Mode) -> bool,
+    out_err: *mut *const libsql_error,
+    f: impl FnOnce(&mut Options) -> Result<(), libsql_error>,
+) -> c_int {
+    guard(builder, out_err, |builder| {
+        let builder = get_mut_ref(builder);
+        if !supported(&builder.mode) {
+            return set_err(builder.unsupported(option), out_err);
+        }
+        match f(&mut builder.options) {
+            Ok(()) => 0,
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
+
+/// `path` may be `:memory:` for an in memory database.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_new_local(
+    path: *const c_char,
+    out_builder: *mut *const libsql_builder,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    catch_panic(out_err, || {
+        let mode = to_string(path, "db path").map(|path| Mode::Local { path });
+        new_builder(mode, out_builder, out_err)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_new_remote(
+    url: *const c_char,
+    auth_token: *const c_char,
+    out_builder: *mut *const libsql_builder,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    catch_panic(out_err, || {
+        let mode = (|| {
+            Ok(Mode::Remote {
+                url: to_string(url, "URL")?,
+                auth_token: to_string(auth_token, "auth token")?,
+            })
+        })();
+        new_builder(mode, out_builder, out_err)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_new_remote_replica(
+    path: *const c_char,
+    url: *const c_char,
+    auth_token: *const c_char,
+    out_builder: *mut *const libsql_builder,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    catch_panic(out_err, || {
+        let mode = (|| {
+            Ok(Mode::RemoteReplica {
+                path: to_string(path, "db path")?,
+                url: to_string(url, "URL")?,
+                auth_token: to_string(auth_token, "auth token")?,
+            })
+        })();
+        new_builder(mode, out_builder, out_err)
+    })
+}
+
+/// A local database accepting writes while offline, pushed to the remote one on sync.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_new_synced(
+    path: *const c_char,
+    url: *const c_char,
+    auth_token: *const c_char,
+    out_builder: *mut *const libsql_builder,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    catch_panic(out_err, || {
+        let mode = (|| {
+            Ok(Mode::Synced {
+                path: to_string(path, "db path")?,
+                url: to_string(url, "URL")?,
+                auth_token: to_string(auth_token, "auth token")?,
+            })
+        })();
+        new_builder(mode, out_builder, out_err)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_namespace(
+    builder: *const libsql_builder,
+    namespace: *const c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "namespace",
+        |mode| matches!(mode, Mode::Remote { .. } | Mode::RemoteReplica { .. }),
+        out_err,
+        |options| {
+            options.namespace = Some(to_string(namespace, "namespace")?);
+            Ok(())
+        },
+    )
+}
+
+/// Version of the remote protocol the client announces to the server.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_version(
+    builder: *const libsql_builder,
+    version: *const c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(builder, "version", is_remote, out_err, |options| {
+        options.version = Some(to_string(version, "version")?);
+        Ok(())
+    })
+}
+
+/// Trusts the bundled webpki roots.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_webpki(
+    builder: *const libsql_builder,
+    with_webpki: c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(builder, "webpki roots", is_remote, out_err, |options| {
+        options.tls.with_webpki = with_webpki != 0;
+        Ok(())
+    })
+}
+
+fn is_remote(mode: &Mode) -> bool {
+    !matches!(mode, Mode::Local { .. })
+}
+
+unsafe fn bytes<'a>(ptr: *const c_char, len: c_int, what: &str) -> Result<&'a [u8], libsql_error> {
+    if ptr.is_null() || len < 0 {
+        return Err(libsql_error::invalid_argument(format!("Missing {what}")));
+    }
+    Ok(std::slice::from_raw_parts(ptr as *const u8, len as usize))
+}
+
+/// Trusts the certificate authorities of the operating system.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_native_roots(
+    builder: *const libsql_builder,
+    native_roots: c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(builder, "native roots", is_remote, out_err, |options| {
+        options.tls.native_roots = native_roots != 0;
+        Ok(())
+    })
+}
+
+/// Trusts the PEM encoded certificates of `pem`, can be called several times.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_add_root_certs(
+    builder: *const libsql_builder,
+    pem: *const c_char,
+    pem_len: c_int,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "root certificates",
+        is_remote,
+        out_err,
+        |options| {
+            options
+                .tls
+                .add_root_certs(bytes(pem, pem_len, "root certificates")?)
+        },
+    )
+}
+
+/// Authenticates to the server (mTLS) with a PEM encoded certificate chain and private key.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_client_cert(
+    builder: *const libsql_builder,
+    cert_pem: *const c_char,
+    cert_pem_len: c_int,
+    key_pem: *const c_char,
+    key_pem_len: c_int,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "client certificate",
+        is_remote,
+        out_err,
+        |options| {
+            options.tls.set_client_auth(
+                bytes(cert_pem, cert_pem_len, "client certificate")?,
+                bytes(key_pem, key_pem_len, "client key")?,
+            )
+        },
+    )
+}
+
+/// Offers HTTP/2 besides HTTP/1.1.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_http2(
+    builder: *const libsql_builder,
+    http2: c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(builder, "HTTP/2", is_remote, out_err, |options| {
+        options.tls.http2 = http2 != 0;
+        Ok(())
+    })
+}
+
+/// Accepts any server certificate. Only meant for local test servers.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_skip_tls_verification(
+    builder: *const libsql_builder,
+    skip: c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(builder, "TLS verification", is_remote, out_err, |options| {
+        options.tls.skip_verify = skip != 0;
+        Ok(())
+    })
+}
+
+/// Tunnels through the HTTP CONNECT proxy at `url` (`http://host:port`), null connects directly.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_proxy(
+    builder: *const libsql_builder,
+    url: *const c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(builder, "proxy", is_remote, out_err, |options| {
+        if url.is_null() {
+            return options.http.set_proxy(None);
+        }
+        options.http.set_proxy(Some(&to_string(url, "proxy URL")?))
+    })
+}
+
+/// Sends an extra header with every request, can be called several times. Building fails if
+/// HTTP/2 is also enabled.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_add_header(
+    builder: *const libsql_builder,
+    name: *const c_char,
+    value: *const c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(builder, "headers", is_remote, out_err, |options| {
+        options.http.add_header(
+            &to_string(name, "header name")?,
+            &to_string(value, "header value")?,
+        )
+    })
+}
+
+/// Syncs periodically in the background, 0 disables it. The sync is run by the bindings rather
+/// than by libsql, right away and then once the interval elapsed after each sync ends.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_sync_interval(
+    builder: *const libsql_builder,
+    interval_ms: c_ulonglong,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "sync interval",
+        |mode| matches!(mode, Mode::RemoteReplica { .. } | Mode::Synced { .. }),
+        out_err,
+        |options| {
+            options.sync_interval = match interval_ms {
+                0 => None,
+                ms => Some(Duration::from_millis(ms)),
+            };
+            Ok(())
+        },
+    )
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_read_your_writes(
+    builder: *const libsql_builder,
+    read_your_writes: c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "read your writes",
+        |mode| matches!(mode, Mode::RemoteReplica { .. } | Mode::Synced { .. }),
+        out_err,
+        |options| {
+            options.read_your_writes = Some(read_your_writes != 0);
+            Ok(())
+        },
+    )
+}
+
+/// Sends the writes of a synced database straight to the remote one.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_remote_writes(
+    builder: *const libsql_builder,
+    remote_writes: c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "remote writes",
+        |mode| matches!(mode, Mode::Synced { .. }),
+        out_err,
+        |options| {
+            options.remote_writes = Some(remote_writes != 0);
+            Ok(())
+        },
+    )
+}
+
+/// Encrypts the local file with a raw key of `key_len` bytes, a null `key` removes it.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_encryption(
+    builder: *const libsql_builder,
+    key: *const c_char,
+    key_len: c_int,
+    cipher: c_int,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "encryption",
+        |mode| matches!(mode, Mode::Local { .. } | Mode::RemoteReplica { .. }),
+        out_err,
+        |options| {
+            options.encryption = encryption_config(key, key_len, cipher)?;
+            Ok(())
+        },
+    )
+}
+
+/// Seeds the replica from a copy of another one when its db path doesn't exist yet, null removes
+/// it.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_snapshot(
+    builder: *const libsql_builder,
+    snapshot_path: *const c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "snapshot",
+        |mode| matches!(mode, Mode::RemoteReplica { .. }),
+        out_err,
+        |options| {
+            options.snapshot = if snapshot_path.is_null() {
+                None
+            } else {
+                Some(to_string(snapshot_path, "snapshot path")?)
+            };
+            Ok(())
+        },
+    )
+}
+
+/// `flags` is a set of `LIBSQL_OPEN_*`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_set_flags(
+    builder: *const libsql_builder,
+    flags: c_int,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    set_option(
+        builder,
+        "flags",
+        |mode| matches!(mode, Mode::Local { .. }),
+        out_err,
+        |options| {
+            options.flags = Some(open_flags(flags)?);
+            Ok(())
+        },
+    )
+}
+
+/// The builder stays usable afterwards and must still be freed.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_builder_build(
+    builder: *const libsql_builder,
+    out_db: *mut *const libsql::Database,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    guard(builder, out_err, |builder| {
+        let builder = get_ref(builder);
+        match RT.block_on(builder.build()) {
+            Ok((db, tracker)) => {
+                *out_db = into_handle(db);
+                if let Some(path) = builder.mode.path() {
+                    raw::track(*out_db, path);
+                }
+                if let Some(tracker) = tracker {
+                    sync::track(*out_db, tracker);
+                }
+                0
+            }
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_free_builder(builder: *mut libsql_builder) {
+    guard_free(builder, |builder| {
+        let _ = Box::from_raw(builder);
+    })
+}
@@ -0,0 +1,106 @@
+// The operations run on a connection by the query, execute, batch and prepare functions. Each is
+// written once as a future owning what it needs, the blocking entry points run it with
+// `RT.block_on` and the `_async` ones hand it to their `Completion`.
+
+use std::{ffi::CStr, future::Future, time::Duration};
+
+use libsql::params::Params;
+
+use crate::cancel::{cancellable, get_cancel_token, libsql_cancel_token};
+use crate::error::libsql_error;
+use crate::timeout::{call_timeout, with_timeout};
+use crate::{get_handle, get_ref};
+
+/// Arguments shared by the operations, read before running them.
+pub struct Call {
+    conn: libsql::Connection,
+    sql: String,
+    token: Option<libsql_cancel_token>,
+    timeout: Option<Duration>,
+}
+
+/// Reads the arguments of an operation on `conn`. A `timeout_ms` of 0 uses the default timeout
+/// of the connection.
+pub unsafe fn read_call(
+    conn: *mut libsql::Connection,
+    sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
+    timeout_ms: u64,
+) -> Result<Call, libsql_error> {
+    debug_assert!(!sql.is_null());
+
+    let sql = match CStr::from_ptr(sql).to_str() {
+        Ok(sql) => sql.to_string(),
+        Err(e) => return Err(libsql_error::invalid_argument(format!("Wrong SQL: {e}"))),
+    };
+    Ok(Call {
+        token: get_cancel_token(cancel_token)?,
+        timeout: call_timeout(conn, timeout_ms),
+        conn: get_ref(conn).clone(),
+        sql,
+    })
+}
+
+pub unsafe fn positional(values: *const Vec<libsql::Value>) -> Result<Params, libsql_error> {
+    Ok(Params::Positional(get_handle(values)?.clone()))
+}
+
+pub unsafe fn named(values: *const Vec<(String, libsql::Value)>) -> Result<Params, libsql_error> {
+    Ok(Params::Named(get_handle(values)?.clone()))
+}
+
+/// Awaits `fut` running on `conn`, interrupting it when the call is cancelled or times out.
+async fn run<T>(
+    conn: &libsql::Connection,
+    token: Option<libsql_cancel_token>,
+    timeout: Option<Duration>,
+    context: &str,
+    fut: impl Future<Output = Result<T, libsql::Error>>,
+) -> Result<T, libsql_error> {
+    let fut = async { fut.await.map_err(|e| libsql_error::from_libsql(context, e)) };
+    cancellable(token, Some(conn), with_timeout(timeout, Some(conn), fut)).await
+}
+
+pub async fn query(call: Call, params: Params) -> Result<libsql::Rows, libsql_error> {
+    let Call {
+        conn,
+        sql,
+        token,
+        timeout,
+    } = call;
+    let fut = conn.query(&sql, params);
+    run(&conn, token, timeout, "Error executing statement", fut).await
+}
+
+pub async fn execute(call: Call, params: Params) -> Result<u64, libsql_error> {
+    let Call {
+        conn,
+        sql,
+        token,
+        timeout,
+    } = call;
+    let fut = conn.execute(&sql, params);
+    run(&conn, token, timeout, "Error executing statement", fut).await
+}
+
+pub async fn execute_batch(call: Call) -> Result<libsql::BatchRows, libsql_error> {
+    let Call {
+        conn,
+        sql,
+        token,
+        timeout,
+    } = call;
+    let fut = conn.execute_batch(&sql);
+    run(&conn, token, timeout, "Error executing statement", fut).await
+}
+
+pub async fn prepare(call: Call) -> Result<libsql::Statement, libsql_error> {
+    let Call {
+        conn,
+        sql,
+        token,
+        timeout,
+    } = call;
+    let fut = conn.prepare(&sql);
+    run(&conn, token, timeout, "Error preparing statement", fut).await
+}
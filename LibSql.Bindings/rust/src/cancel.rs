@@ -0,0 +1,144 @@
+// Cancellation of in-flight calls. A token is handed to the calls that accept one and can be
+// triggered from any thread: remote operations are aborted by dropping their future, while
+// local statements run synchronously inside the future, so the connections they run on are
+// interrupted (`sqlite3_interrupt`) instead. Either way the call fails with
+// `LIBSQL_ERR_CANCELLED`. Tokens are one-shot, once cancelled every call using them fails.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::sync::Notify;
+
+use crate::error::libsql_error;
+use crate::guard::{catch_panic_or, guard_free, guard_or};
+use crate::handles::{into_handle, resolve};
+use crate::types::LIBSQL_ERR_CANCELLED;
+
+#[derive(Clone, Default)]
+pub struct libsql_cancel_token(Arc<State>);
+
+#[derive(Default)]
+struct State {
+    cancelled: AtomicBool,
+    notify: Notify,
+    next_id: AtomicU64,
+    // Connections running a call with this token, interrupted on cancel.
+    running: Mutex<HashMap<u64, libsql::Connection>>,
+}
+
+impl libsql_cancel_token {
+    fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+        let running = self.0.running.lock().unwrap_or_else(|e| e.into_inner());
+        for conn in running.values() {
+            // Remote connections don't support it, their future is dropped instead.
+            let _ = conn.interrupt();
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    async fn cancelled(&self) {
+        let notified = self.0.notify.notified();
+        tokio::pin!(notified);
+        // Register before checking the flag, otherwise a cancel in between would be missed.
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+
+    fn track(&self, conn: &libsql::Connection) -> Tracked<'_> {
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut running = self.0.running.lock().unwrap_or_else(|e| e.into_inner());
+        running.insert(id, conn.clone());
+        Tracked { token: self, id }
+    }
+}
+
+struct Tracked<'a> {
+    token: &'a libsql_cancel_token,
+    id: u64,
+}
+
+impl Drop for Tracked<'_> {
+    fn drop(&mut self) {
+        let mut running = self
+            .token
+            .0
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        running.remove(&self.id);
+    }
+}
+
+fn cancelled_error() -> libsql_error {
+    libsql_error::new(LIBSQL_ERR_CANCELLED, "Operation was cancelled")
+}
+
+/// Resolves the optional `cancel_token` argument of a call, null means the call can't be
+/// cancelled.
+pub unsafe fn get_cancel_token(
+    handle: *const libsql_cancel_token,
+) -> Result<Option<libsql_cancel_token>, libsql_error> {
+    if handle.is_null() {
+        return Ok(None);
+    }
+    resolve(handle).map(|token| Some((*token).clone()))
+}
+
+/// Runs `fut` racing it against `token`. `conn` is the connection the operation runs on, if
+/// any, so local statements can be interrupted.
+pub async fn cancellable<R>(
+    token: Option<libsql_cancel_token>,
+    conn: Option<&libsql::Connection>,
+    fut: impl Future<Output = Result<R, libsql_error>>,
+) -> Result<R, libsql_error> {
+    let Some(token) = token else {
+        return fut.await;
+    };
+    if token.is_cancelled() {
+        return Err(cancelled_error());
+    }
+    let _tracked = conn.map(|conn| token.track(conn));
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(cancelled_error()),
+        result = fut => match result {
+            // An interrupted statement fails with SQLITE_INTERRUPT, report the cancellation.
+            Err(_) if token.is_cancelled() => Err(cancelled_error()),
+            result => result,
+        },
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_make_cancel_token(out_token: *mut *const libsql_cancel_token) {
+    catch_panic_or((), || {
+        *out_token = into_handle(libsql_cancel_token::default());
+    })
+}
+
+/// Can be called from any thread while calls using the token are running.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_cancel(token: *const libsql_cancel_token) {
+    guard_or(token, (), |token| (*token).cancel())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_free_cancel_token(token: *mut libsql_cancel_token) {
+    guard_free(token, |token| {
+        let _ = Box::from_raw(token);
+    })
+}
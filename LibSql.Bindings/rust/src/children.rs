@@ -0,0 +1,303 @@
+// Reference counts of the handles created from each database: its connections and pools, and
+// the transactions, statements and rows created from the connections. `libsql_close` uses them
+// to refuse closing a database that is still in use, to wait for its handles to be freed, or to
+// close it anyway, leaving its handles usable only to be freed.
+
+use std::{
+    collections::HashMap,
+    ffi::{c_int, c_ulonglong},
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::error::libsql_error;
+use crate::get_ref;
+use crate::guard::{catch_panic_or, guard_free, poison_with, Poison};
+use crate::handles::{release as release_handle, resolve};
+use crate::raw;
+use crate::sync;
+use crate::types::{
+    LIBSQL_CLOSE_DEFER, LIBSQL_CLOSE_FAIL_IF_BUSY, LIBSQL_CLOSE_FORCE, LIBSQL_CLOSE_WAIT,
+    LIBSQL_ERR_BUSY, LIBSQL_ERR_TIMEOUT,
+};
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Connection,
+    Transaction,
+    Statement,
+    /// Rows of a query, including those of a batch.
+    Rows,
+    /// A row taken from `Rows`.
+    Row,
+    Pool,
+}
+
+#[derive(Default)]
+struct Counts {
+    // Indexed by `Kind`.
+    open: [usize; 6],
+    /// Value pointer of a database closed with `LIBSQL_CLOSE_DEFER`, dropped with its last
+    /// handle.
+    deferred: Option<usize>,
+}
+
+impl Counts {
+    fn total(&self) -> usize {
+        self.open.iter().sum()
+    }
+
+    fn describe(&self) -> String {
+        let names = [
+            ("connection", "connections"),
+            ("transaction", "transactions"),
+            ("statement", "statements"),
+            ("result set", "result sets"),
+            ("row", "rows"),
+            ("pool", "pools"),
+        ];
+        let open: Vec<_> = self
+            .open
+            .iter()
+            .zip(names)
+            .filter(|(count, _)| **count > 0)
+            .map(|(count, (one, many))| format!("{count} {}", if *count == 1 { one } else { many }))
+            .collect();
+        match open.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+            None => "no handles".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Child {
+    db: usize,
+    kind: Kind,
+    handle: usize,
+    /// Value pointer the child was created from, the database for connections and pools.
+    parent: usize,
+}
+
+// Keyed by value pointers rather than handles, the exported functions only see the former once
+// the handle is resolved.
+#[derive(Default)]
+struct Children {
+    /// Children by their value pointer.
+    parents: HashMap<usize, Child>,
+    /// Open children of each database.
+    counts: HashMap<usize, Counts>,
+    /// Borrowed values, which aren't counted, and the child they were borrowed from.
+    aliases: HashMap<usize, usize>,
+}
+
+lazy_static! {
+    static ref CHILDREN: Mutex<Children> = Mutex::default();
+    // Signaled whenever a child is released, for `LIBSQL_CLOSE_WAIT`.
+    static ref RELEASED: Condvar = Condvar::new();
+}
+
+fn children() -> MutexGuard<'static, Children> {
+    CHILDREN.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Counts the handle `child` against the database of `parent`, the value of either the database
+/// or a handle created from it. Returns `child`.
+pub fn adopt<T: 'static, P>(child: *const T, parent: *const P, kind: Kind) -> *const T {
+    let Ok(ptr) = resolve(child) else {
+        return child;
+    };
+    let mut children = children();
+    let parent = parent as usize;
+    let parent = children.aliases.get(&parent).copied().unwrap_or(parent);
+    let db = match children.parents.get(&parent) {
+        Some(parent) => parent.db,
+        None if matches!(kind, Kind::Connection | Kind::Pool) => parent,
+        // Created from a handle whose database was force closed, there's nothing to count.
+        None => return child,
+    };
+    children.parents.insert(
+        ptr as usize,
+        Child {
+            db,
+            kind,
+            handle: child as usize,
+            parent,
+        },
+    );
+    children.counts.entry(db).or_default().open[kind as usize] += 1;
+    child
+}
+
+/// Lets the handles created from `borrowed`, a value owned by `owner`, count against the
+/// database of `owner`.
+pub fn alias<T, P>(borrowed: *const T, owner: *const P) {
+    children().aliases.insert(borrowed as usize, owner as usize);
+}
+
+/// Connection the value `child` was created from, directly or through other handles, so its
+/// statements can be interrupted. None if the connection was freed.
+pub fn connection<T>(child: *const T) -> Option<libsql::Connection> {
+    let children = children();
+    let mut ptr = child as usize;
+    loop {
+        let child = children.parents.get(&ptr)?;
+        // Not freed while the lock is held, it's released first.
+        match child.kind {
+            Kind::Connection => {
+                return Some(unsafe { get_ref(ptr as *const libsql::Connection) }.clone())
+            }
+            Kind::Transaction => {
+                let tx = unsafe { get_ref(ptr as *const libsql::Transaction) };
+                return Some((**tx).clone());
+            }
+            _ => ptr = child.parent,
+        }
+    }
+}
+
+/// Connection owning `conn`, itself unless it was borrowed from a transaction. In that case it's
+/// the connection the transaction was created from, directly or through other transactions.
+pub fn owner(conn: *const libsql::Connection) -> *const libsql::Connection {
+    let children = children();
+    let mut ptr = conn as usize;
+    loop {
+        if let Some(owner) = children.aliases.get(&ptr) {
+            ptr = *owner;
+            continue;
+        }
+        match children.parents.get(&ptr) {
+            Some(Child {
+                kind: Kind::Transaction,
+                parent,
+                ..
+            }) => ptr = *parent,
+            _ => return ptr as *const libsql::Connection,
+        }
+    }
+}
+
+/// Stops counting the value `child`, to be called when the host frees it. The database is
+/// dropped if it was closed with `LIBSQL_CLOSE_DEFER` and this was its last handle.
+pub fn release<T>(child: *mut T) {
+    let mut children = children();
+    let child = child as usize;
+    if !children.aliases.is_empty() {
+        children.aliases.retain(|_, owner| *owner != child);
+    }
+    let Some(Child { db, kind, .. }) = children.parents.remove(&child) else {
+        return;
+    };
+    let Some(counts) = children.counts.get_mut(&db) else {
+        return;
+    };
+    counts.open[kind as usize] -= 1;
+    if counts.total() > 0 {
+        return;
+    }
+    let deferred = counts.deferred;
+    if deferred.is_some() {
+        children.counts.remove(&db);
+    }
+    drop(children);
+    RELEASED.notify_all();
+    if let Some(ptr) = deferred {
+        tracing::debug!("Dropping database closed while in use, its last handle was freed");
+        catch_panic_or((), || unsafe { drop_db(ptr as *mut libsql::Database) });
+    }
+}
+
+unsafe fn drop_db(db: *mut libsql::Database) {
+    raw::forget(db);
+    sync::untrack(Box::from_raw(db));
+}
+
+fn busy(counts: &Counts) -> libsql_error {
+    libsql_error::new(
+        LIBSQL_ERR_BUSY,
+        format!("Database is busy, it has {} open", counts.describe()),
+    )
+}
+
+/// Closes `db` following `mode`, on failure the database stays open. A `timeout_ms` of 0 waits
+/// without limit in `LIBSQL_CLOSE_WAIT`, other modes ignore it.
+pub unsafe fn close(
+    db: *mut libsql::Database,
+    mode: c_int,
+    timeout_ms: c_ulonglong,
+) -> Result<(), libsql_error> {
+    if db.is_null() {
+        return Ok(());
+    }
+    let key = resolve(db)? as usize;
+    let mut children = children();
+    match mode {
+        LIBSQL_CLOSE_FAIL_IF_BUSY => {
+            if let Some(counts) = children.counts.get(&key).filter(|c| c.total() > 0) {
+                return Err(busy(counts));
+            }
+        }
+        LIBSQL_CLOSE_WAIT => {
+            let deadline =
+                (timeout_ms > 0).then(|| Instant::now() + Duration::from_millis(timeout_ms));
+            while let Some(counts) = children.counts.get(&key).filter(|c| c.total() > 0) {
+                let Some(deadline) = deadline else {
+                    children = RELEASED.wait(children).unwrap_or_else(|e| e.into_inner());
+                    continue;
+                };
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Err(libsql_error::new(
+                        LIBSQL_ERR_TIMEOUT,
+                        format!(
+                            "Timed out waiting to close the database, it has {} open",
+                            counts.describe()
+                        ),
+                    ));
+                }
+                children = RELEASED
+                    .wait_timeout(children, left)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+        LIBSQL_CLOSE_FORCE => {
+            let orphans: Vec<_> = children
+                .parents
+                .iter()
+                .filter(|(_, child)| child.db == key)
+                .map(|(ptr, child)| (*ptr, child.handle))
+                .collect();
+            if !orphans.is_empty() {
+                tracing::warn!(
+                    "Closing database with {} open",
+                    children.counts[&key].describe()
+                );
+            }
+            for (ptr, handle) in orphans {
+                children.parents.remove(&ptr);
+                poison_with(handle as *const (), Poison::Closed);
+            }
+        }
+        LIBSQL_CLOSE_DEFER => {
+            if children.counts.get(&key).is_some_and(|c| c.total() > 0) {
+                // The handle goes away now, the value once the last child is freed.
+                let ptr = release_handle(db)?;
+                if let Some(counts) = children.counts.get_mut(&key) {
+                    counts.deferred = Some(ptr as usize);
+                }
+                return Ok(());
+            }
+        }
+        _ => {
+            return Err(libsql_error::invalid_argument(format!(
+                "Unknown close mode {mode}"
+            )))
+        }
+    }
+    children.counts.remove(&key);
+    drop(children);
+    guard_free(db, |db| drop_db(db));
+    Ok(())
+}
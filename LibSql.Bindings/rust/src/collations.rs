@@ -0,0 +1,171 @@
+// Collations implemented by the host, registered on the SQLite connection of local and replica
+// connections. SQLite calls them from the thread running the statement with the UTF-8 texts to
+// compare. Collations a statement uses without them being registered can be registered lazily
+// from the collation needed hook, which runs while the statement is prepared. Like functions, the
+// collations are unregistered when the connection is detached from its handle.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_char, c_int, c_void, CString},
+    sync::{Mutex, MutexGuard},
+};
+
+use libsql::ffi;
+
+use crate::error::{libsql_error, set_err};
+use crate::functions::{destroy_function, missing_callback, read_name, Function};
+use crate::guard::{catch_panic_or, guard};
+use crate::hooks;
+use crate::raw::{raw_connection, sqlite_error};
+use crate::types::{
+    libsql_collation_callback, libsql_collation_needed_callback, libsql_destroy_callback,
+};
+
+lazy_static! {
+    // Names of the collations registered on each connection, keyed by the address of the
+    // connection.
+    static ref REGISTERED: Mutex<HashMap<usize, HashSet<CString>>> = Mutex::default();
+}
+
+fn registered() -> MutexGuard<'static, HashMap<usize, HashSet<CString>>> {
+    REGISTERED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Unregisters the collations registered on `conn` from `raw_conn`, destroying their user data.
+pub unsafe fn detach(conn: *const libsql::Connection, raw_conn: *mut ffi::sqlite3) {
+    let Some(collations) = registered().remove(&(conn as usize)) else {
+        return;
+    };
+    for name in collations {
+        let rc = ffi::sqlite3_create_collation_v2(
+            raw_conn,
+            name.as_ptr(),
+            ffi::SQLITE_UTF8 as c_int,
+            std::ptr::null_mut(),
+            None,
+            None,
+        );
+        if rc != ffi::SQLITE_OK as c_int {
+            let e = sqlite_error(raw_conn, rc, "Error removing collation");
+            tracing::warn!("{name:?} stays registered: {e}");
+        }
+    }
+}
+
+unsafe extern "C" fn call_compare(
+    app: *mut c_void,
+    left_len: c_int,
+    left: *const c_void,
+    right_len: c_int,
+    right: *const c_void,
+) -> c_int {
+    // A collation can't fail, texts compare equal if it panics.
+    catch_panic_or(0, || {
+        let collation = &*(app as *const Function<libsql_collation_callback>);
+        (collation.callbacks)(
+            collation.user_data(),
+            left as *const c_char,
+            left_len,
+            right as *const c_char,
+            right_len,
+        )
+    })
+}
+
+unsafe extern "C" fn call_collation_needed(
+    app: *mut c_void,
+    _raw_conn: *mut ffi::sqlite3,
+    _text_rep: c_int,
+    name: *const c_char,
+) {
+    catch_panic_or((), || {
+        let hook = &*(app as *const Function<libsql_collation_needed_callback>);
+        (hook.callbacks)(hook.user_data(), name);
+    })
+}
+
+/// Registers a collation comparing texts with `compare`, replacing the collation of the same
+/// name. `destroy` is called with `user_data` once the collation is replaced, the connection is
+/// disconnected or released to its pool, or registering it fails. Only available on connections
+/// to local databases and replicas, other connections fail with `LIBSQL_ERR_NOT_SUPPORTED`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_create_collation(
+    conn: *const libsql::Connection,
+    name: *const c_char,
+    compare: Option<libsql_collation_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let Some(compare) = compare else {
+        return missing_callback("compare", user_data, destroy, out_err);
+    };
+    // Dropped on failures before SQLite takes it, destroying the user data.
+    let collation = Box::new(Function {
+        callbacks: compare,
+        user_data: user_data as usize,
+        destroy,
+    });
+    guard(conn, out_err, |conn| {
+        let (conn, raw_conn, name) = match raw_connection(conn)
+            .and_then(|(conn, raw_conn)| Ok((conn, raw_conn, read_name(name)?)))
+        {
+            Ok(found) => found,
+            Err(e) => return set_err(e, out_err),
+        };
+        // SQLite owns the collation from here, destroying it even if this fails.
+        let rc = ffi::sqlite3_create_collation_v2(
+            raw_conn,
+            name.as_ptr(),
+            ffi::SQLITE_UTF8 as c_int,
+            Box::into_raw(collation) as *mut c_void,
+            Some(call_compare),
+            Some(destroy_function::<libsql_collation_callback>),
+        );
+        if rc != ffi::SQLITE_OK as c_int {
+            return set_err(
+                sqlite_error(raw_conn, rc, "Error creating collation"),
+                out_err,
+            );
+        }
+        registered()
+            .entry(conn as usize)
+            .or_default()
+            .insert(name.to_owned());
+        0
+    })
+}
+
+/// Sets the hook called with the name of a collation a statement being prepared uses but which
+/// isn't registered, to register it with `libsql_create_collation`. A NULL `callback` removes
+/// it. `destroy` is called with `user_data` once the hook is replaced or removed, or the
+/// connection is disconnected.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_collation_needed(
+    conn: *const libsql::Connection,
+    callback: Option<libsql_collation_needed_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let hook = callback.map(|callback| Function {
+        callbacks: callback,
+        user_data: user_data as usize,
+        destroy,
+    });
+    guard(conn, out_err, |conn| {
+        let set = hooks::set(
+            conn,
+            hook,
+            |hooks| &mut hooks.collation_needed,
+            |raw_conn, app| {
+                let callback = (!app.is_null()).then_some(call_collation_needed as _);
+                ffi::sqlite3_collation_needed(raw_conn, app, callback);
+            },
+        );
+        match set {
+            Ok(()) => 0,
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
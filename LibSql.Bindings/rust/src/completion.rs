@@ -0,0 +1,161 @@
+// Support for the `*_async` functions: instead of blocking the calling thread with
+// `RT.block_on`, the future is spawned on the runtime and its outcome is reported through a
+// `libsql_callback`, invoked from a runtime worker thread. If the exported function itself
+// fails (invalid handle, bad arguments...) it returns the error and the callback never runs.
+// Otherwise it runs exactly once, with a `LIBSQL_ERR_MISUSE` error for calls still pending when
+// the runtime shuts down.
+
+use std::{
+    ffi::{c_int, c_void},
+    future::Future,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr::null,
+};
+
+use crate::error::{libsql_error, set_err};
+use crate::guard::{guard, panic_error, poison};
+use crate::types::{libsql_callback, replicated, LIBSQL_ERR_MISUSE, LIBSQL_ERR_UNKNOWN};
+use crate::RT;
+
+/// Raw pointer moved into a spawned task, the host guarantees the pointee outlives the task.
+pub struct SendPtr<T>(*mut T);
+
+unsafe impl<T> Send for SendPtr<T> {}
+
+impl<T> Clone for SendPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SendPtr<T> {}
+
+impl<T> SendPtr<T> {
+    pub fn new(ptr: *mut T) -> Self {
+        SendPtr(ptr)
+    }
+
+    // Taking `self` makes async blocks capture the wrapper rather than the bare pointer.
+    pub fn get(self) -> *mut T {
+        self.0
+    }
+}
+
+/// Value handed to a callback alongside the error when the operation failed.
+pub trait Empty {
+    fn empty() -> Self;
+}
+
+impl<T> Empty for *const T {
+    fn empty() -> Self {
+        null()
+    }
+}
+
+impl Empty for u64 {
+    fn empty() -> Self {
+        0
+    }
+}
+
+impl Empty for replicated {
+    fn empty() -> Self {
+        replicated {
+            frame_no: 0,
+            frames_synced: 0,
+            has_frame_no: 0,
+        }
+    }
+}
+
+/// Pending call to the host callback of an `*_async` function. Once spawned, the callback is
+/// also called if the task is dropped without completing, which happens when the runtime shuts
+/// down.
+pub struct Completion<T: Empty> {
+    handle: usize,
+    callback: libsql_callback<T>,
+    user_data: SendPtr<c_void>,
+    spawned: bool,
+}
+
+impl<T: Empty + 'static> Completion<T> {
+    /// Spawns `fut` and calls back with its output mapped through `convert`. A panic while
+    /// polling poisons the handle the call was made on and is reported as `LIBSQL_ERR_PANIC`.
+    pub fn spawn<R: Send + 'static>(
+        mut self,
+        fut: impl Future<Output = Result<R, libsql_error>> + Send + 'static,
+        convert: impl FnOnce(R) -> T + Send + 'static,
+    ) {
+        self.spawned = true;
+        let task = RT.spawn(fut);
+        RT.spawn(async move {
+            let result = match task.await {
+                Ok(result) => result,
+                Err(e) if e.is_cancelled() => Err(shut_down()),
+                Err(e) => {
+                    poison(self.handle as *const ());
+                    Err(match e.try_into_panic() {
+                        Ok(payload) => panic_error(payload),
+                        Err(e) => {
+                            libsql_error::new(LIBSQL_ERR_UNKNOWN, format!("Task failed: {e}"))
+                        }
+                    })
+                }
+            };
+            let result = result.and_then(|value| {
+                catch_unwind(AssertUnwindSafe(|| convert(value))).map_err(panic_error)
+            });
+            match result {
+                Ok(value) => self.call(value, null()),
+                Err(e) => self.call(T::empty(), e.into_raw()),
+            }
+        });
+    }
+}
+
+impl<T: Empty> Completion<T> {
+    fn call(&mut self, value: T, err: *const libsql_error) {
+        // Called at most once, dropping it afterwards does nothing.
+        self.spawned = false;
+        unsafe { (self.callback)(self.user_data.get(), value, err) };
+    }
+}
+
+impl<T: Empty> Drop for Completion<T> {
+    fn drop(&mut self) {
+        // Not spawned means the exported function failed, the callback must not run then.
+        if self.spawned {
+            self.call(T::empty(), shut_down().into_raw());
+        }
+    }
+}
+
+fn shut_down() -> libsql_error {
+    libsql_error::new(LIBSQL_ERR_MISUSE, "The runtime was shut down")
+}
+
+/// `guard` for the `*_async` functions, `f` receives the completion it must spawn the
+/// operation with once the arguments are checked.
+pub unsafe fn guard_async<H: 'static, T: Empty + 'static>(
+    handle: *const H,
+    callback: Option<libsql_callback<T>>,
+    user_data: *mut c_void,
+    out_err: *mut *const libsql_error,
+    f: impl FnOnce(*mut H, Completion<T>) -> c_int,
+) -> c_int {
+    let Some(callback) = callback else {
+        return set_err(
+            libsql_error::invalid_argument("Missing completion callback"),
+            out_err,
+        );
+    };
+    guard(handle, out_err, |ptr| {
+        let completion = Completion {
+            handle: handle as usize,
+            callback,
+            user_data: SendPtr::new(user_data),
+            spawned: false,
+        };
+        f(ptr, completion)
+    })
+}
@@ -0,0 +1,512 @@
+// HTTP settings of the connections to a remote server: an HTTP CONNECT proxy to tunnel through
+// and extra headers sent with every request. libsql doesn't expose the requests of every mode,
+// so the headers are written by the connector into the head of each HTTP/1 request, following
+// the bodies to find the next head. Requests it can't add them to fail rather than going out
+// without them, and HTTP/2 can't be combined with headers. Remote replicas also set them from
+// `request_callback`, those already in a request aren't added again. The connections also count
+// the bytes they receive, which is how sync progress is measured.
+
+use std::{
+    error::Error,
+    ffi::{c_char, c_int, CStr},
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{ready, Context, Poll},
+};
+
+use hyper::{
+    client::{
+        connect::{Connected, Connection},
+        HttpConnector,
+    },
+    header::{HeaderName, HeaderValue},
+    service::Service,
+    Uri,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::TcpStream,
+};
+
+use crate::error::libsql_error;
+use crate::tls::TlsOptions;
+use crate::types::LIBSQL_ERR_NOT_SUPPORTED;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+type BoxFuture<T> = Pin<Box<dyn Future<Output = Result<T, BoxError>> + Send>>;
+
+/// Longest request or proxy response head looked at.
+const MAX_HEAD: usize = 64 * 1024;
+
+pub type Connector = HeaderConnector<HttpsConnector<ProxyConnector>>;
+
+#[derive(Clone, Default)]
+pub struct HttpOptions {
+    proxy: Option<Uri>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl HttpOptions {
+    /// Reads a proxy URL and `headers_len` "Name: value" headers, both may be null.
+    pub unsafe fn from_raw(
+        proxy_url: *const c_char,
+        headers: *const *const c_char,
+        headers_len: c_int,
+    ) -> Result<HttpOptions, libsql_error> {
+        let mut options = HttpOptions::default();
+        if !proxy_url.is_null() {
+            let url = CStr::from_ptr(proxy_url)
+                .to_str()
+                .map_err(|e| libsql_error::invalid_argument(format!("Wrong proxy URL: {e}")))?;
+            options.set_proxy(Some(url))?;
+        }
+        if headers.is_null() || headers_len <= 0 {
+            return Ok(options);
+        }
+        for &header in std::slice::from_raw_parts(headers, headers_len as usize) {
+            if header.is_null() {
+                return Err(libsql_error::invalid_argument("Missing header"));
+            }
+            let header = CStr::from_ptr(header)
+                .to_str()
+                .map_err(|e| libsql_error::invalid_argument(format!("Wrong header: {e}")))?;
+            let Some((name, value)) = header.split_once(':') else {
+                return Err(libsql_error::invalid_argument(format!(
+                    "Wrong header {header}, expected \"Name: value\""
+                )));
+            };
+            options.add_header(name.trim(), value.trim())?;
+        }
+        Ok(options)
+    }
+
+    /// Tunnels through the `http://` proxy at `url`, none connects directly.
+    pub fn set_proxy(&mut self, url: Option<&str>) -> Result<(), libsql_error> {
+        let Some(url) = url else {
+            self.proxy = None;
+            return Ok(());
+        };
+        let proxy: Uri = url
+            .parse()
+            .map_err(|e| libsql_error::invalid_argument(format!("Wrong proxy URL {url}: {e}")))?;
+        if proxy.scheme_str() != Some("http") || proxy.host().is_none() {
+            return Err(libsql_error::invalid_argument(format!(
+                "Wrong proxy URL {url}, expected http://host:port"
+            )));
+        }
+        if proxy.authority().is_some_and(|a| a.as_str().contains('@')) {
+            return Err(libsql_error::new(
+                LIBSQL_ERR_NOT_SUPPORTED,
+                "Proxy credentials are not supported",
+            ));
+        }
+        self.proxy = Some(proxy);
+        Ok(())
+    }
+
+    pub fn add_header(&mut self, name: &str, value: &str) -> Result<(), libsql_error> {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| {
+            libsql_error::invalid_argument(format!("Wrong header name {name}: {e}"))
+        })?;
+        let value = HeaderValue::from_str(value).map_err(|e| {
+            libsql_error::invalid_argument(format!("Wrong value of header {name}: {e}"))
+        })?;
+        self.headers.push((name, value));
+        Ok(())
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.proxy.is_none() && self.headers.is_empty()
+    }
+
+    /// Sets the headers on the requests libsql lets us see, none without headers.
+    pub fn request_callback(
+        &self,
+    ) -> Option<impl Fn(&mut hyper::Request<()>) + Send + Sync + 'static> {
+        if self.headers.is_empty() {
+            return None;
+        }
+        let headers = self.headers.clone();
+        Some(move |request: &mut hyper::Request<()>| {
+            for (name, value) in &headers {
+                request.headers_mut().insert(name.clone(), value.clone());
+            }
+        })
+    }
+}
+
+/// The connector to use, none to keep libsql's default one. `received` adds up the bytes read
+/// from every connection.
+pub fn connector(
+    tls: &TlsOptions,
+    http: &HttpOptions,
+    received: Option<Arc<AtomicU64>>,
+) -> Result<Option<Connector>, libsql_error> {
+    if tls.is_default() && http.is_default() && received.is_none() {
+        return Ok(None);
+    }
+    if tls.http2 && !http.headers.is_empty() {
+        return Err(libsql_error::new(
+            LIBSQL_ERR_NOT_SUPPORTED,
+            "Custom headers can't be sent over HTTP/2",
+        ));
+    }
+    let mut tcp = HttpConnector::new();
+    tcp.enforce_http(false);
+    tcp.set_nodelay(true);
+    let proxy = ProxyConnector {
+        tcp,
+        proxy: http.proxy.clone(),
+    };
+    let builder = HttpsConnectorBuilder::new()
+        .with_tls_config(tls.client_config()?)
+        .https_or_http()
+        .enable_http1();
+    let https = if tls.http2 {
+        builder.enable_http2().wrap_connector(proxy)
+    } else {
+        builder.wrap_connector(proxy)
+    };
+    Ok(Some(HeaderConnector {
+        inner: https,
+        headers: http.headers.clone().into(),
+        received,
+    }))
+}
+
+/// Opens TCP connections, through an HTTP CONNECT tunnel when a proxy is set.
+#[derive(Clone)]
+pub struct ProxyConnector {
+    tcp: HttpConnector,
+    proxy: Option<Uri>,
+}
+
+impl Service<Uri> for ProxyConnector {
+    type Response = TcpStream;
+    type Error = BoxError;
+    type Future = BoxFuture<TcpStream>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.tcp.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let mut tcp = self.tcp.clone();
+        let proxy = self.proxy.clone();
+        Box::pin(async move {
+            let Some(proxy) = proxy else {
+                return Ok(tcp.call(dst).await?);
+            };
+            let mut stream = tcp.call(proxy).await?;
+            tunnel(&mut stream, &dst).await?;
+            Ok(stream)
+        })
+    }
+}
+
+async fn tunnel(stream: &mut TcpStream, dst: &Uri) -> Result<(), BoxError> {
+    let host = dst.host().ok_or("Missing host in the URL")?;
+    let port = dst
+        .port_u16()
+        .unwrap_or(if dst.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+    let request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Byte by byte, so nothing the server sends through the tunnel is consumed
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > MAX_HEAD {
+            return Err("Proxy response head is too large".into());
+        }
+        response.push(stream.read_u8().await?);
+    }
+    let status_line = response.split(|&b| b == b'\r').next().unwrap_or_default();
+    let status = status_line.split(|&b| b == b' ').nth(1).unwrap_or_default();
+    if !status.starts_with(b"2") {
+        return Err(format!(
+            "Proxy refused the tunnel to {host}:{port}: {}",
+            String::from_utf8_lossy(status_line)
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Wraps the connections of `inner` to add the extra headers to their requests.
+#[derive(Clone)]
+pub struct HeaderConnector<C> {
+    inner: C,
+    headers: Arc<[(HeaderName, HeaderValue)]>,
+    received: Option<Arc<AtomicU64>>,
+}
+
+impl<C> Service<Uri> for HeaderConnector<C>
+where
+    C: Service<Uri>,
+    C::Response: Connection + Send + 'static,
+    C::Error: Into<BoxError>,
+    C::Future: Send + 'static,
+{
+    type Response = HeaderStream<C::Response>;
+    type Error = BoxError;
+    type Future = BoxFuture<HeaderStream<C::Response>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting = self.inner.call(dst);
+        let headers = self.headers.clone();
+        let received = self.received.clone();
+        Box::pin(async move {
+            let inner = connecting.await.map_err(Into::into)?;
+            // HTTP/2 requests go out in binary frames
+            if !headers.is_empty() && inner.connected().is_negotiated_h2() {
+                return Err("Custom headers can't be sent over HTTP/2".into());
+            }
+            Ok(HeaderStream {
+                inner,
+                passthrough: headers.is_empty(),
+                headers,
+                state: State::Head(Vec::new()),
+                pending: Vec::new(),
+                written: 0,
+                received,
+            })
+        })
+    }
+}
+
+/// Where a `HeaderStream` is in the requests written to it.
+enum State {
+    /// Buffering a request head.
+    Head(Vec<u8>),
+    /// Bytes left of a `Content-Length` body.
+    Body(u64),
+    /// Reading the size line of a chunk.
+    ChunkSize(Vec<u8>),
+    /// Bytes left of a chunk, followed by its line end.
+    Chunk(u64),
+    /// Bytes left of the line end of a chunk.
+    ChunkEnd(u8),
+    /// Reading the trailer lines after the last chunk, until an empty one.
+    Trailers(Vec<u8>),
+}
+
+/// Buffers each request head until its end to write it back with the extra headers, then
+/// forwards its `Content-Length` or chunked body. Writes of a request it can't add them to fail.
+/// Upgraded connections are forwarded as is once the upgrade request is written.
+pub struct HeaderStream<S> {
+    inner: S,
+    headers: Arc<[(HeaderName, HeaderValue)]>,
+    state: State,
+    pending: Vec<u8>,
+    written: usize,
+    passthrough: bool,
+    received: Option<Arc<AtomicU64>>,
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Custom headers can't be added to {what}"),
+    )
+}
+
+impl<S: AsyncWrite + Unpin> HeaderStream<S> {
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.pending.len() {
+            let n =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Queues `head` with the extra headers it doesn't have yet and returns the state of its body.
+    fn end_head(&mut self, head: Vec<u8>) -> io::Result<State> {
+        let line_end = head.windows(2).position(|w| w == b"\r\n").unwrap_or(0) + 2;
+        let request_line = &head[..line_end - 2];
+        if !request_line.ends_with(b" HTTP/1.1") && !request_line.ends_with(b" HTTP/1.0") {
+            return Err(unsupported("requests other than HTTP/1"));
+        }
+        let mut state = State::Head(Vec::new());
+        let mut present = Vec::new();
+        for line in head[line_end..].split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let Some(colon) = line.iter().position(|&b| b == b':') else {
+                continue;
+            };
+            let (name, value) = (&line[..colon], line[colon + 1..].trim_ascii());
+            if name.eq_ignore_ascii_case(b"content-length") {
+                match std::str::from_utf8(value).ok().and_then(|v| v.parse().ok()) {
+                    Some(0) => {}
+                    Some(len) => state = State::Body(len),
+                    None => return Err(unsupported("requests with a wrong Content-Length")),
+                }
+            } else if name.eq_ignore_ascii_case(b"transfer-encoding") {
+                if !value.to_ascii_lowercase().ends_with(b"chunked") {
+                    return Err(unsupported(
+                        "requests with a Transfer-Encoding other than chunked",
+                    ));
+                }
+                state = State::ChunkSize(Vec::new());
+            } else if name.eq_ignore_ascii_case(b"upgrade") {
+                self.passthrough = true;
+            }
+            present.push(name);
+        }
+        self.pending.extend_from_slice(&head[..line_end]);
+        for (name, value) in self.headers.iter() {
+            let name = name.as_str().as_bytes();
+            if present.iter().any(|p| p.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            self.pending.extend_from_slice(name);
+            self.pending.extend_from_slice(b": ");
+            self.pending.extend_from_slice(value.as_bytes());
+            self.pending.extend_from_slice(b"\r\n");
+        }
+        self.pending.extend_from_slice(&head[line_end..]);
+        Ok(state)
+    }
+
+    /// Takes `byte` of a request head, a chunk size line, a chunk line end or the trailers.
+    fn push(&mut self, byte: u8) -> io::Result<()> {
+        if !matches!(self.state, State::Head(_)) {
+            self.pending.push(byte);
+        }
+        self.state = match std::mem::replace(&mut self.state, State::Body(0)) {
+            State::Head(mut head) => {
+                head.push(byte);
+                if head.ends_with(b"\r\n\r\n") {
+                    self.end_head(head)?
+                } else if head.len() > MAX_HEAD {
+                    return Err(unsupported("requests with a head this large"));
+                } else {
+                    State::Head(head)
+                }
+            }
+            State::ChunkSize(mut line) => {
+                line.push(byte);
+                if !line.ends_with(b"\r\n") {
+                    if line.len() > MAX_HEAD {
+                        return Err(unsupported("requests with a chunk size line this large"));
+                    }
+                    State::ChunkSize(line)
+                } else {
+                    let size = line[..line.len() - 2].split(|&b| b == b';').next();
+                    let size = size
+                        .and_then(|size| std::str::from_utf8(size.trim_ascii()).ok())
+                        .and_then(|size| u64::from_str_radix(size, 16).ok())
+                        .ok_or_else(|| unsupported("requests with a wrong chunk size"))?;
+                    match size {
+                        0 => State::Trailers(Vec::new()),
+                        size => State::Chunk(size),
+                    }
+                }
+            }
+            State::ChunkEnd(1) => State::ChunkSize(Vec::new()),
+            State::ChunkEnd(left) => State::ChunkEnd(left - 1),
+            State::Trailers(mut line) => {
+                line.push(byte);
+                if line == b"\r\n" {
+                    State::Head(Vec::new())
+                } else if line.ends_with(b"\r\n") {
+                    State::Trailers(Vec::new())
+                } else if line.len() > MAX_HEAD {
+                    return Err(unsupported("requests with trailers this large"));
+                } else {
+                    State::Trailers(line)
+                }
+            }
+            State::Body(_) | State::Chunk(_) => unreachable!("body bytes are written as is"),
+        };
+        Ok(())
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for HeaderStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if this.passthrough {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+        if let State::Body(left) | State::Chunk(left) = this.state {
+            let len = left.min(buf.len() as u64) as usize;
+            let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..len]))?;
+            let left = left - n as u64;
+            this.state = match this.state {
+                State::Body(_) if left == 0 => State::Head(Vec::new()),
+                State::Body(_) => State::Body(left),
+                _ if left == 0 => State::ChunkEnd(2),
+                _ => State::Chunk(left),
+            };
+            return Poll::Ready(Ok(n));
+        }
+        let mut consumed = 0;
+        for &byte in buf {
+            this.push(byte)?;
+            consumed += 1;
+            if this.passthrough || matches!(this.state, State::Body(_) | State::Chunk(_)) {
+                break;
+            }
+        }
+        Poll::Ready(Ok(consumed))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for HeaderStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        if let Some(received) = &this.received {
+            received.fetch_add((buf.filled().len() - filled) as u64, Ordering::Relaxed);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: Connection> Connection for HeaderStream<S> {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
@@ -0,0 +1,203 @@
+use std::ffi::{c_char, c_int, CString};
+
+use crate::types::{
+    LIBSQL_ERR_CONNECTION, LIBSQL_ERR_INVALID_ARGUMENT, LIBSQL_ERR_MISUSE,
+    LIBSQL_ERR_NOT_SUPPORTED, LIBSQL_ERR_OUT_OF_RANGE, LIBSQL_ERR_SQLITE, LIBSQL_ERR_SYNC,
+    LIBSQL_ERR_TYPE_MISMATCH, LIBSQL_ERR_UNKNOWN,
+};
+
+/// Error handed to the host through the `out_err` parameter of every fallible function.
+///
+/// `kind` is one of the `LIBSQL_ERR_*` constants and is also the value returned by the
+/// function. `code`/`extended_code` hold the SQLite result code when the failure comes
+/// from SQLite (locally or reported by a remote server), otherwise they are 0.
+pub struct libsql_error {
+    kind: c_int,
+    code: c_int,
+    extended_code: c_int,
+    message: CString,
+}
+
+impl libsql_error {
+    pub fn new(kind: c_int, message: impl Into<String>) -> Self {
+        Self::with_code(kind, 0, message)
+    }
+
+    pub fn with_code(kind: c_int, extended_code: c_int, message: impl Into<String>) -> Self {
+        // Interior NULs would truncate the message on the C side anyway, so drop them.
+        let message = message.into().replace('\0', "");
+        libsql_error {
+            kind,
+            code: extended_code & 0xff,
+            extended_code,
+            message: CString::new(message).unwrap_or_default(),
+        }
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(LIBSQL_ERR_INVALID_ARGUMENT, message)
+    }
+
+    pub fn type_mismatch(message: impl Into<String>) -> Self {
+        Self::new(LIBSQL_ERR_TYPE_MISMATCH, message)
+    }
+
+    pub fn out_of_range(message: impl Into<String>) -> Self {
+        Self::new(LIBSQL_ERR_OUT_OF_RANGE, message)
+    }
+
+    /// Hands the error over to the host, which releases it with `libsql_free_error`.
+    pub fn into_raw(self) -> *const libsql_error {
+        Box::into_raw(Box::new(self))
+    }
+
+    /// Classifies a `libsql::Error`, prefixing its message with `context`.
+    pub fn from_libsql(context: &str, e: libsql::Error) -> Self {
+        use libsql::Error;
+
+        let message = format!("{context}: {e}");
+        match e {
+            Error::SqliteFailure(code, _) => Self::with_code(LIBSQL_ERR_SQLITE, code, message),
+            Error::RemoteSqliteFailure(_, extended_code, _) => {
+                Self::with_code(LIBSQL_ERR_SQLITE, extended_code, message)
+            }
+            Error::Hrana(ref inner) => match sqlite_code_from_name(&inner.to_string()) {
+                Some(code) => Self::with_code(LIBSQL_ERR_SQLITE, code, message),
+                None => Self::new(LIBSQL_ERR_CONNECTION, message),
+            },
+            Error::ConnectionFailed(_)
+            | Error::WriteDelegation(_)
+            | Error::InvalidTlsConfiguration(_) => Self::new(LIBSQL_ERR_CONNECTION, message),
+            Error::Replication(_) | Error::Sync(_) | Error::WalConflict => {
+                Self::new(LIBSQL_ERR_SYNC, message)
+            }
+            Error::SyncNotSupported(_)
+            | Error::LoadExtensionNotSupported
+            | Error::AuthorizerNotSupported
+            | Error::UpdateHookNotSupported
+            | Error::FreezeNotSupported(_)
+            | Error::ReservedBytesNotSupported
+            | Error::Sqlite3UnsupportedStatement => Self::new(LIBSQL_ERR_NOT_SUPPORTED, message),
+            Error::Misuse(_) | Error::ExecuteReturnedRows | Error::InvalidParserState(_) => {
+                Self::new(LIBSQL_ERR_MISUSE, message)
+            }
+            Error::InvalidColumnIndex | Error::ColumnNotFound(_) => {
+                Self::new(LIBSQL_ERR_OUT_OF_RANGE, message)
+            }
+            Error::InvalidColumnType | Error::NullValue => {
+                Self::new(LIBSQL_ERR_TYPE_MISMATCH, message)
+            }
+            Error::InvalidUTF8Path | Error::ToSqlConversionFailure(_) => {
+                Self::new(LIBSQL_ERR_INVALID_ARGUMENT, message)
+            }
+            _ => Self::new(LIBSQL_ERR_UNKNOWN, message),
+        }
+    }
+}
+
+impl std::fmt::Display for libsql_error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message.to_string_lossy())
+    }
+}
+
+/// Remote servers report SQLite failures by name (e.g. `SQLITE_CONSTRAINT_UNIQUE`) inside
+/// the hrana error message, map the most relevant ones back to their numeric code.
+fn sqlite_code_from_name(message: &str) -> Option<c_int> {
+    const CODES: &[(&str, c_int)] = &[
+        ("SQLITE_CONSTRAINT_CHECK", 275),
+        ("SQLITE_CONSTRAINT_FOREIGNKEY", 787),
+        ("SQLITE_CONSTRAINT_NOTNULL", 1299),
+        ("SQLITE_CONSTRAINT_PRIMARYKEY", 1555),
+        ("SQLITE_CONSTRAINT_TRIGGER", 1811),
+        ("SQLITE_CONSTRAINT_UNIQUE", 2067),
+        ("SQLITE_CONSTRAINT_ROWID", 2579),
+        ("SQLITE_BUSY_RECOVERY", 261),
+        ("SQLITE_BUSY_SNAPSHOT", 517),
+        ("SQLITE_BUSY_TIMEOUT", 773),
+        ("SQLITE_LOCKED_SHAREDCACHE", 262),
+        ("SQLITE_READONLY_DBMOVED", 1032),
+        ("SQLITE_ERROR", 1),
+        ("SQLITE_INTERNAL", 2),
+        ("SQLITE_PERM", 3),
+        ("SQLITE_ABORT", 4),
+        ("SQLITE_BUSY", 5),
+        ("SQLITE_LOCKED", 6),
+        ("SQLITE_NOMEM", 7),
+        ("SQLITE_READONLY", 8),
+        ("SQLITE_INTERRUPT", 9),
+        ("SQLITE_IOERR", 10),
+        ("SQLITE_CORRUPT", 11),
+        ("SQLITE_NOTFOUND", 12),
+        ("SQLITE_FULL", 13),
+        ("SQLITE_CANTOPEN", 14),
+        ("SQLITE_PROTOCOL", 15),
+        ("SQLITE_EMPTY", 16),
+        ("SQLITE_SCHEMA", 17),
+        ("SQLITE_TOOBIG", 18),
+        ("SQLITE_CONSTRAINT", 19),
+        ("SQLITE_MISMATCH", 20),
+        ("SQLITE_MISUSE", 21),
+        ("SQLITE_NOLFS", 22),
+        ("SQLITE_AUTH", 23),
+        ("SQLITE_FORMAT", 24),
+        ("SQLITE_RANGE", 25),
+        ("SQLITE_NOTADB", 26),
+    ];
+    CODES
+        .iter()
+        .find(|(name, _)| {
+            message.match_indices(name).any(|(idx, _)| {
+                let next = message.as_bytes().get(idx + name.len());
+                !matches!(next, Some(c) if c.is_ascii_alphanumeric() || *c == b'_')
+            })
+        })
+        .map(|(_, code)| *code)
+}
+
+/// Writes `err` into `output` (if the host asked for it) and returns its kind, so callers can
+/// simply `return set_err(...)`.
+pub unsafe fn set_err(err: libsql_error, output: *mut *const libsql_error) -> c_int {
+    let kind = err.kind;
+    if !output.is_null() {
+        *output = err.into_raw();
+    }
+    kind
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_error_kind(err: *const libsql_error) -> c_int {
+    debug_assert!(!err.is_null());
+
+    (*err).kind
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_error_code(err: *const libsql_error) -> c_int {
+    debug_assert!(!err.is_null());
+
+    (*err).code
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_error_extended_code(err: *const libsql_error) -> c_int {
+    debug_assert!(!err.is_null());
+
+    (*err).extended_code
+}
+
+/// The returned string is owned by the error and valid until `libsql_free_error`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_error_message(err: *const libsql_error) -> *const c_char {
+    debug_assert!(!err.is_null());
+
+    (*err).message.as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_free_error(err: *mut libsql_error) {
+    if err.is_null() {
+        return;
+    }
+    let _ = Box::from_raw(err);
+}
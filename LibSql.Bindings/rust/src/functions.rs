@@ -0,0 +1,514 @@
+// User-defined SQL functions, registered on the SQLite connection of local and replica
+// connections. SQLite calls them from the thread running the statement, which hands the
+// arguments to the host callback as `libsql_value`s, and the callback sets the result with the
+// `libsql_result_*` functions. The functions are unregistered when the connection is detached
+// from its handle, so that those of a pooled connection don't outlive its release.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_char, c_double, c_int, c_longlong, c_void, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Mutex, MutexGuard},
+};
+
+use libsql::ffi;
+
+use crate::error::{libsql_error, set_err};
+use crate::guard::{guard, panic_message};
+use crate::raw::{raw_connection, sqlite_error};
+use crate::types::{
+    blob, libsql_aggregate_result_callback, libsql_aggregate_step_callback,
+    libsql_destroy_callback, libsql_function_context, libsql_scalar_function_callback,
+    libsql_value, LIBSQL_BLOB, LIBSQL_FLOAT, LIBSQL_INT, LIBSQL_NULL, LIBSQL_TEXT,
+};
+
+lazy_static! {
+    // Names and arities of the functions registered on each connection, keyed by the address of
+    // the connection.
+    static ref REGISTERED: Mutex<HashMap<usize, HashSet<(CString, c_int)>>> = Mutex::default();
+}
+
+fn registered() -> MutexGuard<'static, HashMap<usize, HashSet<(CString, c_int)>>> {
+    REGISTERED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Unregisters the functions registered on `conn` from `raw_conn`, destroying their user data.
+pub unsafe fn detach(conn: *const libsql::Connection, raw_conn: *mut ffi::sqlite3) {
+    let Some(functions) = registered().remove(&(conn as usize)) else {
+        return;
+    };
+    for (name, n_args) in functions {
+        let rc = ffi::sqlite3_create_function_v2(
+            raw_conn,
+            name.as_ptr(),
+            n_args,
+            ffi::SQLITE_UTF8 as c_int,
+            std::ptr::null_mut(),
+            None,
+            None,
+            None,
+            None,
+        );
+        if rc != ffi::SQLITE_OK as c_int {
+            let e = sqlite_error(raw_conn, rc, "Error removing function");
+            tracing::warn!("{name:?} stays registered: {e}");
+        }
+    }
+}
+
+/// Callbacks registered on a connection and their user data, given to `destroy` once dropped.
+/// Those of functions and collations are owned by SQLite as their app data.
+pub struct Function<C> {
+    pub callbacks: C,
+    pub user_data: usize,
+    pub destroy: Option<libsql_destroy_callback>,
+}
+
+impl<C> Function<C> {
+    pub fn user_data(&self) -> *mut c_void {
+        self.user_data as *mut c_void
+    }
+}
+
+impl<C> Drop for Function<C> {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.destroy {
+            unsafe { destroy(self.user_data()) };
+        }
+    }
+}
+
+pub unsafe extern "C" fn destroy_function<C>(app: *mut c_void) {
+    drop(Box::from_raw(app as *mut Function<C>));
+}
+
+/// Function whose callbacks SQLite is calling with `ctx`.
+pub unsafe fn function<'a, C>(ctx: *mut ffi::sqlite3_context) -> &'a Function<C> {
+    &*(ffi::sqlite3_user_data(ctx) as *const Function<C>)
+}
+
+/// Reads the arguments of a call, they borrow from SQLite until the call returns.
+pub unsafe fn read_args(argc: c_int, argv: *mut *mut ffi::sqlite3_value) -> Vec<libsql_value> {
+    let argv = match argc {
+        0 => &[][..],
+        argc => std::slice::from_raw_parts(argv, argc as usize),
+    };
+    argv.iter().map(|value| read_value(*value)).collect()
+}
+
+unsafe fn read_value(value: *mut ffi::sqlite3_value) -> libsql_value {
+    let mut arg = libsql_value {
+        value_type: LIBSQL_NULL as c_int,
+        int_value: 0,
+        float_value: 0.0,
+        bytes: blob {
+            ptr: std::ptr::null(),
+            len: 0,
+        },
+    };
+    // The pointer first, reading the length after any conversion it made.
+    match ffi::sqlite3_value_type(value) {
+        ffi::SQLITE_INTEGER => {
+            arg.value_type = LIBSQL_INT as c_int;
+            arg.int_value = ffi::sqlite3_value_int64(value);
+        }
+        ffi::SQLITE_FLOAT => {
+            arg.value_type = LIBSQL_FLOAT as c_int;
+            arg.float_value = ffi::sqlite3_value_double(value);
+        }
+        ffi::SQLITE_TEXT => {
+            arg.value_type = LIBSQL_TEXT as c_int;
+            arg.bytes.ptr = ffi::sqlite3_value_text(value) as *const c_char;
+            arg.bytes.len = ffi::sqlite3_value_bytes(value);
+        }
+        ffi::SQLITE_BLOB => {
+            arg.value_type = LIBSQL_BLOB as c_int;
+            arg.bytes.ptr = ffi::sqlite3_value_blob(value) as *const c_char;
+            arg.bytes.len = ffi::sqlite3_value_bytes(value);
+        }
+        _ => {}
+    }
+    arg
+}
+
+/// Runs `f`, failing the call with the panic message if it panics.
+pub unsafe fn catch_call(ctx: *mut ffi::sqlite3_context, f: impl FnOnce()) {
+    if let Err(payload) = catch_unwind(AssertUnwindSafe(f)) {
+        let message = format!("Internal panic: {}", panic_message(&*payload));
+        tracing::error!("{message}");
+        ffi::sqlite3_result_error(
+            ctx,
+            message.as_ptr() as *const c_char,
+            message.len() as c_int,
+        );
+    }
+}
+
+unsafe extern "C" fn call_scalar(
+    ctx: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    catch_call(ctx, || {
+        let function = function::<libsql_scalar_function_callback>(ctx);
+        let args = read_args(argc, argv);
+        (function.callbacks)(function.user_data(), ctx, argc, args.as_ptr());
+    })
+}
+
+/// Reads the name of a function or collation.
+pub unsafe fn read_name<'a>(name: *const c_char) -> Result<&'a CStr, libsql_error> {
+    if name.is_null() {
+        return Err(libsql_error::invalid_argument("Missing name"));
+    }
+    let name = CStr::from_ptr(name);
+    match name.to_str() {
+        Ok(_) => Ok(name),
+        Err(e) => Err(libsql_error::invalid_argument(format!("Wrong name: {e}"))),
+    }
+}
+
+/// Text encoding and flags of a function.
+pub fn function_flags(deterministic: bool) -> c_int {
+    let mut flags = ffi::SQLITE_UTF8 as c_int;
+    if deterministic {
+        flags |= ffi::SQLITE_DETERMINISTIC as c_int;
+    }
+    flags
+}
+
+/// Fails a registration missing one of its callbacks, destroying `user_data` as registering does
+/// when it fails.
+pub unsafe fn missing_callback(
+    what: &str,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    if let Some(destroy) = destroy {
+        destroy(user_data);
+    }
+    set_err(
+        libsql_error::invalid_argument(format!("Missing {what} callback")),
+        out_err,
+    )
+}
+
+/// Registers `function` on the SQLite connection of `conn` with `register`, which is given the
+/// connection, the name and the app data, and returns the SQLite result code.
+unsafe fn create_function<C>(
+    conn: *const libsql::Connection,
+    name: *const c_char,
+    n_args: c_int,
+    function: Function<C>,
+    out_err: *mut *const libsql_error,
+    register: impl FnOnce(*mut ffi::sqlite3, &CStr, *mut c_void) -> c_int,
+) -> c_int {
+    // Dropped on failures before SQLite takes it, destroying the user data.
+    let function = Box::new(function);
+    guard(conn, out_err, |conn| {
+        let (conn, raw_conn, name) = match raw_connection(conn)
+            .and_then(|(conn, raw_conn)| Ok((conn, raw_conn, read_name(name)?)))
+        {
+            Ok(found) => found,
+            Err(e) => return set_err(e, out_err),
+        };
+        // SQLite owns the function from here, destroying it even if this fails.
+        let rc = register(raw_conn, name, Box::into_raw(function) as *mut c_void);
+        if rc != ffi::SQLITE_OK as c_int {
+            return set_err(
+                sqlite_error(raw_conn, rc, "Error creating function"),
+                out_err,
+            );
+        }
+        let key = (name.to_owned(), n_args);
+        registered().entry(conn as usize).or_default().insert(key);
+        0
+    })
+}
+
+/// Registers a scalar function taking `n_args` arguments, or any number of them if -1, replacing
+/// the function of the same name and arity. Functions registered as `deterministic` must return
+/// the same result for the same arguments, which lets SQLite use them in indexes. `destroy` is
+/// called with `user_data` once the function is replaced, the connection is disconnected or
+/// released to its pool, or registering it fails. Only available on connections to local
+/// databases and replicas, other connections fail with `LIBSQL_ERR_NOT_SUPPORTED`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_create_scalar_function(
+    conn: *const libsql::Connection,
+    name: *const c_char,
+    n_args: c_int,
+    deterministic: bool,
+    callback: Option<libsql_scalar_function_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let Some(callback) = callback else {
+        return missing_callback("function", user_data, destroy, out_err);
+    };
+    let function = Function {
+        callbacks: callback,
+        user_data: user_data as usize,
+        destroy,
+    };
+    create_function(
+        conn,
+        name,
+        n_args,
+        function,
+        out_err,
+        |raw_conn, name, app| {
+            ffi::sqlite3_create_function_v2(
+                raw_conn,
+                name.as_ptr(),
+                n_args,
+                function_flags(deterministic),
+                app,
+                Some(call_scalar),
+                None,
+                None,
+                Some(destroy_function::<libsql_scalar_function_callback>),
+            )
+        },
+    )
+}
+
+struct Aggregate {
+    step: libsql_aggregate_step_callback,
+    final_: libsql_aggregate_result_callback,
+    value: Option<libsql_aggregate_result_callback>,
+    inverse: Option<libsql_aggregate_step_callback>,
+}
+
+/// Slot holding the host state of the group being aggregated, allocated by SQLite with the group
+/// and zeroed. Null if `allocate` is false and the group had no rows yet, or if out of memory.
+unsafe fn group_state(ctx: *mut ffi::sqlite3_context, allocate: bool) -> *mut *mut c_void {
+    let size = if allocate {
+        std::mem::size_of::<*mut c_void>() as c_int
+    } else {
+        0
+    };
+    ffi::sqlite3_aggregate_context(ctx, size) as *mut *mut c_void
+}
+
+unsafe fn call_step(
+    ctx: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+    step: impl FnOnce(&Aggregate) -> libsql_aggregate_step_callback,
+) {
+    catch_call(ctx, || {
+        let function = function::<Aggregate>(ctx);
+        let state = group_state(ctx, true);
+        if state.is_null() {
+            return ffi::sqlite3_result_error_nomem(ctx);
+        }
+        let args = read_args(argc, argv);
+        step(&function.callbacks)(function.user_data(), ctx, state, argc, args.as_ptr());
+    })
+}
+
+unsafe extern "C" fn call_aggregate_step(
+    ctx: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    call_step(ctx, argc, argv, |aggregate| aggregate.step)
+}
+
+unsafe extern "C" fn call_aggregate_inverse(
+    ctx: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    // Only registered along with it.
+    call_step(ctx, argc, argv, |aggregate| aggregate.inverse.unwrap())
+}
+
+unsafe extern "C" fn call_aggregate_final(ctx: *mut ffi::sqlite3_context) {
+    catch_call(ctx, || {
+        let function = function::<Aggregate>(ctx);
+        let state = group_state(ctx, false);
+        let state = if state.is_null() {
+            std::ptr::null_mut()
+        } else {
+            std::mem::replace(&mut *state, std::ptr::null_mut())
+        };
+        (function.callbacks.final_)(function.user_data(), ctx, state);
+    })
+}
+
+unsafe extern "C" fn call_aggregate_value(ctx: *mut ffi::sqlite3_context) {
+    catch_call(ctx, || {
+        let function = function::<Aggregate>(ctx);
+        let state = group_state(ctx, false);
+        let state = if state.is_null() {
+            std::ptr::null_mut()
+        } else {
+            *state
+        };
+        // Only registered along with it.
+        (function.callbacks.value.unwrap())(function.user_data(), ctx, state);
+    })
+}
+
+/// Registers an aggregate function taking `n_args` arguments, or any number of them if -1.
+/// `step` is called for each row of a group with the state of the group, a slot kept by SQLite
+/// that starts NULL and where the callback may store any pointer. `final` is called once per
+/// group, including groups the statement stopped aggregating because it failed or was reset, to
+/// set the result from the state it must release, which is NULL if the group had no rows. Like
+/// scalar functions, it replaces the function of the same name and arity and `destroy` is called
+/// with `user_data` once it can't be called anymore.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_create_aggregate_function(
+    conn: *const libsql::Connection,
+    name: *const c_char,
+    n_args: c_int,
+    deterministic: bool,
+    step: Option<libsql_aggregate_step_callback>,
+    final_: Option<libsql_aggregate_result_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let (Some(step), Some(final_)) = (step, final_) else {
+        return missing_callback("step or final", user_data, destroy, out_err);
+    };
+    create_aggregate(
+        conn,
+        name,
+        n_args,
+        deterministic,
+        Function {
+            callbacks: Aggregate {
+                step,
+                final_,
+                value: None,
+                inverse: None,
+            },
+            user_data: user_data as usize,
+            destroy,
+        },
+        out_err,
+    )
+}
+
+/// Registers an aggregate function which can also be used as a window function, for which
+/// `value` sets the current result of the window from its state without releasing it, and
+/// `inverse` removes a row that left the window from the state. Otherwise like
+/// `libsql_create_aggregate_function`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_create_window_function(
+    conn: *const libsql::Connection,
+    name: *const c_char,
+    n_args: c_int,
+    deterministic: bool,
+    step: Option<libsql_aggregate_step_callback>,
+    final_: Option<libsql_aggregate_result_callback>,
+    value: Option<libsql_aggregate_result_callback>,
+    inverse: Option<libsql_aggregate_step_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let (Some(step), Some(final_), Some(value), Some(inverse)) = (step, final_, value, inverse)
+    else {
+        return missing_callback("step, final, value or inverse", user_data, destroy, out_err);
+    };
+    create_aggregate(
+        conn,
+        name,
+        n_args,
+        deterministic,
+        Function {
+            callbacks: Aggregate {
+                step,
+                final_,
+                value: Some(value),
+                inverse: Some(inverse),
+            },
+            user_data: user_data as usize,
+            destroy,
+        },
+        out_err,
+    )
+}
+
+unsafe fn create_aggregate(
+    conn: *const libsql::Connection,
+    name: *const c_char,
+    n_args: c_int,
+    deterministic: bool,
+    function: Function<Aggregate>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let window = function.callbacks.value.is_some();
+    create_function(
+        conn,
+        name,
+        n_args,
+        function,
+        out_err,
+        |raw_conn, name, app| {
+            ffi::sqlite3_create_window_function(
+                raw_conn,
+                name.as_ptr(),
+                n_args,
+                function_flags(deterministic),
+                app,
+                Some(call_aggregate_step),
+                Some(call_aggregate_final),
+                window.then_some(call_aggregate_value as _),
+                window.then_some(call_aggregate_inverse as _),
+                Some(destroy_function::<Aggregate>),
+            )
+        },
+    )
+}
+
+/// Sets the result of a function call to NULL, which is the result if none is set.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_result_null(ctx: *mut libsql_function_context) {
+    ffi::sqlite3_result_null(ctx);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_result_int(ctx: *mut libsql_function_context, value: c_longlong) {
+    ffi::sqlite3_result_int64(ctx, value);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_result_float(ctx: *mut libsql_function_context, value: c_double) {
+    ffi::sqlite3_result_double(ctx, value);
+}
+
+/// Sets the result of a function call to the UTF-8 text of `len` bytes at `ptr`, or up to its
+/// NUL terminator if `len` is negative. The text is copied.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_result_text(
+    ctx: *mut libsql_function_context,
+    ptr: *const c_char,
+    len: c_int,
+) {
+    ffi::sqlite3_result_text(ctx, ptr, len, ffi::SQLITE_TRANSIENT());
+}
+
+/// Sets the result of a function call to the `len` bytes at `ptr`, which are copied.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_result_blob(
+    ctx: *mut libsql_function_context,
+    ptr: *const c_void,
+    len: c_int,
+) {
+    ffi::sqlite3_result_blob(ctx, ptr, len, ffi::SQLITE_TRANSIENT());
+}
+
+/// Fails the function call, and the statement running it, with the NUL terminated `message`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_result_error(
+    ctx: *mut libsql_function_context,
+    message: *const c_char,
+) {
+    ffi::sqlite3_result_error(ctx, message, -1);
+}
@@ -0,0 +1,232 @@
+// Panics must never unwind into the host: crossing an `extern "C"` boundary aborts the whole
+// process. Every exported function runs its body through one of the helpers below, so a panic
+// is turned into a `LIBSQL_ERR_PANIC` error and the handle it happened on gets poisoned. The
+// helpers also resolve the handle (see `handles`) before handing it to the body.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    ffi::c_int,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use crate::error::{libsql_error, set_err};
+use crate::handles::{release, resolve};
+use crate::runtime::ensure_running;
+use crate::types::{LIBSQL_ERR_MISUSE, LIBSQL_ERR_PANIC, LIBSQL_ERR_POISONED};
+
+/// Why a handle can't be used anymore, it can still be freed.
+#[derive(Clone, Copy)]
+pub enum Poison {
+    Panic,
+    /// Its database was closed with `LIBSQL_CLOSE_FORCE`.
+    Closed,
+}
+
+lazy_static! {
+    static ref POISONED: Mutex<HashMap<usize, Poison>> = Mutex::new(HashMap::new());
+}
+
+// Size of `POISONED`, every call checks its handle so the map is only locked when it has some.
+static POISONED_LEN: AtomicUsize = AtomicUsize::new(0);
+
+fn poisoned() -> MutexGuard<'static, HashMap<usize, Poison>> {
+    // The map is only ever touched by the helpers below, a poisoned lock holds valid data.
+    POISONED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn poison<T>(handle: *const T) {
+    poison_with(handle, Poison::Panic);
+}
+
+pub fn poison_with<T>(handle: *const T, reason: Poison) {
+    if !handle.is_null() {
+        let mut poisoned = poisoned();
+        poisoned.insert(handle as usize, reason);
+        POISONED_LEN.store(poisoned.len(), Ordering::Release);
+    }
+}
+
+fn is_poisoned<T>(handle: *const T) -> Option<Poison> {
+    if handle.is_null() || POISONED_LEN.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+    poisoned().get(&(handle as usize)).copied()
+}
+
+// Must be called when the handle is freed so its address can be reused by a new allocation.
+fn clear_poison<T>(handle: *const T) {
+    if !handle.is_null() && POISONED_LEN.load(Ordering::Acquire) > 0 {
+        let mut poisoned = poisoned();
+        poisoned.remove(&(handle as usize));
+        POISONED_LEN.store(poisoned.len(), Ordering::Release);
+    }
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+pub fn panic_error(payload: Box<dyn Any + Send>) -> libsql_error {
+    let message = panic_message(&*payload);
+    tracing::error!("Caught an internal panic: {message}");
+    libsql_error::new(LIBSQL_ERR_PANIC, format!("Internal panic: {message}"))
+}
+
+fn poisoned_error(reason: Poison) -> libsql_error {
+    match reason {
+        Poison::Panic => libsql_error::new(
+            LIBSQL_ERR_POISONED,
+            "Handle was poisoned by a previous internal panic",
+        ),
+        Poison::Closed => libsql_error::new(
+            LIBSQL_ERR_MISUSE,
+            "The database of this handle was closed, it can only be freed",
+        ),
+    }
+}
+
+/// Runs `f` reporting panics through `out_err`, for functions that don't work on a handle.
+pub unsafe fn catch_panic(out_err: *mut *const libsql_error, f: impl FnOnce() -> c_int) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(code) => code,
+        Err(payload) => set_err(panic_error(payload), out_err),
+    }
+}
+
+/// `catch_panic` for functions that use the runtime without working on a handle, failing with
+/// `LIBSQL_ERR_MISUSE` once it was shut down.
+pub unsafe fn catch_panic_running(
+    out_err: *mut *const libsql_error,
+    f: impl FnOnce() -> c_int,
+) -> c_int {
+    if let Err(e) = ensure_running() {
+        return set_err(e, out_err);
+    }
+    catch_panic(out_err, f)
+}
+
+/// `catch_panic` for functions without an error output, `default` is returned instead.
+pub fn catch_panic_or<R>(default: R, f: impl FnOnce() -> R) -> R {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => value,
+        Err(payload) => {
+            tracing::error!("Internal panic: {}", panic_message(&*payload));
+            default
+        }
+    }
+}
+
+/// Resolves `handle` and runs `f` over the value pointer, refusing to do so if the handle is
+/// invalid, a previous call panicked on it or the runtime was shut down.
+pub unsafe fn guard<T: 'static>(
+    handle: *const T,
+    out_err: *mut *const libsql_error,
+    f: impl FnOnce(*mut T) -> c_int,
+) -> c_int {
+    if let Some(reason) = is_poisoned(handle) {
+        return set_err(poisoned_error(reason), out_err);
+    }
+    if let Err(e) = ensure_running() {
+        return set_err(e, out_err);
+    }
+    let ptr = match resolve(handle) {
+        Ok(ptr) => ptr,
+        Err(e) => return set_err(e, out_err),
+    };
+    match catch_unwind(AssertUnwindSafe(|| f(ptr))) {
+        Ok(code) => code,
+        Err(payload) => {
+            poison(handle);
+            set_err(panic_error(payload), out_err)
+        }
+    }
+}
+
+/// Same as `guard` for functions without an error output, `default` is returned instead.
+pub fn guard_or<T: 'static, R>(handle: *const T, default: R, f: impl FnOnce(*mut T) -> R) -> R {
+    if let Some(reason) = is_poisoned(handle) {
+        tracing::error!("{}", poisoned_error(reason));
+        return default;
+    }
+    let ptr = match resolve(handle) {
+        Ok(ptr) => ptr,
+        Err(e) => {
+            tracing::error!("{e}");
+            return default;
+        }
+    };
+    match catch_unwind(AssertUnwindSafe(|| f(ptr))) {
+        Ok(value) => value,
+        Err(payload) => {
+            poison(handle);
+            tracing::error!("Internal panic: {}", panic_message(&*payload));
+            default
+        }
+    }
+}
+
+/// Invalidates `handle` and runs `f` to drop the value, which the host hands back to us even if
+/// the operation fails (e.g. committing a transaction).
+pub unsafe fn guard_consume<T: 'static>(
+    handle: *const T,
+    out_err: *mut *const libsql_error,
+    f: impl FnOnce(*mut T) -> c_int,
+) -> c_int {
+    let teardown = |ptr| {
+        crate::children::release(ptr);
+        drop(Box::from_raw(ptr));
+    };
+    guard_consume_with(handle, out_err, teardown, f)
+}
+
+/// `guard_consume` dropping the value through `teardown` when `f` can't run on it, for values
+/// with state kept elsewhere by their address.
+pub unsafe fn guard_consume_with<T: 'static>(
+    handle: *const T,
+    out_err: *mut *const libsql_error,
+    teardown: impl FnOnce(*mut T),
+    f: impl FnOnce(*mut T) -> c_int,
+) -> c_int {
+    let poisoned = is_poisoned(handle);
+    clear_poison(handle);
+    let ptr = match release(handle) {
+        Ok(ptr) => ptr,
+        Err(e) => return set_err(e, out_err),
+    };
+    let unusable = match poisoned {
+        Some(reason) => Err(poisoned_error(reason)),
+        None => ensure_running(),
+    };
+    if let Err(e) = unusable {
+        // Still drop the value, it won't be reachable anymore.
+        let _ = catch_unwind(AssertUnwindSafe(|| teardown(ptr)));
+        return set_err(e, out_err);
+    }
+    match catch_unwind(AssertUnwindSafe(|| f(ptr))) {
+        Ok(code) => code,
+        Err(payload) => set_err(panic_error(payload), out_err),
+    }
+}
+
+/// Invalidates `handle` and drops its value through `f`, null or already freed handles are
+/// ignored and panics while dropping are logged and swallowed.
+pub fn guard_free<T: 'static>(handle: *const T, f: impl FnOnce(*mut T)) {
+    clear_poison(handle);
+    let Ok(ptr) = release(handle) else {
+        return;
+    };
+    if let Err(payload) = catch_unwind(AssertUnwindSafe(|| f(ptr))) {
+        tracing::error!("Internal panic while freeing: {}", panic_message(&*payload));
+    }
+}
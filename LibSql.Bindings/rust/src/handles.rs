@@ -0,0 +1,179 @@
+// Handles given to the host. By default a handle is the raw pointer of a boxed value, with the
+// `handle-registry` feature it becomes an opaque generational id that is validated on every
+// call, so a stale or double freed handle is reported as `LIBSQL_ERR_INVALID_HANDLE` instead of
+// being undefined behaviour.
+
+use crate::error::libsql_error;
+use crate::types::LIBSQL_ERR_INVALID_HANDLE;
+
+fn invalid_handle(message: &str) -> libsql_error {
+    libsql_error::new(LIBSQL_ERR_INVALID_HANDLE, message)
+}
+
+/// Boxes `value` and returns the handle the host will use to refer to it.
+pub fn into_handle<T: 'static>(value: T) -> *const T {
+    register(Box::into_raw(Box::new(value)), None)
+}
+
+/// Hands out a handle to a value owned by `parent`, it can't be freed by the host and it is
+/// invalidated together with its parent.
+pub fn borrowed_handle<T: 'static, P: 'static>(ptr: *const T, parent: *const P) -> *const T {
+    register(ptr as *mut T, Some(parent as usize))
+}
+
+/// Returns the value pointer behind `handle`.
+pub fn resolve<T: 'static>(handle: *const T) -> Result<*mut T, libsql_error> {
+    if handle.is_null() {
+        return Err(invalid_handle("Null handle"));
+    }
+    lookup(handle)
+}
+
+/// Invalidates `handle` returning the pointer of the value, which the caller must drop.
+pub fn release<T: 'static>(handle: *const T) -> Result<*mut T, libsql_error> {
+    if handle.is_null() {
+        return Err(invalid_handle("Null handle"));
+    }
+    remove(handle)
+}
+
+#[cfg(not(feature = "handle-registry"))]
+fn register<T: 'static>(ptr: *mut T, _parent: Option<usize>) -> *const T {
+    ptr
+}
+
+#[cfg(not(feature = "handle-registry"))]
+fn lookup<T: 'static>(handle: *const T) -> Result<*mut T, libsql_error> {
+    Ok(handle as *mut T)
+}
+
+#[cfg(not(feature = "handle-registry"))]
+fn remove<T: 'static>(handle: *const T) -> Result<*mut T, libsql_error> {
+    Ok(handle as *mut T)
+}
+
+#[cfg(feature = "handle-registry")]
+use registry::{lookup, register, remove};
+
+#[cfg(feature = "handle-registry")]
+mod registry {
+    use std::{any::TypeId, collections::HashMap, sync::RwLock};
+
+    use super::invalid_handle;
+    use crate::error::libsql_error;
+
+    #[cfg(not(target_pointer_width = "64"))]
+    compile_error!("The handle registry packs its ids in 64 bits pointers");
+
+    struct Slot {
+        generation: u32,
+        // `None` when the slot is free.
+        value: Option<Entry>,
+    }
+
+    struct Entry {
+        ptr: usize,
+        type_id: TypeId,
+        parent: Option<usize>,
+    }
+
+    #[derive(Default)]
+    struct Registry {
+        slots: Vec<Slot>,
+        free: Vec<usize>,
+        borrowed: HashMap<usize, Vec<usize>>,
+    }
+
+    lazy_static! {
+        static ref REGISTRY: RwLock<Registry> = RwLock::new(Registry::default());
+    }
+
+    // Ids are `(index + 1) << 32 | generation`, so they are never null.
+    fn encode(index: usize, generation: u32) -> usize {
+        ((index + 1) << 32) | generation as usize
+    }
+
+    fn decode(id: usize) -> (usize, u32) {
+        ((id >> 32).wrapping_sub(1), id as u32)
+    }
+
+    impl Registry {
+        fn get(&self, id: usize) -> Result<&Entry, libsql_error> {
+            let (index, generation) = decode(id);
+            match self.slots.get(index) {
+                Some(Slot {
+                    generation: current,
+                    value: Some(entry),
+                }) if *current == generation => Ok(entry),
+                Some(_) => Err(invalid_handle("Handle was already freed")),
+                None => Err(invalid_handle("Unknown handle")),
+            }
+        }
+
+        fn take(&mut self, id: usize) -> Option<Entry> {
+            let (index, _) = decode(id);
+            let slot = &mut self.slots[index];
+            let entry = slot.value.take();
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(index);
+            if let Some(children) = self.borrowed.remove(&id) {
+                for child in children {
+                    if self.get(child).is_ok() {
+                        self.take(child);
+                    }
+                }
+            }
+            entry
+        }
+    }
+
+    pub fn register<T: 'static>(ptr: *mut T, parent: Option<usize>) -> *const T {
+        let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+        let entry = Entry {
+            ptr: ptr as usize,
+            type_id: TypeId::of::<T>(),
+            parent,
+        };
+        let index = match registry.free.pop() {
+            Some(index) => {
+                registry.slots[index].value = Some(entry);
+                index
+            }
+            None => {
+                registry.slots.push(Slot {
+                    generation: 0,
+                    value: Some(entry),
+                });
+                registry.slots.len() - 1
+            }
+        };
+        let id = encode(index, registry.slots[index].generation);
+        if let Some(parent) = parent {
+            registry.borrowed.entry(parent).or_default().push(id);
+        }
+        id as *const T
+    }
+
+    pub fn lookup<T: 'static>(handle: *const T) -> Result<*mut T, libsql_error> {
+        let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+        let entry = registry.get(handle as usize)?;
+        if entry.type_id != TypeId::of::<T>() {
+            return Err(invalid_handle("Handle has a different type"));
+        }
+        Ok(entry.ptr as *mut T)
+    }
+
+    pub fn remove<T: 'static>(handle: *const T) -> Result<*mut T, libsql_error> {
+        let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+        let id = handle as usize;
+        let entry = registry.get(id)?;
+        if entry.type_id != TypeId::of::<T>() {
+            return Err(invalid_handle("Handle has a different type"));
+        }
+        if entry.parent.is_some() {
+            return Err(invalid_handle("Borrowed handles can't be freed"));
+        }
+        let entry = registry.take(id).expect("entry checked above");
+        Ok(entry.ptr as *mut T)
+    }
+}
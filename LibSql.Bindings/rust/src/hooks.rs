@@ -0,0 +1,296 @@
+// Callbacks set on a connection rather than registered under a name, of which SQLite keeps one of
+// each kind without ever releasing them. Their user data is kept here until replaced, and they
+// are unregistered from SQLite when the connection is detached from its handle.
+
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_int, c_longlong, c_void},
+    sync::{Mutex, MutexGuard},
+};
+
+use libsql::ffi;
+
+use crate::error::{libsql_error, set_err};
+use crate::functions::Function;
+use crate::guard::{catch_panic_or, guard};
+use crate::raw::raw_connection;
+use crate::types::{
+    libsql_authorizer_callback, libsql_collation_needed_callback, libsql_commit_hook_callback,
+    libsql_destroy_callback, libsql_rollback_hook_callback, libsql_update_hook_callback,
+    LIBSQL_AUTH_ALLOW, LIBSQL_AUTH_DENY, LIBSQL_AUTH_IGNORE, LIBSQL_UPDATE_DELETE,
+    LIBSQL_UPDATE_INSERT, LIBSQL_UPDATE_UPDATE,
+};
+
+// The update hook passes SQLite's operations through.
+const _: () = assert!(
+    LIBSQL_UPDATE_DELETE == ffi::SQLITE_DELETE
+        && LIBSQL_UPDATE_INSERT == ffi::SQLITE_INSERT
+        && LIBSQL_UPDATE_UPDATE == ffi::SQLITE_UPDATE
+);
+
+// And the authorizer returns SQLite's decisions.
+const _: () = assert!(
+    LIBSQL_AUTH_ALLOW == ffi::SQLITE_OK
+        && LIBSQL_AUTH_DENY == ffi::SQLITE_DENY
+        && LIBSQL_AUTH_IGNORE == ffi::SQLITE_IGNORE
+);
+
+pub type Hook<C> = Option<Box<Function<C>>>;
+
+#[derive(Default)]
+pub struct Hooks {
+    pub collation_needed: Hook<libsql_collation_needed_callback>,
+    update: Hook<libsql_update_hook_callback>,
+    commit: Hook<libsql_commit_hook_callback>,
+    rollback: Hook<libsql_rollback_hook_callback>,
+    authorizer: Hook<libsql_authorizer_callback>,
+}
+
+impl Hooks {
+    unsafe fn unregister(&self, raw_conn: *mut ffi::sqlite3) {
+        if self.collation_needed.is_some() {
+            ffi::sqlite3_collation_needed(raw_conn, std::ptr::null_mut(), None);
+        }
+        if self.update.is_some() {
+            ffi::sqlite3_update_hook(raw_conn, None, std::ptr::null_mut());
+        }
+        if self.commit.is_some() {
+            ffi::sqlite3_commit_hook(raw_conn, None, std::ptr::null_mut());
+        }
+        if self.rollback.is_some() {
+            ffi::sqlite3_rollback_hook(raw_conn, None, std::ptr::null_mut());
+        }
+        if self.authorizer.is_some() {
+            ffi::sqlite3_set_authorizer(raw_conn, None, std::ptr::null_mut());
+        }
+    }
+}
+
+lazy_static! {
+    // Keyed by the address of the connection, not by its handle.
+    static ref HOOKS: Mutex<HashMap<usize, Hooks>> = Mutex::default();
+}
+
+fn hooks() -> MutexGuard<'static, HashMap<usize, Hooks>> {
+    HOOKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replaces the hook of `conn` in `slot` with `hook`, or removes it if `None`. `register` sets it
+/// on the SQLite connection given its app data, null when removing it.
+pub unsafe fn set<C>(
+    conn: *const libsql::Connection,
+    hook: Option<Function<C>>,
+    slot: fn(&mut Hooks) -> &mut Hook<C>,
+    register: impl FnOnce(*mut ffi::sqlite3, *mut c_void),
+) -> Result<(), libsql_error> {
+    // Dropped if the connection has no SQLite connection, destroying the user data.
+    let hook = hook.map(Box::new);
+    let (conn, raw_conn) = raw_connection(conn)?;
+    let app = hook
+        .as_deref()
+        .map_or(std::ptr::null_mut(), |hook| hook as *const _ as *mut c_void);
+    // Not under the lock, the hooks of another connection may be set from a running callback.
+    register(raw_conn, app);
+    let replaced = std::mem::replace(slot(hooks().entry(conn as usize).or_default()), hook);
+    // SQLite calls the new hook from now on.
+    drop(replaced);
+    Ok(())
+}
+
+/// Unregisters the hooks of `conn` from `raw_conn` and releases them.
+pub unsafe fn detach(conn: *const libsql::Connection, raw_conn: *mut ffi::sqlite3) {
+    let Some(hooks) = hooks().remove(&(conn as usize)) else {
+        return;
+    };
+    hooks.unregister(raw_conn);
+}
+
+fn hook<C>(
+    callback: Option<C>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+) -> Option<Function<C>> {
+    callback.map(|callback| Function {
+        callbacks: callback,
+        user_data: user_data as usize,
+        destroy,
+    })
+}
+
+/// Hook whose app data SQLite is calling it with.
+unsafe fn registered<'a, C>(app: *mut c_void) -> &'a Function<C> {
+    &*(app as *const Function<C>)
+}
+
+unsafe extern "C" fn call_update(
+    app: *mut c_void,
+    op: c_int,
+    db_name: *const c_char,
+    table_name: *const c_char,
+    rowid: ffi::sqlite3_int64,
+) {
+    catch_panic_or((), || {
+        let hook = registered::<libsql_update_hook_callback>(app);
+        (hook.callbacks)(
+            hook.user_data(),
+            op,
+            db_name,
+            table_name,
+            rowid as c_longlong,
+        );
+    })
+}
+
+unsafe extern "C" fn call_commit(app: *mut c_void) -> c_int {
+    // Committing is the safer outcome of a panic, the host didn't ask for a rollback.
+    catch_panic_or(0, || {
+        let hook = registered::<libsql_commit_hook_callback>(app);
+        (hook.callbacks)(hook.user_data())
+    })
+}
+
+unsafe extern "C" fn call_rollback(app: *mut c_void) {
+    catch_panic_or((), || {
+        let hook = registered::<libsql_rollback_hook_callback>(app);
+        (hook.callbacks)(hook.user_data());
+    })
+}
+
+unsafe extern "C" fn call_authorizer(
+    app: *mut c_void,
+    action: c_int,
+    arg1: *const c_char,
+    arg2: *const c_char,
+    db_name: *const c_char,
+    trigger_or_view: *const c_char,
+) -> c_int {
+    // Denying is the safer outcome of a panic, the authorizer may be sandboxing the statement.
+    catch_panic_or(LIBSQL_AUTH_DENY, || {
+        let hook = registered::<libsql_authorizer_callback>(app);
+        (hook.callbacks)(
+            hook.user_data(),
+            action,
+            arg1,
+            arg2,
+            db_name,
+            trigger_or_view,
+        )
+    })
+}
+
+/// Sets the hook called after each row of a rowid table the connection inserts, updates or
+/// deletes, with the operation, the database and table names and the rowid. Rows deleted by a
+/// `DELETE` without `WHERE` clause aren't reported, SQLite drops them all at once. A NULL
+/// `callback` removes it. `destroy` is called with `user_data` once the hook is replaced or
+/// removed, or the connection is disconnected. Only available on connections to local databases
+/// and replicas, other connections fail with `LIBSQL_ERR_NOT_SUPPORTED`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_update_hook(
+    conn: *const libsql::Connection,
+    callback: Option<libsql_update_hook_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let hook = hook(callback, user_data, destroy);
+    guard(conn, out_err, |conn| {
+        let set = set(
+            conn,
+            hook,
+            |hooks| &mut hooks.update,
+            |raw_conn, app| {
+                let callback = (!app.is_null()).then_some(call_update as _);
+                ffi::sqlite3_update_hook(raw_conn, callback, app);
+            },
+        );
+        match set {
+            Ok(()) => 0,
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
+
+/// Sets the hook called before each transaction of the connection commits, which rolls it back
+/// instead if the hook returns non-zero. Otherwise like `libsql_set_update_hook`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_commit_hook(
+    conn: *const libsql::Connection,
+    callback: Option<libsql_commit_hook_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let hook = hook(callback, user_data, destroy);
+    guard(conn, out_err, |conn| {
+        let set = set(
+            conn,
+            hook,
+            |hooks| &mut hooks.commit,
+            |raw_conn, app| {
+                let callback = (!app.is_null()).then_some(call_commit as _);
+                ffi::sqlite3_commit_hook(raw_conn, callback, app);
+            },
+        );
+        match set {
+            Ok(()) => 0,
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
+
+/// Sets the hook called after each transaction of the connection rolls back. Otherwise like
+/// `libsql_set_update_hook`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_rollback_hook(
+    conn: *const libsql::Connection,
+    callback: Option<libsql_rollback_hook_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let hook = hook(callback, user_data, destroy);
+    guard(conn, out_err, |conn| {
+        let set = set(
+            conn,
+            hook,
+            |hooks| &mut hooks.rollback,
+            |raw_conn, app| {
+                let callback = (!app.is_null()).then_some(call_rollback as _);
+                ffi::sqlite3_rollback_hook(raw_conn, callback, app);
+            },
+        );
+        match set {
+            Ok(()) => 0,
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
+
+/// Sets the authorizer, which allows, denies or ignores each action of the statements the
+/// connection prepares from then on. Statements already prepared aren't authorized again unless
+/// SQLite prepares them anew. Otherwise like `libsql_set_update_hook`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_authorizer(
+    conn: *const libsql::Connection,
+    callback: Option<libsql_authorizer_callback>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    let hook = hook(callback, user_data, destroy);
+    guard(conn, out_err, |conn| {
+        let set = set(
+            conn,
+            hook,
+            |hooks| &mut hooks.authorizer,
+            |raw_conn, app| {
+                let callback = (!app.is_null()).then_some(call_authorizer as _);
+                ffi::sqlite3_set_authorizer(raw_conn, callback, app);
+            },
+        );
+        match set {
+            Ok(()) => 0,
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
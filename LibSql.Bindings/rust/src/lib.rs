@@ -1,4 +1,4 @@
-// This rust lib was extracted from the https://github.com/tursodatabase/libsql and their 
+// This rust lib was extracted from the https://github.com/tursodatabase/libsql and their
 // c-bindings, and modified in many aspects to be use in LibSql.Bindings
 
 #![allow(clippy::missing_safety_doc)]
@@ -6,20 +6,58 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod builder;
+mod calls;
+mod cancel;
+mod children;
+mod collations;
+mod completion;
+mod connector;
+mod error;
+mod functions;
+mod guard;
+mod handles;
+mod hooks;
+mod log;
+mod pool;
+mod raw;
+mod runtime;
+mod snapshot;
+mod sync;
+mod timeout;
+mod tls;
 mod types;
 
-use std::{ops::Deref, ptr::null};
+use std::{
+    ops::Deref,
+    ptr::{null, null_mut},
+};
 
-use crate::types::LibSqlConfig;
-use libsql::{errors, LoadExtensionGuard};
-use tokio::runtime::Runtime;
+use crate::calls::{named, positional, read_call};
+use crate::cancel::{cancellable, get_cancel_token, libsql_cancel_token};
+use crate::children::Kind;
+use crate::completion::{guard_async, SendPtr};
+use crate::connector::{connector, HttpOptions};
+use crate::error::{libsql_error, set_err};
+use crate::guard::{
+    catch_panic, catch_panic_or, catch_panic_running, guard, guard_consume, guard_free, guard_or,
+};
+use crate::handles::{borrowed_handle, into_handle, resolve};
+use crate::timeout::{clear_default_timeout, set_default_timeout, with_timeout};
+use crate::tls::TlsOptions;
+use crate::types::{
+    read_config, LibSqlConfig, LibSqlLocalConfig, LIBSQL_OPEN_CREATE, LIBSQL_OPEN_NO_MUTEX,
+    LIBSQL_OPEN_READ_ONLY, LIBSQL_OPEN_READ_WRITE,
+};
+use libsql::{errors, params::Params, LoadExtensionGuard};
 use types::{
-    blob, replicated, LIBSQL_TRANSACTION_DEFERRED, LIBSQL_TRANSACTION_EXCLUSIVE,
-    LIBSQL_TRANSACTION_IMMEDIATE, LIBSQL_TRANSACTION_READONLY,
+    blob, libsql_callback, libsql_cipher, replicated, LIBSQL_TRANSACTION_DEFERRED,
+    LIBSQL_TRANSACTION_EXCLUSIVE, LIBSQL_TRANSACTION_IMMEDIATE, LIBSQL_TRANSACTION_READONLY,
 };
 
 lazy_static! {
-    static ref RT: Runtime = tokio::runtime::Runtime::new().unwrap();
+    // Entry points check `runtime::running` first, this only ever picks up a running runtime.
+    static ref RT: tokio::runtime::Handle = runtime::running().unwrap_or_else(|e| panic!("{e}"));
 }
 
 fn translate_string(s: String) -> *const std::ffi::c_char {
@@ -29,19 +67,15 @@ fn translate_string(s: String) -> *const std::ffi::c_char {
     }
 }
 
-unsafe fn set_err_msg(msg: String, output: *mut *const std::ffi::c_char) {
-    if !output.is_null() {
-        *output = translate_string(msg);
-    }
-}
-
 #[no_mangle]
 pub unsafe extern "C" fn libsql_enable_internal_tracing() -> std::ffi::c_int {
-    if tracing_subscriber::fmt::try_init().is_ok() {
-        1
-    } else {
-        0
-    }
+    catch_panic_or(0, || {
+        if tracing_subscriber::fmt::try_init().is_ok() {
+            1
+        } else {
+            0
+        }
+    })
 }
 
 pub unsafe fn get_ref<'a, T>(ptr: *const T) -> &'a T {
@@ -52,141 +86,380 @@ pub unsafe fn get_mut_ref<'a, T>(ptr: *mut T) -> &'a mut T {
     return &mut (*ptr);
 }
 
+/// Resolves a handle that isn't the main one of the call (see `guard`).
+unsafe fn get_handle<'a, T: 'static>(handle: *const T) -> Result<&'a T, libsql_error> {
+    resolve(handle).map(|ptr| get_ref(ptr))
+}
+
 ////////////////////////////////////
 ///////////// DATABASE /////////////
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_sync(
     db: *const libsql::Database,
+    cancel_token: *const libsql_cancel_token,
     out_replicated: *mut replicated,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!db.is_null());
+    libsql_sync_timeout(db, cancel_token, 0, out_replicated, out_err)
+}
 
-    let db = get_ref(db);
-    match RT.block_on(db.sync()) {
-        Ok(replicated) => {
-            if !out_replicated.is_null() {
-                (*out_replicated).frame_no = replicated.frame_no().unwrap_or(0) as i32;
-                (*out_replicated).frames_synced = replicated.frames_synced() as i32;
+/// A `timeout_ms` of 0 means no timeout.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_sync_timeout(
+    db: *const libsql::Database,
+    cancel_token: *const libsql_cancel_token,
+    timeout_ms: std::ffi::c_ulonglong,
+    out_replicated: *mut replicated,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(db, out_err, |db| {
+        let db = get_ref(db);
+        let timeout = match timeout_ms {
+            0 => None,
+            ms => Some(std::time::Duration::from_millis(ms)),
+        };
+        let token = match get_cancel_token(cancel_token) {
+            Ok(token) => token,
+            Err(e) => return set_err(e, out_err),
+        };
+        let fut = async { sync::sync(db, None).await };
+        match RT.block_on(cancellable(token, None, with_timeout(timeout, None, fut))) {
+            Ok(replicated) => {
+                if !out_replicated.is_null() {
+                    *out_replicated = replicated.into();
+                }
+                0
             }
-            0
+            Err(e) => set_err(e, out_err),
         }
-        Err(e) => {
-            set_err_msg(format!("Error syncing database: {e}"), out_err_msg);
-            1
-        }
-    }
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn libsql_open_sync_with_config(
-    config: LibSqlConfig,
-    out_db: *mut *const libsql::Database,
-    out_err_msg: *mut *const std::ffi::c_char,
+pub unsafe extern "C" fn libsql_sync_async(
+    db: *const libsql::Database,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<replicated>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    let db_path = unsafe { std::ffi::CStr::from_ptr(config.db_path) };
-    let db_path = match db_path.to_str() {
-        Ok(url) => url,
-        Err(e) => {
-            set_err_msg(format!("Wrong URL: {e}"), out_err_msg);
-            return 1;
-        }
-    };
-    let primary_url = unsafe { std::ffi::CStr::from_ptr(config.primary_url) };
-    let primary_url = match primary_url.to_str() {
-        Ok(url) => url,
-        Err(e) => {
-            set_err_msg(format!("Wrong URL: {e}"), out_err_msg);
-            return 2;
+    guard_async(db, callback, user_data, out_err, |db, completion| {
+        let db = SendPtr::new(db);
+        let token = match get_cancel_token(cancel_token) {
+            Ok(token) => token,
+            Err(e) => return set_err(e, out_err),
+        };
+        completion.spawn(
+            async move {
+                let db = get_ref(db.get());
+                cancellable(token, None, sync::sync(db, None)).await
+            },
+            replicated::from,
+        );
+        0
+    })
+}
+
+/// Current replication index of an embedded replica, `out_has_frame_no` is set to 0 when it
+/// didn't receive any frame yet.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_replication_index(
+    db: *const libsql::Database,
+    out_frame_no: *mut std::ffi::c_ulonglong,
+    out_has_frame_no: *mut std::ffi::c_char,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(db, out_err, |db| {
+        let db = get_ref(db);
+        match RT.block_on(db.replication_index()) {
+            Ok(frame_no) => {
+                *out_frame_no = frame_no.unwrap_or(0);
+                *out_has_frame_no = frame_no.is_some() as std::ffi::c_char;
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error reading the replication index", e),
+                out_err,
+            ),
         }
+    })
+}
+
+/// Encryption of a database from a raw key of `key_len` bytes, none when `key` is null.
+unsafe fn encryption_config(
+    key: *const std::ffi::c_char,
+    key_len: std::ffi::c_int,
+    cipher: std::ffi::c_int,
+) -> Result<Option<libsql::EncryptionConfig>, libsql_error> {
+    if key.is_null() {
+        return Ok(None);
+    }
+    let Some(cipher) = libsql_cipher::from_raw(cipher) else {
+        return Err(libsql_error::invalid_argument(format!(
+            "Unknown cipher {cipher}"
+        )));
     };
-    let auth_token = unsafe { std::ffi::CStr::from_ptr(config.auth_token) };
-    let auth_token = match auth_token.to_str() {
-        Ok(token) => token,
-        Err(e) => {
-            set_err_msg(format!("Wrong Auth Token: {e}"), out_err_msg);
-            return 3;
-        }
+    if key_len < 0 || key_len as usize != cipher.key_len() {
+        return Err(libsql_error::invalid_argument(format!(
+            "Encryption key of {cipher:?} must be {} bytes, got {}",
+            cipher.key_len(),
+            key_len
+        )));
+    }
+    let key = std::slice::from_raw_parts(key as *const u8, key_len as usize);
+    let cipher = match cipher {
+        libsql_cipher::Aes256Cbc => libsql::Cipher::Aes256Cbc,
     };
-    let mut builder = libsql::Builder::new_remote_replica(
-        db_path,
-        primary_url.to_string(),
-        auth_token.to_string(),
-    );
-    if config.with_webpki != 0 {
-        let https = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_webpki_roots()
-            .https_or_http()
-            .enable_http1()
-            .build();
-        builder = builder.connector(https);
+    Ok(Some(libsql::EncryptionConfig::new(
+        cipher,
+        bytes::Bytes::copy_from_slice(key),
+    )))
+}
+
+/// Validates a set of `LIBSQL_OPEN_*`, 0 opens for reading and writing creating the file.
+fn open_flags(flags: std::ffi::c_int) -> Result<libsql::OpenFlags, libsql_error> {
+    let known =
+        LIBSQL_OPEN_READ_ONLY | LIBSQL_OPEN_READ_WRITE | LIBSQL_OPEN_CREATE | LIBSQL_OPEN_NO_MUTEX;
+    if flags & !known != 0 {
+        return Err(libsql_error::invalid_argument(format!(
+            "Unknown open flags {flags:#x}"
+        )));
+    }
+    let read_only = flags & LIBSQL_OPEN_READ_ONLY != 0;
+    if read_only && flags & (LIBSQL_OPEN_READ_WRITE | LIBSQL_OPEN_CREATE) != 0 {
+        return Err(libsql_error::invalid_argument(
+            "A read only database can't be opened for writing nor created",
+        ));
+    }
+    let mut flags = flags;
+    if flags & (LIBSQL_OPEN_READ_ONLY | LIBSQL_OPEN_READ_WRITE) == 0 {
+        flags |= LIBSQL_OPEN_READ_WRITE | LIBSQL_OPEN_CREATE;
     }
-    if config.sync_interval > 0 {
-        let interval = match config.sync_interval.try_into() {
-            Ok(d) => d,
+    Ok(libsql::OpenFlags::from_bits_retain(flags))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_open_sync_with_config(
+    config: *const LibSqlConfig,
+    out_db: *mut *const libsql::Database,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    catch_panic_running(out_err, || {
+        let config = match read_config(config) {
+            Ok(config) => config,
+            Err(e) => return set_err(e, out_err),
+        };
+        let db_path = unsafe { std::ffi::CStr::from_ptr(config.db_path) };
+        let db_path = match db_path.to_str() {
+            Ok(url) => url,
             Err(e) => {
-                set_err_msg(format!("Wrong periodic sync interval: {e}"), out_err_msg);
-                return 4;
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong URL: {e}")),
+                    out_err,
+                );
             }
         };
-        builder = builder.sync_interval(std::time::Duration::from_secs(interval));
-    }
-    builder = builder.read_your_writes(config.read_your_writes != 0);
-    if !config.encryption_key.is_null() {
-        let key = unsafe { std::ffi::CStr::from_ptr(config.encryption_key) };
-        let key = match key.to_str() {
-            Ok(k) => k,
+        let primary_url = unsafe { std::ffi::CStr::from_ptr(config.primary_url) };
+        let primary_url = match primary_url.to_str() {
+            Ok(url) => url,
             Err(e) => {
-                set_err_msg(format!("Wrong encryption key: {e}"), out_err_msg);
-                return 5;
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong URL: {e}")),
+                    out_err,
+                );
             }
         };
-        let key = bytes::Bytes::copy_from_slice(key.as_bytes());
-        let config = libsql::EncryptionConfig::new(libsql::Cipher::Aes256Cbc, key);
-        builder = builder.encryption_config(config)
-    };
-    match RT.block_on(builder.build()) {
-        Ok(db) => {
-            let db = Box::leak(Box::new(db));
-            *out_db = db as *const libsql::Database;
-            0
+        let auth_token = unsafe { std::ffi::CStr::from_ptr(config.auth_token) };
+        let auth_token = match auth_token.to_str() {
+            Ok(token) => token,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong Auth Token: {e}")),
+                    out_err,
+                );
+            }
+        };
+        if !config.snapshot_path.is_null() {
+            let snapshot = unsafe { std::ffi::CStr::from_ptr(config.snapshot_path) };
+            let seeded = match snapshot.to_str() {
+                Ok(snapshot) => snapshot::seed(snapshot, db_path),
+                Err(e) => Err(libsql_error::invalid_argument(format!(
+                    "Wrong snapshot path: {e}"
+                ))),
+            };
+            if let Err(e) = seeded {
+                return set_err(e, out_err);
+            }
         }
-        Err(e) => {
-            set_err_msg(
-                format!("Error opening db path {db_path}, primary url {primary_url}: {e}"),
-                out_err_msg,
-            );
-            6
+        let mut builder = libsql::Builder::new_remote_replica(
+            db_path,
+            primary_url.to_string(),
+            auth_token.to_string(),
+        );
+        let http = match HttpOptions::from_raw(config.proxy_url, config.headers, config.headers_len)
+        {
+            Ok(http) => http,
+            Err(e) => return set_err(e, out_err),
+        };
+        let mut tls = TlsOptions::default();
+        tls.with_webpki = config.with_webpki != 0;
+        let mut interval = None;
+        if config.sync_interval > 0 {
+            match config.sync_interval.try_into() {
+                Ok(secs) => interval = Some(std::time::Duration::from_secs(secs)),
+                Err(e) => {
+                    return set_err(
+                        libsql_error::invalid_argument(format!(
+                            "Wrong periodic sync interval: {e}"
+                        )),
+                        out_err,
+                    );
+                }
+            }
         }
-    }
+        let tracker = sync::Tracker::new(interval);
+        match connector(&tls, &http, Some(tracker.received())) {
+            Ok(Some(connector)) => builder = builder.connector(connector),
+            Ok(None) => {}
+            Err(e) => return set_err(e, out_err),
+        }
+        if let Some(callback) = http.request_callback() {
+            builder = builder.http_request_callback(callback);
+        }
+        builder = builder.read_your_writes(config.read_your_writes != 0);
+        match encryption_config(
+            config.encryption_key,
+            config.encryption_key_len,
+            config.cipher,
+        ) {
+            Ok(Some(config)) => builder = builder.encryption_config(config),
+            Ok(None) => {}
+            Err(e) => return set_err(e, out_err),
+        }
+        match RT.block_on(builder.build()) {
+            Ok(db) => {
+                *out_db = into_handle(db);
+                raw::track(*out_db, db_path);
+                sync::track(*out_db, tracker);
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql(
+                    &format!("Error opening db path {db_path}, primary url {primary_url}"),
+                    e,
+                ),
+                out_err,
+            ),
+        }
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_open_file(
     url: *const std::ffi::c_char,
     out_db: *mut *const libsql::Database,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    let url = unsafe { std::ffi::CStr::from_ptr(url) };
-    let url = match url.to_str() {
-        Ok(url) => url,
-        Err(e) => {
-            set_err_msg(format!("Wrong URL: {e}"), out_err_msg);
-            return 1;
+    catch_panic_running(out_err, || {
+        let url = unsafe { std::ffi::CStr::from_ptr(url) };
+        let url = match url.to_str() {
+            Ok(url) => url,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong URL: {e}")),
+                    out_err,
+                );
+            }
+        };
+        match RT.block_on(libsql::Builder::new_local(url).build()) {
+            Ok(db) => {
+                *out_db = into_handle(db);
+                raw::track(*out_db, url);
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql(&format!("Error opening URL {url}"), e),
+                out_err,
+            ),
         }
-    };
-    match RT.block_on(libsql::Builder::new_local(url).build()) {
-        Ok(db) => {
-            let db = Box::leak(Box::new(db));
-            *out_db = db as *const libsql::Database;
-            0
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_open_local_with_config(
+    config: *const LibSqlLocalConfig,
+    out_db: *mut *const libsql::Database,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    catch_panic_running(out_err, || {
+        let config = match read_config(config) {
+            Ok(config) => config,
+            Err(e) => return set_err(e, out_err),
+        };
+        let encryption = match encryption_config(
+            config.encryption_key,
+            config.encryption_key_len,
+            config.cipher,
+        ) {
+            Ok(encryption) => encryption,
+            Err(e) => return set_err(e, out_err),
+        };
+        if config.in_memory != 0 {
+            if encryption.is_some() || config.flags != 0 {
+                return set_err(
+                    libsql_error::invalid_argument(
+                        "In memory databases can't be encrypted nor opened with flags",
+                    ),
+                    out_err,
+                );
+            }
+            return match RT.block_on(libsql::Builder::new_local(":memory:").build()) {
+                Ok(db) => {
+                    *out_db = into_handle(db);
+                    raw::track(*out_db, ":memory:");
+                    0
+                }
+                Err(e) => set_err(
+                    libsql_error::from_libsql("Error opening in memory database", e),
+                    out_err,
+                ),
+            };
         }
-        Err(e) => {
-            set_err_msg(format!("Error opening URL {url}: {e}"), out_err_msg);
-            1
+
+        if config.db_path.is_null() {
+            return set_err(libsql_error::invalid_argument("Missing db path"), out_err);
         }
-    }
+        let db_path = unsafe { std::ffi::CStr::from_ptr(config.db_path) };
+        let db_path = match db_path.to_str() {
+            Ok(path) => path,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong db path: {e}")),
+                    out_err,
+                );
+            }
+        };
+        let flags = match open_flags(config.flags) {
+            Ok(flags) => flags,
+            Err(e) => return set_err(e, out_err),
+        };
+        let mut builder = libsql::Builder::new_local(db_path).flags(flags);
+        if let Some(encryption) = encryption {
+            builder = builder.encryption_config(encryption);
+        }
+        match RT.block_on(builder.build()) {
+            Ok(db) => {
+                *out_db = into_handle(db);
+                raw::track(*out_db, db_path);
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql(&format!("Error opening db path {db_path}"), e),
+                out_err,
+            ),
+        }
+    })
 }
 
 #[no_mangle]
@@ -194,75 +467,94 @@ pub unsafe extern "C" fn libsql_open_remote_internal(
     url: *const std::ffi::c_char,
     auth_token: *const std::ffi::c_char,
     with_webpki: bool,
+    proxy_url: *const std::ffi::c_char,
+    headers: *const *const std::ffi::c_char,
+    headers_len: std::ffi::c_int,
     out_db: *mut *const libsql::Database,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    let url = unsafe { std::ffi::CStr::from_ptr(url) };
-    let url = match url.to_str() {
-        Ok(url) => url,
-        Err(e) => {
-            set_err_msg(format!("Wrong URL: {e}"), out_err_msg);
-            return 1;
-        }
-    };
-    let auth_token = unsafe { std::ffi::CStr::from_ptr(auth_token) };
-    let auth_token = match auth_token.to_str() {
-        Ok(token) => token,
-        Err(e) => {
-            set_err_msg(format!("Wrong Auth Token: {e}"), out_err_msg);
-            return 2;
-        }
-    };
-    let mut builder = libsql::Builder::new_remote(url.to_string(), auth_token.to_string());
-    if with_webpki {
-        let https = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_webpki_roots()
-            .https_or_http()
-            .enable_http1()
-            .build();
-        builder = builder.connector(https);
-    }
-    match RT.block_on(builder.build()) {
-        Ok(db) => {
-            let db = Box::leak(Box::new(db));
-            *out_db = db as *const libsql::Database;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error opening URL {url}: {e}"), out_err_msg);
-            1
+    catch_panic_running(out_err, || {
+        let url = unsafe { std::ffi::CStr::from_ptr(url) };
+        let url = match url.to_str() {
+            Ok(url) => url,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong URL: {e}")),
+                    out_err,
+                );
+            }
+        };
+        let auth_token = unsafe { std::ffi::CStr::from_ptr(auth_token) };
+        let auth_token = match auth_token.to_str() {
+            Ok(token) => token,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong Auth Token: {e}")),
+                    out_err,
+                );
+            }
+        };
+        let http = match HttpOptions::from_raw(proxy_url, headers, headers_len) {
+            Ok(http) => http,
+            Err(e) => return set_err(e, out_err),
+        };
+        let mut tls = TlsOptions::default();
+        tls.with_webpki = with_webpki;
+        let mut builder = libsql::Builder::new_remote(url.to_string(), auth_token.to_string());
+        match connector(&tls, &http, None) {
+            Ok(Some(connector)) => builder = builder.connector(connector),
+            Ok(None) => {}
+            Err(e) => return set_err(e, out_err),
+        }
+        match RT.block_on(builder.build()) {
+            Ok(db) => {
+                *out_db = into_handle(db);
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql(&format!("Error opening URL {url}"), e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
+/// Closes the database following `mode`, one of `LIBSQL_CLOSE_*`. With
+/// `LIBSQL_CLOSE_FAIL_IF_BUSY` a database with open handles fails with `LIBSQL_ERR_BUSY` listing
+/// them, `LIBSQL_CLOSE_WAIT` waits for them up to `timeout_ms`, 0 meaning no limit, and fails with
+/// `LIBSQL_ERR_TIMEOUT` after that. The database stays open when this fails. Unlike most calls
+/// it still works once the runtime was shut down.
 #[no_mangle]
-pub unsafe extern "C" fn libsql_close(db: *mut libsql::Database) {
-    if db.is_null() {
-        return;
-    }
-    let _db = Box::from_raw(db);
-    // TODO: change this to free LibSqlDatabase (close action would be related with assuring
-    // closing current connections)
+pub unsafe extern "C" fn libsql_close(
+    db: *mut libsql::Database,
+    mode: std::ffi::c_int,
+    timeout_ms: std::ffi::c_ulonglong,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    catch_panic(out_err, || match children::close(db, mode, timeout_ms) {
+        Ok(()) => 0,
+        Err(e) => set_err(e, out_err),
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_connect(
     db: *const libsql::Database,
     out_conn: *mut *const libsql::Connection,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!db.is_null());
-
-    let db = get_ref(db);
-    let conn = match db.connect() {
-        Ok(conn) => conn,
-        Err(err) => {
-            set_err_msg(format!("Unable to connect: {}", err), out_err_msg);
-            return 1;
-        }
-    };
-    *out_conn = Box::leak(Box::new(conn)) as *const libsql::Connection;
-    0
+    guard(db, out_err, |db| {
+        let db = get_ref(db);
+        let (conn, raw_conn) = match raw::connect(db) {
+            Ok(conn) => conn,
+            Err(err) => {
+                return set_err(libsql_error::from_libsql("Unable to connect", err), out_err)
+            }
+        };
+        let conn = raw::into_connection_handle(conn, raw_conn);
+        *out_conn = children::adopt(conn, db, Kind::Connection);
+        0
+    })
 }
 
 ////////////////////////////////////
@@ -273,43 +565,50 @@ pub unsafe extern "C" fn libsql_load_extension(
     conn: *const libsql::Connection,
     path: *const std::ffi::c_char,
     entry_point: *const std::ffi::c_char,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!path.is_null());
+    guard(conn, out_err, |conn| {
+        debug_assert!(!path.is_null());
 
-    let path = unsafe { std::ffi::CStr::from_ptr(path) };
-    let path = match path.to_str() {
-        Ok(path) => path,
-        Err(e) => {
-            set_err_msg(format!("Wrong path: {}", e), out_err_msg);
-            return 2;
-        }
-    };
-    let mut entry_point_option = None;
-    if !entry_point.is_null() {
-        let entry_point = unsafe { std::ffi::CStr::from_ptr(entry_point) };
-        entry_point_option = match entry_point.to_str() {
-            Ok(entry_point) => Some(entry_point),
+        let path = unsafe { std::ffi::CStr::from_ptr(path) };
+        let path = match path.to_str() {
+            Ok(path) => path,
             Err(e) => {
-                set_err_msg(format!("Wrong entry point: {}", e), out_err_msg);
-                return 4;
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong path: {}", e)),
+                    out_err,
+                );
             }
         };
-    }
-    let conn = get_ref(conn);
-    match RT.block_on(async move {
-        let _guard = LoadExtensionGuard::new(conn)?;
-        conn.load_extension(path, entry_point_option)?;
-        Ok::<(), errors::Error>(())
-    }) {
-        Ok(()) => {}
-        Err(e) => {
-            set_err_msg(format!("Error loading extension: {}", e), out_err_msg);
-            return 6;
+        let mut entry_point_option = None;
+        if !entry_point.is_null() {
+            let entry_point = unsafe { std::ffi::CStr::from_ptr(entry_point) };
+            entry_point_option = match entry_point.to_str() {
+                Ok(entry_point) => Some(entry_point),
+                Err(e) => {
+                    return set_err(
+                        libsql_error::invalid_argument(format!("Wrong entry point: {}", e)),
+                        out_err,
+                    );
+                }
+            };
         }
-    };
-    0
+        let conn = get_ref(conn);
+        match RT.block_on(async move {
+            let _guard = LoadExtensionGuard::new(conn)?;
+            conn.load_extension(path, entry_point_option)?;
+            Ok::<(), errors::Error>(())
+        }) {
+            Ok(()) => {}
+            Err(e) => {
+                return set_err(
+                    libsql_error::from_libsql("Error loading extension", e),
+                    out_err,
+                );
+            }
+        };
+        0
+    })
 }
 
 #[no_mangle]
@@ -317,61 +616,88 @@ pub unsafe extern "C" fn libsql_transaction_with_behavior(
     conn: *const libsql::Connection,
     out_transaction: *mut *const libsql::Transaction,
     transaction_behavior: std::ffi::c_int,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-
-    let transaction_behavior = match transaction_behavior as i8 {
-        LIBSQL_TRANSACTION_DEFERRED => libsql::TransactionBehavior::Deferred,
-        LIBSQL_TRANSACTION_IMMEDIATE => libsql::TransactionBehavior::Immediate,
-        LIBSQL_TRANSACTION_EXCLUSIVE => libsql::TransactionBehavior::Exclusive,
-        LIBSQL_TRANSACTION_READONLY => libsql::TransactionBehavior::ReadOnly,
-        _ => libsql::TransactionBehavior::Deferred,
-    };
+    guard(conn, out_err, |conn| {
+        let transaction_behavior = match transaction_behavior as i8 {
+            LIBSQL_TRANSACTION_DEFERRED => libsql::TransactionBehavior::Deferred,
+            LIBSQL_TRANSACTION_IMMEDIATE => libsql::TransactionBehavior::Immediate,
+            LIBSQL_TRANSACTION_EXCLUSIVE => libsql::TransactionBehavior::Exclusive,
+            LIBSQL_TRANSACTION_READONLY => libsql::TransactionBehavior::ReadOnly,
+            _ => libsql::TransactionBehavior::Deferred,
+        };
 
-    let conn = get_ref(conn);
-    match RT.block_on(conn.transaction_with_behavior(transaction_behavior)) {
-        Ok(transaction) => {
-            let transaction = Box::leak(Box::from(transaction));
-            *out_transaction = transaction;
-            return 0;
-        }
-        Err(e) => {
-            set_err_msg(format!("Error creating transaction: {e}"), out_err_msg);
-            return 1;
+        let conn = get_ref(conn);
+        match RT.block_on(conn.transaction_with_behavior(transaction_behavior)) {
+            Ok(transaction) => {
+                *out_transaction =
+                    children::adopt(into_handle(transaction), conn, Kind::Transaction);
+                return 0;
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error creating transaction", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_query(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
     out_rows: *mut *const libsql::Rows,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!sql.is_null());
+    libsql_query_timeout(conn, sql, cancel_token, 0, out_rows, out_err)
+}
 
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    match RT.block_on(conn.query(sql, ())) {
-        Ok(rows_) => {
-            *out_rows = Box::leak(Box::new(rows_)) as *const libsql::Rows;
-            return 0;
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            return 1;
+/// A `timeout_ms` of 0 uses the default timeout of the connection.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_query_timeout(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
+    timeout_ms: std::ffi::c_ulonglong,
+    out_rows: *mut *const libsql::Rows,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, cancel_token, timeout_ms) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::query(call, Params::None)) {
+            Ok(rows) => {
+                *out_rows = children::adopt(into_handle(rows), conn, Kind::Rows);
+                0
+            }
+            Err(e) => set_err(e, out_err),
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_query_async(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<*const libsql::Rows>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let parent = SendPtr::new(conn);
+        completion.spawn(calls::query(call, Params::None), move |rows| {
+            children::adopt(into_handle(rows), parent.get(), Kind::Rows)
+        });
+        0
+    })
 }
 
 #[no_mangle]
@@ -379,33 +705,54 @@ pub unsafe extern "C" fn libsql_query_positional(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
     in_positional_values: *const Vec<libsql::Value>,
+    cancel_token: *const libsql_cancel_token,
     out_rows: *mut *const libsql::Rows,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!sql.is_null());
-    debug_assert!(!in_positional_values.is_null());
-
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    let pos_values = (*in_positional_values).clone();
-    match RT.block_on(conn.query(sql, libsql::params::Params::Positional(pos_values))) {
-        Ok(rows) => {
-            *out_rows = Box::leak(Box::from(rows));
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match positional(in_positional_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::query(call, params)) {
+            Ok(rows) => {
+                *out_rows = children::adopt(into_handle(rows), conn, Kind::Rows);
+                0
+            }
+            Err(e) => set_err(e, out_err),
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_query_positional_async(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    in_positional_values: *const Vec<libsql::Value>,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<*const libsql::Rows>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match positional(in_positional_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        let parent = SendPtr::new(conn);
+        completion.spawn(calls::query(call, params), move |rows| {
+            children::adopt(into_handle(rows), parent.get(), Kind::Rows)
+        });
+        0
+    })
 }
 
 #[no_mangle]
@@ -413,64 +760,111 @@ pub unsafe extern "C" fn libsql_query_named(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
     in_named_values: *const Vec<(String, libsql::Value)>,
+    cancel_token: *const libsql_cancel_token,
     out_rows: *mut *const libsql::Rows,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!sql.is_null());
-    debug_assert!(!in_named_values.is_null());
-
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    let pos_values = (*in_named_values).clone();
-    match RT.block_on(conn.query(sql, libsql::params::Params::Named(pos_values))) {
-        Ok(rows) => {
-            *out_rows = Box::leak(Box::from(rows));
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match named(in_named_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::query(call, params)) {
+            Ok(rows) => {
+                *out_rows = children::adopt(into_handle(rows), conn, Kind::Rows);
+                0
+            }
+            Err(e) => set_err(e, out_err),
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_query_named_async(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    in_named_values: *const Vec<(String, libsql::Value)>,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<*const libsql::Rows>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match named(in_named_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        let parent = SendPtr::new(conn);
+        completion.spawn(calls::query(call, params), move |rows| {
+            children::adopt(into_handle(rows), parent.get(), Kind::Rows)
+        });
+        0
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_execute_none(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
     out_rows_change: *mut std::ffi::c_ulong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!sql.is_null());
+    libsql_execute_timeout(conn, sql, cancel_token, 0, out_rows_change, out_err)
+}
 
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    match RT.block_on(conn.execute(sql, libsql::params::Params::None)) {
-        Ok(rows_change) => {
-            *out_rows_change = rows_change;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
+/// A `timeout_ms` of 0 uses the default timeout of the connection.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_execute_timeout(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
+    timeout_ms: std::ffi::c_ulonglong,
+    out_rows_change: *mut std::ffi::c_ulong,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, cancel_token, timeout_ms) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::execute(call, Params::None)) {
+            Ok(rows_change) => {
+                *out_rows_change = rows_change;
+                0
+            }
+            Err(e) => set_err(e, out_err),
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_execute_async(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<u64>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        completion.spawn(calls::execute(call, Params::None), |rows_change| {
+            rows_change
+        });
+        0
+    })
 }
 
 #[no_mangle]
@@ -478,33 +872,51 @@ pub unsafe extern "C" fn libsql_execute_positional(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
     in_positional_values: *const Vec<libsql::Value>,
+    cancel_token: *const libsql_cancel_token,
     out_rows_change: *mut std::ffi::c_ulong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!sql.is_null());
-    debug_assert!(!in_positional_values.is_null());
-
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    let pos_values = (*in_positional_values).clone();
-    match RT.block_on(conn.execute(sql, libsql::params::Params::Positional(pos_values))) {
-        Ok(rows_change) => {
-            *out_rows_change = rows_change;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match positional(in_positional_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::execute(call, params)) {
+            Ok(rows_change) => {
+                *out_rows_change = rows_change;
+                0
+            }
+            Err(e) => set_err(e, out_err),
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_execute_positional_async(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    in_positional_values: *const Vec<libsql::Value>,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<u64>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match positional(in_positional_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        completion.spawn(calls::execute(call, params), |rows_change| rows_change);
+        0
+    })
 }
 
 #[no_mangle]
@@ -512,129 +924,215 @@ pub unsafe extern "C" fn libsql_execute_named(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
     in_named_values: *const Vec<(String, libsql::Value)>,
+    cancel_token: *const libsql_cancel_token,
     out_rows_change: *mut std::ffi::c_ulong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!sql.is_null());
-    debug_assert!(!in_named_values.is_null());
-
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    let pos_values = (*in_named_values).clone();
-    match RT.block_on(conn.execute(sql, libsql::params::Params::Named(pos_values))) {
-        Ok(rows_change) => {
-            *out_rows_change = rows_change;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match named(in_named_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::execute(call, params)) {
+            Ok(rows_change) => {
+                *out_rows_change = rows_change;
+                0
+            }
+            Err(e) => set_err(e, out_err),
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_execute_named_async(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    in_named_values: *const Vec<(String, libsql::Value)>,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<u64>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let params = match named(in_named_values) {
+            Ok(params) => params,
+            Err(e) => return set_err(e, out_err),
+        };
+        completion.spawn(calls::execute(call, params), |rows_change| rows_change);
+        0
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_execute_batch(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
     out_batch_rows: *mut *const libsql::BatchRows,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    match RT.block_on(conn.execute_batch(sql)) {
-        Ok(b_rows) => {
-            *out_batch_rows = Box::leak(Box::new(b_rows)) as *const libsql::BatchRows;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::execute_batch(call)) {
+            Ok(batch_rows) => {
+                *out_batch_rows = children::adopt(into_handle(batch_rows), conn, Kind::Rows);
+                0
+            }
+            Err(e) => set_err(e, out_err),
         }
-    }
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn libsql_changes(conn: *const libsql::Connection) -> u64 {
-    debug_assert!(!conn.is_null());
+pub unsafe extern "C" fn libsql_execute_batch_async(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<*const libsql::BatchRows>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, cancel_token, 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let parent = SendPtr::new(conn);
+        completion.spawn(calls::execute_batch(call), move |batch_rows| {
+            children::adopt(into_handle(batch_rows), parent.get(), Kind::Rows)
+        });
+        0
+    })
+}
 
-    return get_ref(conn).changes();
+#[no_mangle]
+pub unsafe extern "C" fn libsql_changes(conn: *const libsql::Connection) -> u64 {
+    guard_or(conn, 0, |conn| {
+        return get_ref(conn).changes();
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_last_insert_rowid(conn: *const libsql::Connection) -> i64 {
-    debug_assert!(!conn.is_null());
+    guard_or(conn, 0, |conn| {
+        return get_ref(conn).last_insert_rowid();
+    })
+}
 
-    return get_ref(conn).last_insert_rowid();
+/// Timeout applied to the statements run on the connection, 0 removes it.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_default_timeout(
+    conn: *const libsql::Connection,
+    timeout_ms: std::ffi::c_ulonglong,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(conn, out_err, |conn| {
+        set_default_timeout(conn, timeout_ms);
+        0
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_reset(
     conn: *const libsql::Connection,
-    _out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
+    guard(conn, out_err, |conn| {
+        let conn = get_ref(conn);
+        RT.block_on(conn.reset());
+        0
+    })
+}
 
-    let conn = get_ref(conn);
-    RT.block_on(conn.reset());
-    0
+/// Takes back the connection of a freed handle, forgetting everything kept about it by its
+/// address (children, default timeout, SQLite connection and what's registered on it).
+unsafe fn detach_connection(
+    conn: *mut libsql::Connection,
+) -> (Box<libsql::Connection>, Option<raw::RawConnection>) {
+    children::release(conn);
+    clear_default_timeout(conn);
+    let raw_conn = raw::detach(conn);
+    (Box::from_raw(conn), raw_conn)
 }
 
+/// Connections acquired from a pool are released to it instead.
 #[no_mangle]
 pub unsafe extern "C" fn libsql_disconnect(conn: *mut libsql::Connection) {
-    if conn.is_null() {
-        return;
-    }
-    let conn = Box::from_raw(conn);
-    RT.spawn_blocking(|| {
-        drop(conn);
-    });
+    guard_free(conn, |conn| {
+        let (conn, raw_conn) = detach_connection(conn);
+        let Some(conn) = pool::release_disconnected(conn, raw_conn) else {
+            return;
+        };
+        RT.spawn_blocking(|| {
+            drop(conn);
+        });
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_prepare(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    out_stmt: *mut *const libsql::Statement,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    libsql_prepare_timeout(conn, sql, 0, out_stmt, out_err)
+}
+
+/// A `timeout_ms` of 0 uses the default timeout of the connection.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_prepare_timeout(
+    conn: *const libsql::Connection,
+    sql: *const std::ffi::c_char,
+    timeout_ms: std::ffi::c_ulonglong,
+    out_stmt: *mut *const libsql::Statement,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(conn, out_err, |conn| {
+        let call = match read_call(conn, sql, null(), timeout_ms) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(calls::prepare(call)) {
+            Ok(stmt) => {
+                *out_stmt = children::adopt(into_handle(stmt), conn, Kind::Statement);
+                0
+            }
+            Err(e) => set_err(e, out_err),
+        }
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn libsql_prepare(
+pub unsafe extern "C" fn libsql_prepare_async(
     conn: *const libsql::Connection,
     sql: *const std::ffi::c_char,
-    out_stmt: *mut *const libsql::Statement,
-    out_err_msg: *mut *const std::ffi::c_char,
+    callback: Option<libsql_callback<*const libsql::Statement>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!conn.is_null());
-    debug_assert!(!sql.is_null());
-
-    let sql = unsafe { std::ffi::CStr::from_ptr(sql) };
-    let sql = match sql.to_str() {
-        Ok(sql) => sql,
-        Err(e) => {
-            set_err_msg(format!("Wrong SQL: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    let conn = get_ref(conn);
-    match RT.block_on(conn.prepare(sql)) {
-        Ok(stmt) => {
-            *out_stmt = Box::leak(Box::new(stmt)) as *const libsql::Statement;
-        }
-        Err(e) => {
-            set_err_msg(format!("Error preparing statement: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    0
+    guard_async(conn, callback, user_data, out_err, |conn, completion| {
+        let call = match read_call(conn, sql, null(), 0) {
+            Ok(call) => call,
+            Err(e) => return set_err(e, out_err),
+        };
+        let parent = SendPtr::new(conn);
+        completion.spawn(calls::prepare(call), move |stmt| {
+            children::adopt(into_handle(stmt), parent.get(), Kind::Statement)
+        });
+        0
+    })
 }
 
 //////////////////////////////////////////////
@@ -644,35 +1142,37 @@ pub unsafe extern "C" fn libsql_prepare(
 pub unsafe extern "C" fn libsql_make_namedvalues(
     named_vals: *mut *const Vec<(String, libsql::Value)>,
 ) {
-    *named_vals = Box::leak(Box::new(Vec::new())) as *const Vec<(String, libsql::Value)>;
+    catch_panic_or((), || {
+        *named_vals = into_handle(Vec::new());
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_namedvalues(named_vals: *mut Vec<(String, libsql::Value)>) {
-    if named_vals.is_null() {
-        return;
-    }
-    let _ = Box::from_raw(named_vals);
+    guard_free(named_vals, |named_vals| {
+        let _ = Box::from_raw(named_vals);
+    })
 }
 
 unsafe fn named_helper<T>(
     named_vals: *mut Vec<(String, libsql::Value)>,
     name: *const std::ffi::c_char,
     value: T,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int
 where
     libsql::Value: From<T>,
 {
-    debug_assert!(!named_vals.is_null());
     debug_assert!(!name.is_null());
 
     let name = unsafe { std::ffi::CStr::from_ptr(name) };
     let name = match name.to_str() {
         Ok(v) => v,
         Err(e) => {
-            set_err_msg(format!("Wrong named string: {}", e), out_err_msg);
-            return 1;
+            return set_err(
+                libsql_error::invalid_argument(format!("Wrong named string: {}", e)),
+                out_err,
+            );
         }
     };
     let named_vals = get_mut_ref(named_vals);
@@ -687,9 +1187,11 @@ pub unsafe extern "C" fn libsql_named_bind_int(
     named_vals: *mut Vec<(String, libsql::Value)>,
     name: *const std::ffi::c_char,
     value: std::ffi::c_longlong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    return named_helper(named_vals, name, value, out_err_msg);
+    guard(named_vals, out_err, |named_vals| {
+        return named_helper(named_vals, name, value, out_err);
+    })
 }
 
 #[no_mangle]
@@ -697,18 +1199,22 @@ pub unsafe extern "C" fn libsql_named_bind_float(
     named_vals: *mut Vec<(String, libsql::Value)>,
     name: *const std::ffi::c_char,
     value: std::ffi::c_double,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    return named_helper(named_vals, name, value, out_err_msg);
+    guard(named_vals, out_err, |named_vals| {
+        return named_helper(named_vals, name, value, out_err);
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_named_bind_null(
     named_vals: *mut Vec<(String, libsql::Value)>,
     name: *const std::ffi::c_char,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    return named_helper(named_vals, name, libsql::Value::Null, out_err_msg);
+    guard(named_vals, out_err, |named_vals| {
+        return named_helper(named_vals, name, libsql::Value::Null, out_err);
+    })
 }
 
 #[no_mangle]
@@ -716,19 +1222,23 @@ pub unsafe extern "C" fn libsql_named_bind_string(
     named_vals: *mut Vec<(String, libsql::Value)>,
     name: *const std::ffi::c_char,
     value: *const std::ffi::c_char,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!value.is_null());
+    guard(named_vals, out_err, |named_vals| {
+        debug_assert!(!value.is_null());
 
-    let value = unsafe { std::ffi::CStr::from_ptr(value) };
-    let value = match value.to_str() {
-        Ok(v) => v,
-        Err(e) => {
-            set_err_msg(format!("Wrong value string: {}", e), out_err_msg);
-            return 1;
-        }
-    };
-    return named_helper(named_vals, name, value, out_err_msg);
+        let value = unsafe { std::ffi::CStr::from_ptr(value) };
+        let value = match value.to_str() {
+            Ok(v) => v,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong value string: {}", e)),
+                    out_err,
+                );
+            }
+        };
+        return named_helper(named_vals, name, value, out_err);
+    })
 }
 
 #[no_mangle]
@@ -737,20 +1247,24 @@ pub unsafe extern "C" fn libsql_named_bind_blob(
     name: *const std::ffi::c_char,
     value: *const std::ffi::c_uchar,
     value_len: std::ffi::c_int,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!value.is_null());
+    guard(named_vals, out_err, |named_vals| {
+        debug_assert!(!value.is_null());
 
-    let value_len: usize = match value_len.try_into() {
-        Ok(v) => v,
-        Err(e) => {
-            set_err_msg(format!("Wrong param value len: {}", e), out_err_msg);
-            return 2;
-        }
-    };
-    let value = unsafe { core::slice::from_raw_parts(value, value_len) };
-    let value = Vec::from(value);
-    return named_helper(named_vals, name, value, out_err_msg);
+        let value_len: usize = match value_len.try_into() {
+            Ok(v) => v,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong param value len: {}", e)),
+                    out_err,
+                );
+            }
+        };
+        let value = unsafe { core::slice::from_raw_parts(value, value_len) };
+        let value = Vec::from(value);
+        return named_helper(named_vals, name, value, out_err);
+    })
 }
 
 ///////////////////////////////////////////////
@@ -758,33 +1272,34 @@ pub unsafe extern "C" fn libsql_named_bind_blob(
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_make_positional_values(pos_values: *mut *const Vec<libsql::Value>) {
-    *pos_values = Box::leak(Box::new(Vec::new())) as *const Vec<libsql::Value>;
+    catch_panic_or((), || {
+        *pos_values = into_handle(Vec::new());
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_positional_values(pos_values: *mut Vec<libsql::Value>) {
-    if pos_values.is_null() {
-        return;
-    }
-    let _ = Box::from_raw(pos_values);
+    guard_free(pos_values, |pos_values| {
+        let _ = Box::from_raw(pos_values);
+    })
 }
 
 unsafe fn positional_helper<T>(
     pos_values: *mut Vec<libsql::Value>,
     idx: std::ffi::c_uint,
     value: T,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int
 where
     libsql::Value: From<T>,
 {
-    debug_assert!(!pos_values.is_null());
-
     let idx: usize = match idx.try_into() {
         Ok(x) => x,
         Err(e) => {
-            set_err_msg(format!("Wrong param index: {}", e), out_err_msg);
-            return 1;
+            return set_err(
+                libsql_error::invalid_argument(format!("Wrong param index: {}", e)),
+                out_err,
+            );
         }
     };
     let pos_values = get_mut_ref(pos_values);
@@ -800,9 +1315,11 @@ pub unsafe extern "C" fn libsql_positional_bind_int(
     pos_values: *mut Vec<libsql::Value>,
     idx: std::ffi::c_uint,
     value: std::ffi::c_longlong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    return positional_helper(pos_values, idx, value, out_err_msg);
+    guard(pos_values, out_err, |pos_values| {
+        return positional_helper(pos_values, idx, value, out_err);
+    })
 }
 
 #[no_mangle]
@@ -810,18 +1327,22 @@ pub unsafe extern "C" fn libsql_positional_bind_float(
     pos_values: *mut Vec<libsql::Value>,
     idx: std::ffi::c_uint,
     value: std::ffi::c_double,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    return positional_helper(pos_values, idx, value, out_err_msg);
+    guard(pos_values, out_err, |pos_values| {
+        return positional_helper(pos_values, idx, value, out_err);
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_positional_bind_null(
     pos_values: *mut Vec<libsql::Value>,
     idx: std::ffi::c_uint,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    return positional_helper(pos_values, idx, libsql::Value::Null, out_err_msg);
+    guard(pos_values, out_err, |pos_values| {
+        return positional_helper(pos_values, idx, libsql::Value::Null, out_err);
+    })
 }
 
 #[no_mangle]
@@ -829,19 +1350,23 @@ pub unsafe extern "C" fn libsql_positional_bind_string(
     pos_values: *mut Vec<libsql::Value>,
     idx: std::ffi::c_uint,
     value: *const std::ffi::c_char,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!value.is_null());
+    guard(pos_values, out_err, |pos_values| {
+        debug_assert!(!value.is_null());
 
-    let value = unsafe { std::ffi::CStr::from_ptr(value) };
-    let value = match value.to_str() {
-        Ok(v) => v,
-        Err(e) => {
-            set_err_msg(format!("Wrong param value: {}", e), out_err_msg);
-            return 2;
-        }
-    };
-    return positional_helper(pos_values, idx, value.to_string(), out_err_msg);
+        let value = unsafe { std::ffi::CStr::from_ptr(value) };
+        let value = match value.to_str() {
+            Ok(v) => v,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong param value: {}", e)),
+                    out_err,
+                );
+            }
+        };
+        return positional_helper(pos_values, idx, value.to_string(), out_err);
+    })
 }
 
 #[no_mangle]
@@ -850,20 +1375,24 @@ pub unsafe extern "C" fn libsql_positional_bind_blob(
     idx: std::ffi::c_uint,
     value: *const std::ffi::c_uchar,
     value_len: std::ffi::c_int,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!value.is_null());
+    guard(pos_values, out_err, |pos_values| {
+        debug_assert!(!value.is_null());
 
-    let value_len: usize = match value_len.try_into() {
-        Ok(v) => v,
-        Err(e) => {
-            set_err_msg(format!("Wrong param value len: {}", e), out_err_msg);
-            return 2;
-        }
-    };
-    let value = unsafe { core::slice::from_raw_parts(value, value_len) };
-    let value = Vec::from(value);
-    return positional_helper(pos_values, idx, value, out_err_msg);
+        let value_len: usize = match value_len.try_into() {
+            Ok(v) => v,
+            Err(e) => {
+                return set_err(
+                    libsql_error::invalid_argument(format!("Wrong param value len: {}", e)),
+                    out_err,
+                );
+            }
+        };
+        let value = unsafe { core::slice::from_raw_parts(value, value_len) };
+        let value = Vec::from(value);
+        return positional_helper(pos_values, idx, value, out_err);
+    })
 }
 
 ////////////////////////////////////////////////
@@ -873,22 +1402,22 @@ pub unsafe extern "C" fn libsql_positional_bind_blob(
 pub unsafe extern "C" fn libsql_query_stmt(
     stmt: *mut libsql::Statement,
     out_rows: *mut *const libsql::Rows,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-
-    let stmt = get_mut_ref(stmt);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
 
-    match RT.block_on(stmt.query(libsql::params::Params::None)) {
-        Ok(rows_) => {
-            *out_rows = Box::leak(Box::new(rows_)) as *const libsql::Rows;
-            return 0;
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            return 1;
+        match RT.block_on(stmt.query(libsql::params::Params::None)) {
+            Ok(rows_) => {
+                *out_rows = children::adopt(into_handle(rows_), stmt, Kind::Rows);
+                return 0;
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
@@ -896,24 +1425,26 @@ pub unsafe extern "C" fn libsql_query_stmt_positional(
     stmt: *mut libsql::Statement,
     pos_values: *const Vec<libsql::Value>,
     out_rows: *mut *const libsql::Rows,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-    debug_assert!(!pos_values.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    let pos_values = get_ref(pos_values);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        let pos_values = match get_handle(pos_values) {
+            Ok(pos_values) => pos_values,
+            Err(e) => return set_err(e, out_err),
+        };
 
-    match RT.block_on(stmt.query(libsql::params::Params::Positional(pos_values.clone()))) {
-        Ok(rows_) => {
-            *out_rows = Box::leak(Box::new(rows_)) as *const libsql::Rows;
-            return 0;
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            return 1;
+        match RT.block_on(stmt.query(libsql::params::Params::Positional(pos_values.clone()))) {
+            Ok(rows_) => {
+                *out_rows = children::adopt(into_handle(rows_), stmt, Kind::Rows);
+                return 0;
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
@@ -921,46 +1452,48 @@ pub unsafe extern "C" fn libsql_query_stmt_named(
     stmt: *mut libsql::Statement,
     named_values: *const Vec<(String, libsql::Value)>,
     out_rows: *mut *const libsql::Rows,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-    debug_assert!(!named_values.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    let named_values = get_ref(named_values);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        let named_values = match get_handle(named_values) {
+            Ok(named_values) => named_values,
+            Err(e) => return set_err(e, out_err),
+        };
 
-    match RT.block_on(stmt.query(libsql::params::Params::Named(named_values.clone()))) {
-        Ok(rows_) => {
-            *out_rows = Box::leak(Box::new(rows_)) as *const libsql::Rows;
-            return 0;
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            return 1;
+        match RT.block_on(stmt.query(libsql::params::Params::Named(named_values.clone()))) {
+            Ok(rows_) => {
+                *out_rows = children::adopt(into_handle(rows_), stmt, Kind::Rows);
+                return 0;
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_execute_stmt(
     stmt: *mut libsql::Statement,
     affected_rows: *mut std::ffi::c_ulong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-
-    let stmt = get_mut_ref(stmt);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
 
-    return match RT.block_on(stmt.execute(libsql::params::Params::None)) {
-        Ok(rows) => {
-            *affected_rows = rows as u64;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
-        }
-    };
+        return match RT.block_on(stmt.execute(libsql::params::Params::None)) {
+            Ok(rows) => {
+                *affected_rows = rows as u64;
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
+        };
+    })
 }
 
 #[no_mangle]
@@ -968,24 +1501,28 @@ pub unsafe extern "C" fn libsql_execute_stmt_positional(
     stmt: *mut libsql::Statement,
     pos_values: *const Vec<libsql::Value>,
     affected_rows: *mut std::ffi::c_ulong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-    debug_assert!(!pos_values.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    let pos_values = get_ref(pos_values);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        let pos_values = match get_handle(pos_values) {
+            Ok(pos_values) => pos_values,
+            Err(e) => return set_err(e, out_err),
+        };
 
-    return match RT.block_on(stmt.execute(libsql::params::Params::Positional(pos_values.clone()))) {
-        Ok(rows) => {
-            *affected_rows = rows as u64;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
-        }
-    };
+        return match RT
+            .block_on(stmt.execute(libsql::params::Params::Positional(pos_values.clone())))
+        {
+            Ok(rows) => {
+                *affected_rows = rows as u64;
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
+        };
+    })
 }
 
 #[no_mangle]
@@ -993,116 +1530,123 @@ pub unsafe extern "C" fn libsql_execute_stmt_named(
     stmt: *mut libsql::Statement,
     named_values: *const Vec<(String, libsql::Value)>,
     affected_rows: *mut std::ffi::c_ulong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-    debug_assert!(!named_values.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    let named_values = get_ref(named_values);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        let named_values = match get_handle(named_values) {
+            Ok(named_values) => named_values,
+            Err(e) => return set_err(e, out_err),
+        };
 
-    return match RT.block_on(stmt.execute(libsql::params::Params::Named(named_values.clone()))) {
-        Ok(rows) => {
-            *affected_rows = rows as u64;
-            0
-        }
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            2
-        }
-    };
+        return match RT.block_on(stmt.execute(libsql::params::Params::Named(named_values.clone())))
+        {
+            Ok(rows) => {
+                *affected_rows = rows as u64;
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
+        };
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_run_stmt(
     stmt: *mut libsql::Statement,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-
-    let stmt = get_mut_ref(stmt);
-
-    return match RT.block_on(stmt.run(libsql::params::Params::None)) {
-        Ok(_) => 0,
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            1
-        }
-    };
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+
+        return match RT.block_on(stmt.run(libsql::params::Params::None)) {
+            Ok(_) => 0,
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
+        };
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_run_stmt_positional(
     stmt: *mut libsql::Statement,
     pos_values: *const Vec<libsql::Value>,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-    debug_assert!(!pos_values.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    let pos_values = get_ref(pos_values);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        let pos_values = match get_handle(pos_values) {
+            Ok(pos_values) => pos_values,
+            Err(e) => return set_err(e, out_err),
+        };
 
-    return match RT.block_on(stmt.run(libsql::params::Params::Positional(pos_values.clone()))) {
-        Ok(_) => 0,
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            1
-        }
-    };
+        return match RT.block_on(stmt.run(libsql::params::Params::Positional(pos_values.clone()))) {
+            Ok(_) => 0,
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
+        };
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_run_stmt_named(
     stmt: *mut libsql::Statement,
     named_values: *const Vec<(String, libsql::Value)>,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-    debug_assert!(!named_values.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    let named_values = get_ref(named_values);
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        let named_values = match get_handle(named_values) {
+            Ok(named_values) => named_values,
+            Err(e) => return set_err(e, out_err),
+        };
 
-    return match RT.block_on(stmt.run(libsql::params::Params::Named(named_values.clone()))) {
-        Ok(_) => 0,
-        Err(e) => {
-            set_err_msg(format!("Error executing statement: {}", e), out_err_msg);
-            1
-        }
-    };
+        return match RT.block_on(stmt.run(libsql::params::Params::Named(named_values.clone()))) {
+            Ok(_) => 0,
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error executing statement", e),
+                out_err,
+            ),
+        };
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_finalize_stmt(
     stmt: *mut libsql::Statement,
-    _out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    stmt.finalize();
-    return 0;
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        stmt.finalize();
+        return 0;
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_reset_stmt(
     stmt: *mut libsql::Statement,
-    _out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!stmt.is_null());
-
-    let stmt = get_mut_ref(stmt);
-    stmt.reset();
-    return 0;
+    guard(stmt, out_err, |stmt| {
+        let stmt = get_mut_ref(stmt);
+        stmt.reset();
+        return 0;
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_stmt(stmt: *mut libsql::Statement) {
-    if stmt.is_null() {
-        return;
-    }
-    let _ = Box::from_raw(stmt);
+    guard_free(stmt, |stmt| {
+        children::release(stmt);
+        let _ = Box::from_raw(stmt);
+    })
 }
 
 ///////////////////////////////////////
@@ -1110,34 +1654,41 @@ pub unsafe extern "C" fn libsql_free_stmt(stmt: *mut libsql::Statement) {
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_rows(res: *mut libsql::Rows) {
-    if res.is_null() {
-        return;
-    }
-    let _ = Box::from_raw(res);
+    guard_free(res, |res| {
+        children::release(res);
+        let _ = Box::from_raw(res);
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_rows_future(res: *mut libsql::RowsFuture) {
-    if res.is_null() {
-        return;
-    }
-    let mut res = Box::from_raw(res);
-    res.wait().unwrap();
+    guard_free(res, |res| {
+        let mut res = Box::from_raw(res);
+        let _ = res.wait();
+    })
 }
 
+/// Waits for the statement of `res` to complete and frees it, failing with its error if any.
 #[no_mangle]
-pub unsafe extern "C" fn libsql_wait_result(res: *mut libsql::RowsFuture) {
-    debug_assert!(!res.is_null());
-
-    let mut res = Box::from_raw(res);
-    res.wait().unwrap();
+pub unsafe extern "C" fn libsql_wait_result(
+    res: *mut libsql::RowsFuture,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard_consume(res, out_err, |res| {
+        let mut res = Box::from_raw(res);
+        match res.wait() {
+            Ok(_) => 0,
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error waiting for result", e),
+                out_err,
+            ),
+        }
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_column_count(res: *const libsql::Rows) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-
-    get_ref(res).column_count()
+    guard_or(res, 0, |res| get_ref(res).column_count())
 }
 
 #[no_mangle]
@@ -1145,39 +1696,40 @@ pub unsafe extern "C" fn libsql_column_name(
     res: *const libsql::Rows,
     col: std::ffi::c_int,
     out_name: *mut *const std::ffi::c_char,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-
-    let res = get_ref(res);
-    if col >= res.column_count() {
-        set_err_msg(
-            format!(
-                "Column index too big - got index {} with {} columns",
-                col,
-                res.column_count()
-            ),
-            out_err_msg,
-        );
-        return 1;
-    }
-    let name = res.column_name(col);
-
-    if name.is_none() {
-        set_err_msg("Column should have valid index".to_string(), out_err_msg);
-        return 1;
-    }
+    guard(res, out_err, |res| {
+        let res = get_ref(res);
+        if col >= res.column_count() {
+            return set_err(
+                libsql_error::out_of_range(format!(
+                    "Column index too big - got index {} with {} columns",
+                    col,
+                    res.column_count()
+                )),
+                out_err,
+            );
+        }
+        let name = res.column_name(col);
 
-    match std::ffi::CString::new(name.unwrap()) {
-        Ok(name) => {
-            *out_name = name.into_raw();
-            0
+        if name.is_none() {
+            return set_err(
+                libsql_error::out_of_range("Column should have valid index"),
+                out_err,
+            );
         }
-        Err(e) => {
-            set_err_msg(format!("Invalid name: {}", e), out_err_msg);
-            1
+
+        match std::ffi::CString::new(name.unwrap()) {
+            Ok(name) => {
+                *out_name = name.into_raw();
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::invalid_argument(format!("Invalid name: {e}")),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
@@ -1186,82 +1738,134 @@ pub unsafe extern "C" fn libsql_column_type(
     row_: *const libsql::Row,
     col: std::ffi::c_int,
     out_type: *mut std::ffi::c_int,
-    out_err_msg: *mut *const std::ffi::c_char,
-) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-    debug_assert!(!row_.is_null());
-
-    let res = get_ref(res);
-    if col >= res.column_count() {
-        set_err_msg(
-            format!(
-                "Column index too big - got index {} with {} columns",
-                col,
-                res.column_count()
-            ),
-            out_err_msg,
-        );
-        return 1;
-    }
-
-    let row_ = get_ref(row_);
-    match row_.get_value(col) {
-        Ok(libsql::Value::Null) => {
-            *out_type = types::LIBSQL_NULL as i32;
-        }
-        Ok(libsql::Value::Text(_)) => {
-            *out_type = types::LIBSQL_TEXT as i32;
-        }
-        Ok(libsql::Value::Integer(_)) => {
-            *out_type = types::LIBSQL_INT as i32;
-        }
-        Ok(libsql::Value::Real(_)) => {
-            *out_type = types::LIBSQL_FLOAT as i32;
-        }
-        Ok(libsql::Value::Blob(_)) => {
-            *out_type = types::LIBSQL_BLOB as i32;
-        }
-        Err(e) => {
-            set_err_msg(format!("Error fetching value: {e}"), out_err_msg);
-            return 2;
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(res, out_err, |res| {
+        let res = get_ref(res);
+        if col >= res.column_count() {
+            return set_err(
+                libsql_error::out_of_range(format!(
+                    "Column index too big - got index {} with {} columns",
+                    col,
+                    res.column_count()
+                )),
+                out_err,
+            );
         }
-    };
-    0
+
+        let row_ = match get_handle(row_) {
+            Ok(row_) => row_,
+            Err(e) => return set_err(e, out_err),
+        };
+        match row_.get_value(col) {
+            Ok(libsql::Value::Null) => {
+                *out_type = types::LIBSQL_NULL as i32;
+            }
+            Ok(libsql::Value::Text(_)) => {
+                *out_type = types::LIBSQL_TEXT as i32;
+            }
+            Ok(libsql::Value::Integer(_)) => {
+                *out_type = types::LIBSQL_INT as i32;
+            }
+            Ok(libsql::Value::Real(_)) => {
+                *out_type = types::LIBSQL_FLOAT as i32;
+            }
+            Ok(libsql::Value::Blob(_)) => {
+                *out_type = types::LIBSQL_BLOB as i32;
+            }
+            Err(e) => {
+                return set_err(
+                    libsql_error::from_libsql("Error fetching value", e),
+                    out_err,
+                );
+            }
+        };
+        0
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_next_row(
     res: *mut libsql::Rows,
+    cancel_token: *const libsql_cancel_token,
     out_row: *mut *const libsql::Row,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-
-    let res = get_mut_ref(res);
-    let res = RT.block_on(res.next());
-    match res {
-        Ok(Some(row_)) => {
-            *out_row = Box::leak(Box::new(row_)) as *const libsql::Row;
-            0
-        }
-        Ok(None) => {
-            *out_row = std::ptr::null();
-            0
-        }
-        Err(e) => {
-            *out_row = std::ptr::null();
-            set_err_msg(format!("Error fetching next row: {}", e), out_err_msg);
-            1
+    guard(res, out_err, |res| {
+        let rows = res;
+        let res = get_mut_ref(res);
+        let token = match get_cancel_token(cancel_token) {
+            Ok(token) => token,
+            Err(e) => return set_err(e, out_err),
+        };
+        // Interrupted through the connection the rows come from.
+        let conn = token.as_ref().and_then(|_| children::connection(rows));
+        let fut = async {
+            res.next()
+                .await
+                .map_err(|e| libsql_error::from_libsql("Error fetching next row", e))
+        };
+        let res = RT.block_on(cancellable(token, conn.as_ref(), fut));
+        match res {
+            Ok(Some(row_)) => {
+                *out_row = children::adopt(into_handle(row_), rows, Kind::Row);
+                0
+            }
+            Ok(None) => {
+                *out_row = std::ptr::null();
+                0
+            }
+            Err(e) => {
+                *out_row = std::ptr::null();
+                set_err(e, out_err)
+            }
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_next_row_async(
+    res: *mut libsql::Rows,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_callback<*const libsql::Row>>,
+    user_data: *mut std::ffi::c_void,
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    // The host must not use the rows until the callback runs.
+    guard_async(res, callback, user_data, out_err, |res, completion| {
+        let parent = SendPtr::new(res);
+        let res = SendPtr::new(res);
+        let token = match get_cancel_token(cancel_token) {
+            Ok(token) => token,
+            Err(e) => return set_err(e, out_err),
+        };
+        let conn = token.as_ref().and_then(|_| children::connection(res.get()));
+        completion.spawn(
+            async move {
+                let res = get_mut_ref(res.get());
+                let fut = async {
+                    res.next()
+                        .await
+                        .map_err(|e| libsql_error::from_libsql("Error fetching next row", e))
+                };
+                cancellable(token, conn.as_ref(), fut).await
+            },
+            move |row_| {
+                row_.map_or(null(), |row_| {
+                    children::adopt(into_handle(row_), parent.get(), Kind::Row)
+                })
+            },
+        );
+        0
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_row(res: *mut libsql::Row) {
-    if res.is_null() {
-        return;
-    }
-    let _ = Box::from_raw(res);
+    guard_free(res, |res| {
+        children::release(res);
+        let _ = Box::from_raw(res);
+    })
 }
 
 #[no_mangle]
@@ -1269,32 +1873,31 @@ pub unsafe extern "C" fn libsql_get_string(
     res: *const libsql::Row,
     col: std::ffi::c_int,
     out_value: *mut *const std::ffi::c_char,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-
-    let res = get_ref(res);
-    match res.get_value(col) {
-        Ok(libsql::Value::Text(s)) => {
-            *out_value = translate_string(s);
-            0
-        }
-        Ok(_) => {
-            set_err_msg("Value not a string".into(), out_err_msg);
-            1
-        }
-        Err(e) => {
-            set_err_msg(format!("Error fetching value: {e}"), out_err_msg);
-            2
+    guard(res, out_err, |res| {
+        let res = get_ref(res);
+        match res.get_value(col) {
+            Ok(libsql::Value::Text(s)) => {
+                *out_value = translate_string(s);
+                0
+            }
+            Ok(_) => set_err(libsql_error::type_mismatch("Value not a string"), out_err),
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error fetching value", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_string(ptr: *const std::ffi::c_char) {
-    if !ptr.is_null() {
-        let _ = unsafe { std::ffi::CString::from_raw(ptr as *mut _) };
-    }
+    catch_panic_or((), || {
+        if !ptr.is_null() {
+            let _ = unsafe { std::ffi::CString::from_raw(ptr as *mut _) };
+        }
+    })
 }
 
 #[no_mangle]
@@ -1302,25 +1905,22 @@ pub unsafe extern "C" fn libsql_get_int(
     res: *const libsql::Row,
     col: std::ffi::c_int,
     out_value: *mut std::ffi::c_longlong,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-
-    let res = get_ref(res);
-    match res.get_value(col) {
-        Ok(libsql::Value::Integer(i)) => {
-            *out_value = i;
-            0
-        }
-        Ok(_) => {
-            set_err_msg("Value not an integer".into(), out_err_msg);
-            1
-        }
-        Err(e) => {
-            set_err_msg(format!("Error fetching value: {e}"), out_err_msg);
-            2
+    guard(res, out_err, |res| {
+        let res = get_ref(res);
+        match res.get_value(col) {
+            Ok(libsql::Value::Integer(i)) => {
+                *out_value = i;
+                0
+            }
+            Ok(_) => set_err(libsql_error::type_mismatch("Value not an integer"), out_err),
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error fetching value", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
@@ -1328,25 +1928,22 @@ pub unsafe extern "C" fn libsql_get_float(
     res: *const libsql::Row,
     col: std::ffi::c_int,
     out_value: *mut std::ffi::c_double,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-
-    let res = get_ref(res);
-    match res.get_value(col) {
-        Ok(libsql::Value::Real(f)) => {
-            *out_value = f;
-            0
-        }
-        Ok(_) => {
-            set_err_msg("Value not a float".into(), out_err_msg);
-            1
-        }
-        Err(e) => {
-            set_err_msg(format!("Error fetching value: {e}"), out_err_msg);
-            2
+    guard(res, out_err, |res| {
+        let res = get_ref(res);
+        match res.get_value(col) {
+            Ok(libsql::Value::Real(f)) => {
+                *out_value = f;
+                0
+            }
+            Ok(_) => set_err(libsql_error::type_mismatch("Value not a float"), out_err),
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error fetching value", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
@@ -1354,41 +1951,52 @@ pub unsafe extern "C" fn libsql_get_blob(
     res: *const libsql::Row,
     col: std::ffi::c_int,
     out_blob: *mut blob,
-    out_err_msg: *mut *const std::ffi::c_char,
-) -> std::ffi::c_int {
-    debug_assert!(!res.is_null());
-
-    let res = get_ref(res);
-    match res.get_value(col) {
-        Ok(libsql::Value::Blob(v)) => {
-            let len: i32 = v.len().try_into().unwrap();
-            let buf = v.into_boxed_slice();
-            let data = buf.as_ptr();
-            std::mem::forget(buf);
-            *out_blob = blob {
-                ptr: data as *const std::ffi::c_char,
-                len,
-            };
-            0
-        }
-        Ok(_) => {
-            set_err_msg("Value not a float".into(), out_err_msg);
-            1
-        }
-        Err(e) => {
-            set_err_msg(format!("Error fetching value: {}", e), out_err_msg);
-            2
+    out_err: *mut *const libsql_error,
+) -> std::ffi::c_int {
+    guard(res, out_err, |res| {
+        let res = get_ref(res);
+        match res.get_value(col) {
+            Ok(libsql::Value::Blob(v)) => {
+                let len: i32 = match v.len().try_into() {
+                    Ok(len) => len,
+                    Err(e) => {
+                        return set_err(
+                            libsql_error::out_of_range(format!("Blob too big: {e}")),
+                            out_err,
+                        );
+                    }
+                };
+                let buf = v.into_boxed_slice();
+                let data = buf.as_ptr();
+                std::mem::forget(buf);
+                *out_blob = blob {
+                    ptr: data as *const std::ffi::c_char,
+                    len,
+                };
+                0
+            }
+            Ok(_) => set_err(libsql_error::type_mismatch("Value not a blob"), out_err),
+            Err(e) => set_err(
+                libsql_error::from_libsql("Error fetching value", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_free_blob(b: blob) {
-    if !b.ptr.is_null() {
-        let ptr =
-            unsafe { std::slice::from_raw_parts_mut(b.ptr as *mut i8, b.len.try_into().unwrap()) };
-        let _ = unsafe { Box::from_raw(ptr) };
-    }
+    catch_panic_or((), || {
+        if !b.ptr.is_null() {
+            // Not ours then, leaking it beats freeing a wrong allocation.
+            let Ok(len) = b.len.try_into() else {
+                tracing::error!("Not freeing a blob of invalid length {}", b.len);
+                return;
+            };
+            let ptr = unsafe { std::slice::from_raw_parts_mut(b.ptr as *mut i8, len) };
+            let _ = unsafe { Box::from_raw(ptr) };
+        }
+    })
 }
 
 ///////////////////////////////////////////////////////
@@ -1397,37 +2005,36 @@ pub unsafe extern "C" fn libsql_free_blob(b: blob) {
 #[no_mangle]
 pub unsafe extern "C" fn libsql_commit_transaction(
     transaction: *mut libsql::Transaction,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!transaction.is_null());
+    guard_consume(transaction, out_err, |transaction| {
+        children::release(transaction);
+        let transaction = Box::from_raw(transaction);
 
-    let transaction = Box::from_raw(transaction);
-
-    match RT.block_on((*transaction).commit()) {
-        Ok(()) => return 0,
-        Err(e) => {
-            set_err_msg(format!("Transaction Commmit: {e}"), out_err_msg);
-            return 1;
+        match RT.block_on((*transaction).commit()) {
+            Ok(()) => return 0,
+            Err(e) => set_err(libsql_error::from_libsql("Transaction Commmit", e), out_err),
         }
-    }
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn libsql_rollback_transaction(
     transaction: *mut libsql::Transaction,
-    out_err_msg: *mut *const std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!transaction.is_null());
-
-    let transaction = Box::from_raw(transaction);
-
-    match RT.block_on((*transaction).rollback()) {
-        Ok(()) => return 0,
-        Err(e) => {
-            set_err_msg(format!("Transaction Rollback: {e}"), out_err_msg);
-            return 1;
+    guard_consume(transaction, out_err, |transaction| {
+        children::release(transaction);
+        let transaction = Box::from_raw(transaction);
+
+        match RT.block_on((*transaction).rollback()) {
+            Ok(()) => return 0,
+            Err(e) => set_err(
+                libsql_error::from_libsql("Transaction Rollback", e),
+                out_err,
+            ),
         }
-    }
+    })
 }
 
 // This is quite weird a transaction being able to create new transactions and act as a connection,
@@ -1437,43 +2044,41 @@ pub unsafe extern "C" fn libsql_connection_transaction(
     transaction: *const libsql::Transaction,
     connection: *mut *const libsql::Connection,
 ) -> std::ffi::c_int {
-    debug_assert!(!transaction.is_null());
-
-    *connection = get_ref(transaction).deref();
-    return 0;
+    guard(transaction, null_mut(), |tx| {
+        let borrowed = get_ref(tx).deref();
+        children::alias(borrowed, tx);
+        *connection = borrowed_handle(borrowed, transaction);
+        return 0;
+    })
 }
 
 //////////////////////////////////////////////////////
 //////////////// BATCH_ROWS //////////////////////////
 
+/// Rows of the next statement of the batch, null if it returns none. `out_done` is set to 1
+/// instead once every statement was read.
 #[no_mangle]
 pub unsafe extern "C" fn libsql_next_stmt_row_batchrows(
     batchrows: *mut libsql::BatchRows,
     out_rows: *mut *const libsql::Rows,
+    out_done: *mut std::ffi::c_char,
+    out_err: *mut *const libsql_error,
 ) -> std::ffi::c_int {
-    debug_assert!(!batchrows.is_null());
-
-    match get_mut_ref(batchrows).next_stmt_row() {
-        Some(Some(rows)) => {
-            *out_rows = Box::leak(Box::from(rows));
-            return 0;
-        }
-        Some(None) => {
-            *out_rows = null();
-            return 0
-        }
-        None => {
-            *out_rows = null();
-            return 1;
-        }
-    }
+    guard(batchrows, out_err, |batchrows| {
+        let next = get_mut_ref(batchrows).next_stmt_row();
+        *out_done = next.is_none() as std::ffi::c_char;
+        *out_rows = match next {
+            Some(Some(rows)) => children::adopt(into_handle(rows), batchrows, Kind::Rows),
+            _ => null(),
+        };
+        0
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn libsql_free_batchrows(
-    batchrows: *mut libsql::BatchRows) {
-    if batchrows.is_null() {
-        return
-    }
-    let _ = Box::from_raw(batchrows);
+pub unsafe extern "C" fn libsql_free_batchrows(batchrows: *mut libsql::BatchRows) {
+    guard_free(batchrows, |batchrows| {
+        children::release(batchrows);
+        let _ = Box::from_raw(batchrows);
+    })
 }
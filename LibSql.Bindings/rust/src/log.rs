@@ -0,0 +1,200 @@
+// Forwards `tracing` events to a callback of the host. A layer is installed as the global
+// subscriber the first time a callback is set, afterwards the callback, its max level and its
+// target filter can be swapped or removed at any time.
+
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    fmt::Write,
+    sync::{RwLock, RwLockReadGuard},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+use crate::error::{libsql_error, set_err};
+use crate::guard::catch_panic;
+use crate::types::{
+    libsql_log_callback, LIBSQL_ERR_MISUSE, LIBSQL_LOG_DEBUG, LIBSQL_LOG_ERROR, LIBSQL_LOG_INFO,
+    LIBSQL_LOG_TRACE, LIBSQL_LOG_WARN,
+};
+
+struct Sink {
+    callback: libsql_log_callback,
+    // Kept as an address so the sink can be shared between threads.
+    user_data: usize,
+    max_level: Level,
+    // Target prefixes, empty lets every target through.
+    targets: Vec<String>,
+}
+
+impl Sink {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.max_level
+            && (self.targets.is_empty()
+                || self
+                    .targets
+                    .iter()
+                    .any(|target| metadata.target().starts_with(target.as_str())))
+    }
+}
+
+lazy_static! {
+    static ref SINK: RwLock<Option<Sink>> = RwLock::new(None);
+}
+
+fn sink() -> RwLockReadGuard<'static, Option<Sink>> {
+    SINK.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn to_level(level: c_int) -> Option<Level> {
+    match level {
+        LIBSQL_LOG_TRACE => Some(Level::TRACE),
+        LIBSQL_LOG_DEBUG => Some(Level::DEBUG),
+        LIBSQL_LOG_INFO => Some(Level::INFO),
+        LIBSQL_LOG_WARN => Some(Level::WARN),
+        LIBSQL_LOG_ERROR => Some(Level::ERROR),
+        _ => None,
+    }
+}
+
+fn from_level(level: &Level) -> c_int {
+    match *level {
+        Level::TRACE => LIBSQL_LOG_TRACE,
+        Level::DEBUG => LIBSQL_LOG_DEBUG,
+        Level::INFO => LIBSQL_LOG_INFO,
+        Level::WARN => LIBSQL_LOG_WARN,
+        Level::ERROR => LIBSQL_LOG_ERROR,
+    }
+}
+
+/// Formats the `message` field followed by the other fields as `name=value`.
+#[derive(Default)]
+struct Message {
+    message: String,
+    fields: String,
+}
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+struct CallbackLayer;
+
+impl<S: Subscriber> Layer<S> for CallbackLayer {
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        sink().as_ref().is_some_and(|sink| sink.enabled(metadata))
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let sink = sink();
+        let Some(sink) = sink.as_ref() else {
+            return;
+        };
+        let metadata = event.metadata();
+        if !sink.enabled(metadata) {
+            return;
+        }
+        let mut message = Message::default();
+        event.record(&mut message);
+        message.message.push_str(&message.fields);
+        // Interior nul bytes can't cross the boundary.
+        let message = CString::new(message.message.replace('\0', "")).unwrap_or_default();
+        let target = CString::new(metadata.target().replace('\0', "")).unwrap_or_default();
+        unsafe {
+            (sink.callback)(
+                from_level(metadata.level()),
+                target.as_ptr(),
+                message.as_ptr(),
+                sink.user_data as *mut c_void,
+            )
+        };
+    }
+}
+
+lazy_static! {
+    // Whether the layer could be installed, it's only tried once.
+    static ref INSTALLED: bool = tracing_subscriber::registry()
+        .with(CallbackLayer)
+        .try_init()
+        .is_ok();
+}
+
+/// Sets the callback receiving the events at least as severe as `max_level` (`LIBSQL_LOG_*`)
+/// whose target starts with one of the comma separated prefixes of `target_filter`, null or
+/// empty lets every target through. A null `callback` removes the current one. Fails with
+/// `LIBSQL_ERR_MISUSE` if `libsql_enable_internal_tracing` was called before. It must not be
+/// called from the callback itself.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_log_callback(
+    callback: Option<libsql_log_callback>,
+    user_data: *mut c_void,
+    max_level: c_int,
+    target_filter: *const c_char,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    catch_panic(out_err, || {
+        let Some(callback) = callback else {
+            *SINK.write().unwrap_or_else(|e| e.into_inner()) = None;
+            return 0;
+        };
+        let Some(max_level) = to_level(max_level) else {
+            return set_err(
+                libsql_error::invalid_argument(format!("Unknown log level {max_level}")),
+                out_err,
+            );
+        };
+        let targets = if target_filter.is_null() {
+            vec![]
+        } else {
+            match CStr::from_ptr(target_filter).to_str() {
+                Ok(filter) => filter
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|target| !target.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                Err(e) => {
+                    return set_err(
+                        libsql_error::invalid_argument(format!("Wrong target filter: {e}")),
+                        out_err,
+                    );
+                }
+            }
+        };
+        if !*INSTALLED {
+            return set_err(
+                libsql_error::new(
+                    LIBSQL_ERR_MISUSE,
+                    "Another tracing subscriber is already installed",
+                ),
+                out_err,
+            );
+        }
+        *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(Sink {
+            callback,
+            user_data: user_data as usize,
+            max_level,
+            targets,
+        });
+        // The layer tells whether callsites are enabled, cached from the previous sink.
+        tracing::callsite::rebuild_interest_cache();
+        0
+    })
+}
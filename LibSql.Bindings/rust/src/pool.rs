@@ -0,0 +1,425 @@
+// Connection pool over a database. Acquired connections are regular connection handles, so every
+// connection function works on them, but instead of being disconnected they are handed back with
+// `libsql_pool_release`, which resets them and keeps them for the next acquire. Disconnecting one
+// releases it the same way. The pool counts as a handle of its database, which can't be closed
+// while the pool is alive.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ffi::{c_int, CStr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+use crate::cancel::{cancellable, get_cancel_token, libsql_cancel_token};
+use crate::children::{self, Kind};
+use crate::error::{libsql_error, set_err};
+use crate::guard::{guard, guard_consume_with, guard_free};
+use crate::handles::{into_handle, resolve};
+use crate::raw::{self, RawConnection};
+use crate::timeout::with_timeout;
+use crate::types::{
+    libsql_pool_stats, read_config, LibSqlPoolConfig, LIBSQL_ERR_CONNECTION, LIBSQL_ERR_MISUSE,
+};
+use crate::{detach_connection, get_ref, RT};
+
+struct Idle {
+    conn: libsql::Connection,
+    raw_conn: Option<RawConnection>,
+    since: Instant,
+}
+
+struct Pool {
+    // Value pointer of the database, which outlives the pool as its child.
+    db: usize,
+    min_size: usize,
+    idle_timeout: Option<Duration>,
+    health_check: Option<String>,
+    acquire_timeout: Option<Duration>,
+    // One permit per connection that may still be handed out.
+    slots: Semaphore,
+    idle: Mutex<Vec<Idle>>,
+    /// Connections alive, idle or acquired.
+    open: AtomicUsize,
+    acquired: AtomicUsize,
+}
+
+lazy_static! {
+    // Pool of each acquired connection, keyed by the address of the connection.
+    static ref ACQUIRED: Mutex<HashMap<usize, Weak<Pool>>> = Mutex::default();
+}
+
+fn acquired() -> MutexGuard<'static, HashMap<usize, Weak<Pool>>> {
+    ACQUIRED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub struct libsql_pool {
+    pool: Arc<Pool>,
+    reaper: Option<JoinHandle<()>>,
+}
+
+impl Drop for libsql_pool {
+    fn drop(&mut self) {
+        if let Some(reaper) = self.reaper.take() {
+            reaper.abort();
+        }
+        for idle in self.pool.idle().drain(..) {
+            disconnect(idle.conn);
+        }
+    }
+}
+
+fn disconnect(conn: libsql::Connection) {
+    // Same as `libsql_disconnect`, dropping a remote connection may block.
+    RT.spawn_blocking(|| drop(conn));
+}
+
+impl Pool {
+    fn idle(&self) -> MutexGuard<'_, Vec<Idle>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn connect(&self) -> Result<(libsql::Connection, Option<RawConnection>), libsql_error> {
+        let db = unsafe { get_ref(self.db as *const libsql::Database) };
+        let conn =
+            raw::connect(db).map_err(|e| libsql_error::from_libsql("Unable to connect", e))?;
+        self.open.fetch_add(1, Ordering::Relaxed);
+        Ok(conn)
+    }
+
+    /// Drops a connection that won't come back to the pool, freeing its slot if it had one.
+    fn discard(&self, conn: Option<libsql::Connection>, had_slot: bool) {
+        self.open.fetch_sub(1, Ordering::Relaxed);
+        if let Some(conn) = conn {
+            disconnect(conn);
+        }
+        if had_slot {
+            self.slots.add_permits(1);
+        }
+    }
+
+    /// Opens idle connections until `min_size` are open again, after some were discarded.
+    fn replenish(&self) {
+        while self.open.load(Ordering::Relaxed) < self.min_size && !self.slots.is_closed() {
+            match self.connect() {
+                Ok((conn, raw_conn)) => self.idle().push(Idle {
+                    conn,
+                    raw_conn,
+                    since: Instant::now(),
+                }),
+                Err(e) => {
+                    tracing::warn!("Unable to reopen the connections of the pool: {e}");
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn healthy(&self, conn: &libsql::Connection) -> bool {
+        let Some(sql) = &self.health_check else {
+            return true;
+        };
+        let result = async { conn.query(sql, ()).await?.next().await }.await;
+        if let Err(e) = &result {
+            tracing::debug!("Discarding pooled connection failing its health check: {e}");
+        }
+        result.is_ok()
+    }
+
+    /// Takes a slot and returns an idle connection that passes the health check, or a new one.
+    async fn acquire(&self) -> Result<(libsql::Connection, Option<RawConnection>), libsql_error> {
+        let wait = async {
+            let slot = self.slots.acquire().await;
+            slot.map_err(|_| libsql_error::new(LIBSQL_ERR_MISUSE, "The pool was freed"))
+        };
+        // Given back if the acquire fails or is cancelled.
+        let slot = with_timeout(self.acquire_timeout, None, wait).await?;
+        let conn = loop {
+            let idle = self.idle().pop();
+            let Some(Idle { conn, raw_conn, .. }) = idle else {
+                break self.connect()?;
+            };
+            let mut checking = Checking(self, Some(conn));
+            if self.healthy(checking.1.as_ref().unwrap()).await {
+                break (checking.1.take().unwrap(), raw_conn);
+            }
+        };
+        slot.forget();
+        // Idle connections failing the health check were discarded on the way.
+        self.replenish();
+        Ok(conn)
+    }
+
+    /// Resets a released connection and keeps it for the next acquire.
+    async fn put_back(&self, conn: libsql::Connection, raw_conn: Option<RawConnection>) {
+        if !conn.is_autocommit() {
+            tracing::debug!("Rolling back the transaction left open on a released connection");
+            if let Err(e) = conn.execute("ROLLBACK", ()).await {
+                tracing::warn!("Discarding released connection, rolling back failed: {e}");
+                self.discard(Some(conn), true);
+                return self.replenish();
+            }
+        }
+        conn.reset().await;
+        self.idle().push(Idle {
+            conn,
+            raw_conn,
+            since: Instant::now(),
+        });
+        self.slots.add_permits(1);
+    }
+
+    /// Disconnects the connections idle for longer than the timeout, keeping `min_size` open.
+    fn reap(&self, idle_timeout: Duration) {
+        let mut idle = self.idle();
+        // Oldest first, connections are pushed as they are released.
+        while let Some(oldest) = idle.first() {
+            if oldest.since.elapsed() < idle_timeout
+                || self.open.load(Ordering::Relaxed) <= self.min_size
+            {
+                break;
+            }
+            let oldest = idle.remove(0);
+            self.discard(Some(oldest.conn), false);
+        }
+    }
+}
+
+/// Idle connection going through the health check, discarded unless taken out.
+struct Checking<'a>(&'a Pool, Option<libsql::Connection>);
+
+impl Drop for Checking<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.1.take() {
+            self.0.discard(Some(conn), false);
+        }
+    }
+}
+
+fn spawn_reaper(pool: Weak<Pool>, idle_timeout: Duration) -> JoinHandle<()> {
+    RT.spawn(async move {
+        let mut ticker = tokio::time::interval((idle_timeout / 2).max(Duration::from_millis(10)));
+        loop {
+            ticker.tick().await;
+            let Some(pool) = pool.upgrade() else {
+                return;
+            };
+            pool.reap(idle_timeout);
+        }
+    })
+}
+
+fn read_string(ptr: *const std::ffi::c_char, what: &str) -> Result<Option<String>, libsql_error> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) => Ok(Some(s.to_string())),
+        Err(e) => Err(libsql_error::invalid_argument(format!("Wrong {what}: {e}"))),
+    }
+}
+
+fn millis(ms: std::ffi::c_ulonglong) -> Option<Duration> {
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
+
+/// Creates a pool of connections to `db`, opening `min_size` of them right away. Fails with
+/// `LIBSQL_ERR_CONNECTION` if they can't be opened.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_pool_new(
+    db: *const libsql::Database,
+    config: *const LibSqlPoolConfig,
+    out_pool: *mut *const libsql_pool,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    guard(db, out_err, |db| {
+        let config = match read_config(config) {
+            Ok(config) => config,
+            Err(e) => return set_err(e, out_err),
+        };
+        let health_check = match read_string(config.health_check, "health check") {
+            Ok(health_check) => health_check,
+            Err(e) => return set_err(e, out_err),
+        };
+        let (Ok(min_size), Ok(max_size)) = (
+            usize::try_from(config.min_size),
+            usize::try_from(config.max_size),
+        ) else {
+            return set_err(
+                libsql_error::invalid_argument("Pool sizes can't be negative"),
+                out_err,
+            );
+        };
+        if max_size == 0 || min_size > max_size {
+            return set_err(
+                libsql_error::invalid_argument(format!(
+                    "Invalid pool sizes, min {min_size} and max {max_size}"
+                )),
+                out_err,
+            );
+        }
+        let pool = Arc::new(Pool {
+            db: db as usize,
+            min_size,
+            idle_timeout: millis(config.idle_timeout_ms),
+            health_check,
+            acquire_timeout: millis(config.acquire_timeout_ms),
+            slots: Semaphore::new(max_size),
+            idle: Mutex::default(),
+            open: AtomicUsize::new(0),
+            acquired: AtomicUsize::new(0),
+        });
+        for _ in 0..min_size {
+            match pool.connect() {
+                Ok((conn, raw_conn)) => pool.idle().push(Idle {
+                    conn,
+                    raw_conn,
+                    since: Instant::now(),
+                }),
+                Err(e) => {
+                    let e = libsql_error::new(
+                        LIBSQL_ERR_CONNECTION,
+                        format!("Unable to open the connections of the pool: {e}"),
+                    );
+                    return set_err(e, out_err);
+                }
+            }
+        }
+        let reaper = pool
+            .idle_timeout
+            .map(|timeout| spawn_reaper(Arc::downgrade(&pool), timeout));
+        *out_pool = children::adopt(into_handle(libsql_pool { pool, reaper }), db, Kind::Pool);
+        0
+    })
+}
+
+/// Hands out a connection, waiting for one to be released when `max_size` are acquired, up to
+/// the `acquire_timeout_ms` of the pool.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_pool_acquire(
+    pool: *const libsql_pool,
+    cancel_token: *const libsql_cancel_token,
+    out_conn: *mut *const libsql::Connection,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    guard(pool, out_err, |pool| {
+        // Kept alive if the pool is freed meanwhile, the acquire fails then.
+        let pool = get_ref(pool).pool.clone();
+        let token = match get_cancel_token(cancel_token) {
+            Ok(token) => token,
+            Err(e) => return set_err(e, out_err),
+        };
+        match RT.block_on(cancellable(token, None, pool.acquire())) {
+            Ok((conn, raw_conn)) => {
+                let conn = raw::into_connection_handle(conn, raw_conn);
+                if let Ok(ptr) = resolve(conn) {
+                    acquired().insert(ptr as usize, Arc::downgrade(&pool));
+                    pool.acquired.fetch_add(1, Ordering::Relaxed);
+                }
+                *out_conn = children::adopt(conn, pool.db as *const (), Kind::Connection);
+                0
+            }
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
+
+/// Gives back a connection of `libsql_pool_acquire`, its handle is invalidated even if this
+/// fails. Transactions left open are rolled back and the connection is reset.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_pool_release(
+    pool: *const libsql_pool,
+    conn: *mut libsql::Connection,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    guard(pool, out_err, |pool| {
+        let pool = &get_ref(pool).pool;
+        let acquired = match resolve(conn) {
+            Ok(ptr) => match acquired().entry(ptr as usize) {
+                Entry::Occupied(owner) if std::ptr::eq(owner.get().as_ptr(), Arc::as_ptr(pool)) => {
+                    owner.remove();
+                    pool.acquired.fetch_sub(1, Ordering::Relaxed);
+                    true
+                }
+                _ => false,
+            },
+            Err(e) => return set_err(e, out_err),
+        };
+        if !acquired {
+            return set_err(
+                libsql_error::new(
+                    LIBSQL_ERR_MISUSE,
+                    "The connection wasn't acquired from this pool",
+                ),
+                out_err,
+            );
+        }
+        let mut returned = false;
+        let teardown = |conn| drop(detach_connection(conn));
+        let code = guard_consume_with(conn, out_err, teardown, |conn| {
+            let (conn, raw_conn) = detach_connection(conn);
+            returned = true;
+            RT.block_on(pool.put_back(*conn, raw_conn));
+            0
+        });
+        if !returned {
+            // Dropped by `guard_consume_with`, its handle was poisoned or the runtime shut down.
+            pool.discard(None, true);
+            pool.replenish();
+        }
+        code
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn libsql_pool_stats(
+    pool: *const libsql_pool,
+    out_stats: *mut libsql_pool_stats,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    guard(pool, out_err, |pool| {
+        if out_stats.is_null() {
+            return set_err(libsql_error::invalid_argument("Missing stats"), out_err);
+        }
+        let pool = &get_ref(pool).pool;
+        let idle = pool.idle().len();
+        let open = pool.open.load(Ordering::Relaxed);
+        *out_stats = libsql_pool_stats {
+            open: open as c_int,
+            idle: idle as c_int,
+            acquired: pool.acquired.load(Ordering::Relaxed) as c_int,
+        };
+        0
+    })
+}
+
+/// Disconnects the idle connections, those still acquired become regular connections disconnected
+/// by `libsql_disconnect`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_pool_free(pool: *mut libsql_pool) {
+    guard_free(pool, |pool| {
+        children::release(pool);
+        let pool = Box::from_raw(pool);
+        pool.pool.slots.close();
+    })
+}
+
+/// Releases `conn` to the pool it was acquired from, for `libsql_disconnect`. Returns it if it
+/// wasn't acquired or its pool was freed.
+pub fn release_disconnected(
+    conn: Box<libsql::Connection>,
+    raw_conn: Option<RawConnection>,
+) -> Option<Box<libsql::Connection>> {
+    let owner = acquired().remove(&(&*conn as *const _ as usize));
+    let Some(pool) = owner.and_then(|owner| owner.upgrade()) else {
+        return Some(conn);
+    };
+    pool.acquired.fetch_sub(1, Ordering::Relaxed);
+    RT.spawn(async move { pool.put_back(*conn, raw_conn).await });
+    None
+}
@@ -0,0 +1,220 @@
+// libsql doesn't expose the SQLite connection behind a `libsql::Connection`, which functions and
+// hooks have to be registered on. It's caught while the connection opens instead: SQLite runs
+// its auto extensions from `sqlite3_open_v2` on the opening thread, which happens during
+// `Database::connect` for local databases and the local side of replicas. The extension is only
+// registered while a connection opens, and of the connections it sees on the connecting thread
+// only the one whose main database is the file the database was opened with is taken. Remote
+// connections have no SQLite connection.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_char, c_int, CStr},
+    path::Path,
+    sync::{Mutex, MutexGuard, Once},
+};
+
+use libsql::ffi;
+
+use crate::children;
+use crate::error::libsql_error;
+use crate::handles::{into_handle, resolve};
+use crate::types::{LIBSQL_ERR_NOT_SUPPORTED, LIBSQL_ERR_SQLITE};
+use crate::{collations, functions, hooks};
+
+thread_local! {
+    // Set while `connect` runs on this thread, to the connections SQLite opened meanwhile.
+    static OPENED: RefCell<Option<Vec<usize>>> = const { RefCell::new(None) };
+}
+
+lazy_static! {
+    // Keyed by the address of the connection, not by its handle.
+    static ref RAW: Mutex<HashMap<usize, usize>> = Mutex::default();
+    // Path each local database was opened with, keyed by the address of the database.
+    static ref PATHS: Mutex<HashMap<usize, String>> = Mutex::default();
+    // Number of connections being opened, the extension is registered while it isn't 0.
+    static ref OPENING: Mutex<usize> = Mutex::default();
+}
+
+fn raw() -> MutexGuard<'static, HashMap<usize, usize>> {
+    RAW.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn paths() -> MutexGuard<'static, HashMap<usize, String>> {
+    PATHS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+unsafe extern "C" fn on_open(
+    db: *mut ffi::sqlite3,
+    _err: *mut *const c_char,
+    _api: *const ffi::sqlite3_api_routines,
+) -> c_int {
+    OPENED.with_borrow_mut(|opened| {
+        if let Some(opened) = opened {
+            opened.push(db as usize);
+        }
+    });
+    ffi::SQLITE_OK as c_int
+}
+
+/// Keeps the extension registered while alive.
+struct Opening;
+
+impl Opening {
+    fn start() -> Opening {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            // Registering initializes SQLite, which libsql must configure first. Opening an
+            // in-memory database does only that, its connection is opened by `connect`.
+            #[allow(deprecated)]
+            let _ = libsql::Database::open_in_memory();
+        });
+        let mut opening = OPENING.lock().unwrap_or_else(|e| e.into_inner());
+        if *opening == 0 {
+            unsafe { ffi::sqlite3_auto_extension(Some(on_open)) };
+        }
+        *opening += 1;
+        Opening
+    }
+}
+
+impl Drop for Opening {
+    fn drop(&mut self) {
+        let mut opening = OPENING.lock().unwrap_or_else(|e| e.into_inner());
+        *opening -= 1;
+        if *opening == 0 {
+            unsafe { ffi::sqlite3_cancel_auto_extension(Some(on_open)) };
+        }
+    }
+}
+
+/// Whether the main database of `raw_conn` is the one at `path`, which may be `:memory:`.
+unsafe fn opens(raw_conn: *mut ffi::sqlite3, path: &str) -> bool {
+    let file = ffi::sqlite3_db_filename(raw_conn, c"main".as_ptr());
+    // Empty for in-memory and temporary databases.
+    let file = if file.is_null() {
+        ""
+    } else {
+        match CStr::from_ptr(file).to_str() {
+            Ok(file) => file,
+            Err(_) => return false,
+        }
+    };
+    if path.is_empty() || path == ":memory:" {
+        return file.is_empty();
+    }
+    !file.is_empty()
+        && match (
+            Path::new(path).canonicalize(),
+            Path::new(file).canonicalize(),
+        ) {
+            (Ok(path), Ok(file)) => path == file,
+            _ => false,
+        }
+}
+
+/// Remembers the path the database behind `handle` was opened with, for `connect` to find its
+/// connections. Remote databases have none.
+pub fn track(handle: *const libsql::Database, path: &str) {
+    if let Ok(db) = resolve(handle) {
+        paths().insert(db as usize, path.to_string());
+    }
+}
+
+/// Must be called when the database is dropped, its address may be reused.
+pub fn forget(db: *const libsql::Database) {
+    paths().remove(&(db as usize));
+}
+
+/// SQLite connection of a `libsql::Connection`, valid as long as the connection lives.
+pub struct RawConnection(*mut ffi::sqlite3);
+
+// Only used by the calls made on its `libsql::Connection`, which is itself `Send`.
+unsafe impl Send for RawConnection {}
+
+/// Connects to `db`, returning the SQLite connection behind the new connection if it has one.
+/// It must be given to `into_connection_handle` along with the connection.
+pub fn connect(
+    db: &libsql::Database,
+) -> Result<(libsql::Connection, Option<RawConnection>), libsql::Error> {
+    let Some(path) = paths().get(&(db as *const _ as usize)).cloned() else {
+        return Ok((db.connect()?, None));
+    };
+    let opening = Opening::start();
+    OPENED.set(Some(Vec::new()));
+    let conn = db.connect();
+    let opened = OPENED.take().unwrap_or_default();
+    drop(opening);
+    let conn = conn?;
+    let mut found = opened
+        .into_iter()
+        .map(|raw_conn| raw_conn as *mut ffi::sqlite3)
+        .filter(|raw_conn| unsafe { opens(*raw_conn, &path) });
+    let raw_conn = match (found.next(), found.next()) {
+        (Some(raw_conn), None) => Some(RawConnection(raw_conn)),
+        (None, _) => None,
+        (Some(_), Some(_)) => {
+            tracing::warn!("Several SQLite connections opened to {path}, none is used");
+            None
+        }
+    };
+    Ok((conn, raw_conn))
+}
+
+/// Boxes `conn` into a handle, keeping its SQLite connection.
+pub fn into_connection_handle(
+    conn: libsql::Connection,
+    raw_conn: Option<RawConnection>,
+) -> *const libsql::Connection {
+    let handle = into_handle(conn);
+    if let (Some(RawConnection(raw_conn)), Ok(conn)) = (raw_conn, resolve(handle)) {
+        raw().insert(conn as usize, raw_conn as usize);
+    }
+    handle
+}
+
+/// Must be called when the connection is dropped or moved, its address may be reused. The hooks
+/// set on the connection and the functions and collations registered on it are removed.
+pub fn detach(conn: *const libsql::Connection) -> Option<RawConnection> {
+    let raw_conn = raw().remove(&(conn as usize))? as *mut ffi::sqlite3;
+    unsafe {
+        hooks::detach(conn, raw_conn);
+        functions::detach(conn, raw_conn);
+        collations::detach(conn, raw_conn);
+    }
+    Some(RawConnection(raw_conn))
+}
+
+/// Error of a failed call on `raw_conn` which returned `code`.
+pub fn sqlite_error(raw_conn: *mut ffi::sqlite3, code: c_int, context: &str) -> libsql_error {
+    // Calls failing on a misuse leave the message of the previous error in place.
+    let (extended_code, message) = unsafe {
+        match ffi::sqlite3_extended_errcode(raw_conn) {
+            extended_code if extended_code & 0xff == code & 0xff => {
+                (extended_code, ffi::sqlite3_errmsg(raw_conn))
+            }
+            _ => (code, ffi::sqlite3_errstr(code)),
+        }
+    };
+    let message = unsafe { CStr::from_ptr(message) };
+    libsql_error::with_code(
+        LIBSQL_ERR_SQLITE,
+        extended_code,
+        format!("{context}: {}", message.to_string_lossy()),
+    )
+}
+
+/// SQLite connection behind `conn` along with the connection owning it, `conn` itself unless it
+/// was borrowed from a transaction. Only local and replica connections have one.
+pub fn raw_connection(
+    conn: *const libsql::Connection,
+) -> Result<(*const libsql::Connection, *mut ffi::sqlite3), libsql_error> {
+    let owner = children::owner(conn);
+    match raw().get(&(owner as usize)) {
+        Some(raw_conn) => Ok((owner, *raw_conn as *mut ffi::sqlite3)),
+        None => Err(libsql_error::new(
+            LIBSQL_ERR_NOT_SUPPORTED,
+            "Only available on connections to local databases and replicas",
+        )),
+    }
+}
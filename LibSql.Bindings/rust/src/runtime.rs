@@ -0,0 +1,233 @@
+// The tokio runtime every call runs on. It starts on first use with the default settings
+// unless `libsql_runtime_config` was called before. The runtime itself is owned by a driver
+// thread, which keeps a current-thread runtime going between calls, and the rest of the crate
+// only holds its `Handle` (see `RT`). Entry points go through `ensure_running` first, which is
+// where the runtime starts and where calls made after it was shut down are refused.
+
+use std::{
+    ffi::{c_int, CStr},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use tokio::{
+    runtime::{Builder, Handle, Runtime},
+    sync::oneshot,
+};
+
+use crate::error::{libsql_error, set_err};
+use crate::guard::catch_panic;
+use crate::types::{read_config, LibSqlRuntimeConfig, LIBSQL_ERR_MISUSE, LIBSQL_ERR_UNKNOWN};
+
+const DEFAULT_THREAD_NAME: &str = "libsql";
+
+#[derive(Default)]
+struct State {
+    // Built by `libsql_runtime_config`, waiting for the first use.
+    configured: Option<(Runtime, String)>,
+    driver: Option<Driver>,
+    started: bool,
+}
+
+struct Driver {
+    handle: Handle,
+    shutdown: oneshot::Sender<Duration>,
+    thread: JoinHandle<()>,
+}
+
+// Fast path of `ensure_running`, set once the runtime started and cleared when it's shut down.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State::default());
+}
+
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn build(config: &LibSqlRuntimeConfig, name: &str) -> std::io::Result<Runtime> {
+    let mut builder = if config.current_thread != 0 {
+        Builder::new_current_thread()
+    } else {
+        Builder::new_multi_thread()
+    };
+    if config.worker_threads > 0 {
+        builder.worker_threads(config.worker_threads as usize);
+    }
+    if config.max_blocking_threads > 0 {
+        builder.max_blocking_threads(config.max_blocking_threads as usize);
+    }
+    let name = name.to_string();
+    let next_id = AtomicUsize::new(0);
+    builder
+        .thread_name_fn(move || format!("{name}-{}", next_id.fetch_add(1, Ordering::Relaxed)))
+        .enable_all()
+        .build()
+}
+
+fn start(state: &mut State) -> Result<Handle, libsql_error> {
+    let (runtime, name) = match state.configured.take() {
+        Some(configured) => configured,
+        None => {
+            let config = LibSqlRuntimeConfig {
+                struct_size: std::mem::size_of::<LibSqlRuntimeConfig>(),
+                worker_threads: 0,
+                max_blocking_threads: 0,
+                thread_name_prefix: std::ptr::null(),
+                current_thread: 0,
+            };
+            let runtime = build(&config, DEFAULT_THREAD_NAME).map_err(start_error)?;
+            (runtime, DEFAULT_THREAD_NAME.to_string())
+        }
+    };
+    let handle = runtime.handle().clone();
+    let (shutdown, shutdown_requested) = oneshot::channel();
+    let thread = std::thread::Builder::new()
+        .name(format!("{name}-driver"))
+        .spawn(move || {
+            let timeout =
+                runtime.block_on(async { shutdown_requested.await.unwrap_or(Duration::ZERO) });
+            runtime.shutdown_timeout(timeout);
+        })
+        .map_err(start_error)?;
+    tracing::debug!(name, "Started the runtime");
+    state.driver = Some(Driver {
+        handle: handle.clone(),
+        shutdown,
+        thread,
+    });
+    state.started = true;
+    RUNNING.store(true, Ordering::Release);
+    Ok(handle)
+}
+
+fn start_error(e: std::io::Error) -> libsql_error {
+    libsql_error::new(
+        LIBSQL_ERR_UNKNOWN,
+        format!("Error starting the runtime: {e}"),
+    )
+}
+
+/// Returns the handle of the runtime, starting it on first use. Fails with `LIBSQL_ERR_MISUSE`
+/// once it was shut down, entry points check this (see `ensure_running`) before anything
+/// touches `RT`.
+pub fn running() -> Result<Handle, libsql_error> {
+    let mut state = state();
+    if let Some(driver) = &state.driver {
+        return Ok(driver.handle.clone());
+    }
+    if state.started {
+        return Err(libsql_error::new(
+            LIBSQL_ERR_MISUSE,
+            "The runtime was shut down",
+        ));
+    }
+    start(&mut state)
+}
+
+/// `running` for the entry points, which only need to know the runtime can be used.
+pub fn ensure_running() -> Result<(), libsql_error> {
+    if RUNNING.load(Ordering::Acquire) {
+        return Ok(());
+    }
+    running().map(drop)
+}
+
+/// Must be called before anything else in the library, fails once the runtime started.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_runtime_config(
+    config: *const LibSqlRuntimeConfig,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    catch_panic(out_err, || {
+        let config = match read_config(config) {
+            Ok(config) => config,
+            Err(e) => return set_err(e, out_err),
+        };
+        if config.worker_threads < 0 || config.max_blocking_threads < 0 {
+            return set_err(
+                libsql_error::invalid_argument("Thread counts can't be negative"),
+                out_err,
+            );
+        }
+        let name = if config.thread_name_prefix.is_null() {
+            DEFAULT_THREAD_NAME
+        } else {
+            match unsafe { CStr::from_ptr(config.thread_name_prefix) }.to_str() {
+                Ok(name) => name,
+                Err(e) => {
+                    return set_err(
+                        libsql_error::invalid_argument(format!("Wrong thread name prefix: {e}")),
+                        out_err,
+                    );
+                }
+            }
+        };
+        let mut state = state();
+        if state.started {
+            let message = if state.driver.is_some() {
+                "The runtime is already running"
+            } else {
+                "The runtime was shut down"
+            };
+            return set_err(libsql_error::new(LIBSQL_ERR_MISUSE, message), out_err);
+        }
+        match build(&config, name) {
+            Ok(runtime) => {
+                // Replacing a previous configuration drops its runtime.
+                state.configured = Some((runtime, name.to_string()));
+                0
+            }
+            Err(e) => set_err(
+                libsql_error::new(
+                    LIBSQL_ERR_UNKNOWN,
+                    format!("Error building the runtime: {e}"),
+                ),
+                out_err,
+            ),
+        }
+    })
+}
+
+/// Stops the runtime waiting up to `timeout_ms` for its blocking tasks. Pending async calls are
+/// called back with a `LIBSQL_ERR_MISUSE` error and later calls fail with it, only freeing
+/// handles still works.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_shutdown_runtime(
+    timeout_ms: std::ffi::c_ulonglong,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    catch_panic(out_err, || {
+        let mut state = state();
+        state.configured = None;
+        if !state.started {
+            // Nothing to stop, but make sure it won't start later on.
+            state.started = true;
+            return 0;
+        }
+        let Some(driver) = state.driver.take() else {
+            return set_err(
+                libsql_error::new(LIBSQL_ERR_MISUSE, "The runtime was already shut down"),
+                out_err,
+            );
+        };
+        RUNNING.store(false, Ordering::Release);
+        drop(state);
+        let _ = driver.shutdown.send(Duration::from_millis(timeout_ms));
+        if driver.thread.join().is_err() {
+            return set_err(
+                libsql_error::new(
+                    LIBSQL_ERR_UNKNOWN,
+                    "The runtime panicked while shutting down",
+                ),
+                out_err,
+            );
+        }
+        0
+    })
+}
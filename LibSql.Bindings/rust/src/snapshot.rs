@@ -0,0 +1,61 @@
+// Seeds a replica from a snapshot instead of pulling the whole database on its first sync. A
+// snapshot is a copy of a closed replica: its database file together with the replication
+// metadata libsql keeps next to it, from which the replica resumes syncing at the frame number
+// it had reached.
+
+use std::{fs, io, path::Path};
+
+use crate::error::libsql_error;
+use crate::types::LIBSQL_ERR_UNKNOWN;
+
+/// Replication metadata of the v1 and v2 sync protocols.
+const METADATA: [&str; 2] = ["-client_wal_index", "-info"];
+
+fn with_suffix(path: &str, suffix: &str) -> String {
+    format!("{path}{suffix}")
+}
+
+fn copy_error(from: &str, to: &str, e: io::Error) -> libsql_error {
+    libsql_error::new(
+        LIBSQL_ERR_UNKNOWN,
+        format!("Error copying snapshot {from} to {to}: {e}"),
+    )
+}
+
+/// Copies `snapshot` and its metadata to `db_path`, unless a database is already there. Returns
+/// whether the replica was seeded.
+pub fn seed(snapshot: &str, db_path: &str) -> Result<bool, libsql_error> {
+    if Path::new(db_path).exists() {
+        tracing::debug!("Not seeding {db_path} from {snapshot}, the database exists");
+        return Ok(false);
+    }
+    if !Path::new(snapshot).is_file() {
+        return Err(libsql_error::invalid_argument(format!(
+            "Snapshot {snapshot} doesn't exist"
+        )));
+    }
+    let metadata: Vec<_> = METADATA
+        .iter()
+        .filter(|suffix| Path::new(&with_suffix(snapshot, suffix)).is_file())
+        .collect();
+    if metadata.is_empty() {
+        return Err(libsql_error::invalid_argument(format!(
+            "Snapshot {snapshot} has no replication metadata ({snapshot}-client_wal_index or \
+             {snapshot}-info), it must be the copy of a replica"
+        )));
+    }
+
+    // The database file comes last, libsql trusts metadata found next to an existing database.
+    let wal = Path::new(&with_suffix(snapshot, "-wal"))
+        .is_file()
+        .then_some(&"-wal");
+    for suffix in metadata.into_iter().chain(wal) {
+        let (from, to) = (with_suffix(snapshot, suffix), with_suffix(db_path, suffix));
+        fs::copy(&from, &to).map_err(|e| copy_error(&from, &to, e))?;
+    }
+    let partial = with_suffix(db_path, "-snapshot");
+    fs::copy(snapshot, &partial).map_err(|e| copy_error(snapshot, &partial, e))?;
+    fs::rename(&partial, db_path).map_err(|e| copy_error(&partial, db_path, e))?;
+    tracing::info!("Seeded {db_path} from snapshot {snapshot}");
+    Ok(true)
+}
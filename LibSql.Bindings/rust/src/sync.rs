@@ -0,0 +1,358 @@
+// Sync tracking of remote replicas and synced databases. Their connections count the bytes they
+// receive, which is the progress of a sync while libsql applies the frames, and the periodic
+// sync of `sync_interval` runs here rather than in libsql so its outcome can be reported by
+// `libsql_sync_status` and to the callback of `libsql_set_sync_callback`. Unlike libsql's, it
+// syncs right away and then waits the interval after each sync ends rather than between starts.
+
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_int, c_void, CString},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::task::JoinHandle;
+
+use crate::cancel::{cancellable, get_cancel_token, libsql_cancel_token};
+use crate::completion::{Empty, SendPtr};
+use crate::error::{libsql_error, set_err};
+use crate::functions::Function;
+use crate::guard::guard;
+use crate::handles::resolve;
+use crate::types::{
+    libsql_callback, libsql_destroy_callback, libsql_sync_progress, libsql_sync_progress_callback,
+    libsql_sync_status, replicated, LIBSQL_ERR_MISUSE, LIBSQL_ERR_NOT_SUPPORTED,
+};
+use crate::{get_ref, RT};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Default)]
+struct State {
+    last_success_ms: u64,
+    last_error_ms: u64,
+    last_error: Option<String>,
+    periodic: Option<(JoinHandle<()>, Arc<Handover>)>,
+}
+
+/// Shared by the periodic sync, which borrows the database, and `untrack`. Whichever lets go of
+/// it last drops the database handed over by `untrack`, so it outlives the aborted task.
+#[derive(Default)]
+struct Handover(Mutex<Option<Box<libsql::Database>>>);
+
+type Notify = Function<libsql_callback<replicated>>;
+
+pub struct Tracker {
+    received: Arc<AtomicU64>,
+    interval: Option<Duration>,
+    running: AtomicUsize,
+    state: Mutex<State>,
+    // Cloned to be called, a replaced callback is destroyed once it's done running.
+    notify: Mutex<Option<Arc<Notify>>>,
+}
+
+impl Tracker {
+    pub fn new(interval: Option<Duration>) -> Arc<Tracker> {
+        Arc::new(Tracker {
+            received: Arc::default(),
+            interval,
+            running: AtomicUsize::new(0),
+            state: Mutex::default(),
+            notify: Mutex::default(),
+        })
+    }
+
+    /// Counter to give to the connector of the database.
+    pub fn received(&self) -> Arc<AtomicU64> {
+        self.received.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn notify(&self) -> MutexGuard<'_, Option<Arc<Notify>>> {
+        self.notify.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Hands the outcome of a periodic sync to the callback of the host, if any.
+    fn notify_sync(&self, result: Result<libsql::replication::Replicated, libsql_error>) {
+        // Not under the lock, the callback may replace itself.
+        let Some(notify) = self.notify().clone() else {
+            return;
+        };
+        let (value, err) = match result {
+            Ok(replicated) => (replicated.into(), std::ptr::null()),
+            Err(e) => (replicated::empty(), e.into_raw()),
+        };
+        unsafe { (notify.callbacks)(notify.user_data(), value, err) };
+    }
+}
+
+lazy_static! {
+    static ref TRACKERS: Mutex<HashMap<usize, Arc<Tracker>>> = Mutex::default();
+}
+
+fn trackers() -> MutexGuard<'static, HashMap<usize, Arc<Tracker>>> {
+    TRACKERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn tracker(db: &libsql::Database) -> Option<Arc<Tracker>> {
+    trackers().get(&(db as *const _ as usize)).cloned()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Tracks the database behind `handle` and starts its periodic sync.
+pub fn track(handle: *const libsql::Database, tracker: Arc<Tracker>) {
+    let Ok(db) = resolve(handle) else {
+        return;
+    };
+    if let Some(interval) = tracker.interval {
+        let ptr = SendPtr::new(db);
+        let tracked = tracker.clone();
+        let handover = Arc::<Handover>::default();
+        let owner = handover.clone();
+        let periodic = RT.spawn(async move {
+            // Dropped after the sync borrowing the database, locals going in reverse order.
+            let _owner = owner;
+            loop {
+                // Not borrowed while the callback runs, which may free the database.
+                let result =
+                    tracked_sync(unsafe { get_ref(ptr.get()) }, Some(&tracked), None).await;
+                tracked.notify_sync(result);
+                tokio::time::sleep(interval).await;
+            }
+        });
+        tracker.state().periodic = Some((periodic, handover));
+    }
+    trackers().insert(db as usize, tracker);
+}
+
+/// Stops tracking `db` and drops it. Its periodic sync is aborted without waiting for it, the
+/// database is dropped along with the task if it still borrows it.
+pub fn untrack(db: Box<libsql::Database>) {
+    let periodic = trackers()
+        .remove(&(&*db as *const _ as usize))
+        .and_then(|tracker| tracker.state().periodic.take());
+    let Some((periodic, handover)) = periodic else {
+        return;
+    };
+    periodic.abort();
+    *handover.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(db);
+}
+
+pub struct Progress {
+    callback: libsql_sync_progress_callback,
+    user_data: *mut c_void,
+}
+
+// The host is told the callback runs on a runtime worker thread.
+unsafe impl Send for Progress {}
+
+impl Progress {
+    fn report(&self, bytes: u64, frames: u64, done: bool) {
+        let progress = libsql_sync_progress {
+            bytes,
+            frames,
+            done: done as c_char,
+        };
+        unsafe { (self.callback)(self.user_data, &progress) }
+    }
+}
+
+/// Counts a running sync until dropped, cancelled ones included.
+struct Running<'a>(&'a Tracker);
+
+impl<'a> Running<'a> {
+    fn new(tracker: &'a Tracker) -> Self {
+        tracker.running.fetch_add(1, Ordering::Relaxed);
+        Running(tracker)
+    }
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.running.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Syncs `db`, reporting the progress if asked and keeping the outcome in its tracker.
+pub async fn sync(
+    db: &libsql::Database,
+    progress: Option<Progress>,
+) -> Result<libsql::replication::Replicated, libsql_error> {
+    tracked_sync(db, tracker(db).as_deref(), progress).await
+}
+
+async fn tracked_sync(
+    db: &libsql::Database,
+    tracker: Option<&Tracker>,
+    progress: Option<Progress>,
+) -> Result<libsql::replication::Replicated, libsql_error> {
+    let received = tracker.map(|tracker| tracker.received());
+    let start = received.as_ref().map_or(0, |r| r.load(Ordering::Relaxed));
+    let bytes = || {
+        received
+            .as_ref()
+            .map_or(0, |r| r.load(Ordering::Relaxed) - start)
+    };
+    let _running = tracker.map(Running::new);
+
+    let syncing = db.sync();
+    tokio::pin!(syncing);
+    let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+    let mut reported = 0;
+    let result = loop {
+        tokio::select! {
+            result = &mut syncing => break result,
+            _ = ticker.tick(), if progress.is_some() && received.is_some() => {
+                let bytes = bytes();
+                if bytes != reported {
+                    reported = bytes;
+                    if let Some(progress) = &progress {
+                        progress.report(bytes, 0, false);
+                    }
+                }
+            }
+        }
+    };
+
+    if let Some(tracker) = tracker {
+        let mut state = tracker.state();
+        match &result {
+            Ok(_) => state.last_success_ms = now_ms(),
+            Err(e) => {
+                state.last_error_ms = now_ms();
+                state.last_error = Some(e.to_string());
+            }
+        }
+    }
+    let replicated = result.map_err(|e| libsql_error::from_libsql("Error syncing database", e))?;
+    if let Some(progress) = &progress {
+        progress.report(bytes(), replicated.frames_synced() as u64, true);
+    }
+    Ok(replicated)
+}
+
+/// Same as `libsql_sync`, calling `callback` as the bytes come in and once done.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_sync_with_progress(
+    db: *const libsql::Database,
+    cancel_token: *const libsql_cancel_token,
+    callback: Option<libsql_sync_progress_callback>,
+    user_data: *mut c_void,
+    out_replicated: *mut replicated,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    guard(db, out_err, |db| {
+        let db = get_ref(db);
+        let Some(callback) = callback else {
+            return set_err(libsql_error::invalid_argument("Missing callback"), out_err);
+        };
+        let token = match get_cancel_token(cancel_token) {
+            Ok(token) => token,
+            Err(e) => return set_err(e, out_err),
+        };
+        let progress = Progress {
+            callback,
+            user_data,
+        };
+        match RT.block_on(cancellable(token, None, sync(db, Some(progress)))) {
+            Ok(replicated) => {
+                if !out_replicated.is_null() {
+                    *out_replicated = replicated.into();
+                }
+                0
+            }
+            Err(e) => set_err(e, out_err),
+        }
+    })
+}
+
+/// Calls `callback` after each periodic sync with its result or its error, from a runtime worker
+/// thread. A null `callback` removes it, it may be replaced from within itself. `destroy` is
+/// called with `user_data` once the callback is replaced or removed and done running, the
+/// database is freed, or setting it fails. The database must have a sync interval, otherwise it
+/// fails with `LIBSQL_ERR_MISUSE`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_set_sync_callback(
+    db: *const libsql::Database,
+    callback: Option<libsql_callback<replicated>>,
+    user_data: *mut c_void,
+    destroy: Option<libsql_destroy_callback>,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    // Dropped on failures, destroying the user data.
+    let notify = callback.map(|callback| Notify {
+        callbacks: callback,
+        user_data: user_data as usize,
+        destroy,
+    });
+    guard(db, out_err, |db| {
+        let Some(tracker) = tracker(get_ref(db)) else {
+            return set_err(not_tracked(), out_err);
+        };
+        if notify.is_some() && tracker.interval.is_none() {
+            return set_err(
+                libsql_error::new(LIBSQL_ERR_MISUSE, "The database has no periodic sync"),
+                out_err,
+            );
+        }
+        let replaced = std::mem::replace(&mut *tracker.notify(), notify.map(Arc::new));
+        // Destroyed here unless still running.
+        drop(replaced);
+        0
+    })
+}
+
+fn not_tracked() -> libsql_error {
+    libsql_error::new(
+        LIBSQL_ERR_NOT_SUPPORTED,
+        "Syncs are only tracked for remote replicas and synced databases",
+    )
+}
+
+/// Only remote replicas and synced databases opened with a config or a builder are tracked,
+/// others fail with `LIBSQL_ERR_NOT_SUPPORTED`.
+#[no_mangle]
+pub unsafe extern "C" fn libsql_sync_status(
+    db: *const libsql::Database,
+    out_status: *mut libsql_sync_status,
+    out_err: *mut *const libsql_error,
+) -> c_int {
+    guard(db, out_err, |db| {
+        if out_status.is_null() {
+            return set_err(libsql_error::invalid_argument("Missing status"), out_err);
+        }
+        let Some(tracker) = tracker(get_ref(db)) else {
+            return set_err(not_tracked(), out_err);
+        };
+        let state = tracker.state();
+        let last_error = match &state.last_error {
+            Some(message) => CString::new(message.replace('\0', ""))
+                .unwrap_or_default()
+                .into_raw() as *const _,
+            None => std::ptr::null(),
+        };
+        *out_status = libsql_sync_status {
+            periodic: state
+                .periodic
+                .as_ref()
+                .is_some_and(|(periodic, _)| !periodic.is_finished())
+                as c_char,
+            syncing: (tracker.running.load(Ordering::Relaxed) > 0) as c_char,
+            last_success_ms: state.last_success_ms,
+            last_error_ms: state.last_error_ms,
+            last_error,
+        };
+        0
+    })
+}
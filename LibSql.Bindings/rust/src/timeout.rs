@@ -0,0 +1,152 @@
+// Deadlines for calls. Each connection may have a default timeout applied to the statements it
+// runs, and the `*_timeout` variants take one per call. Like cancellation, remote operations
+// are aborted by dropping their future while local statements get interrupted, in both cases
+// the call fails with `LIBSQL_ERR_TIMEOUT`. Local statements are interrupted by one watchdog
+// thread shared by every call.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, Once,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::error::libsql_error;
+use crate::types::LIBSQL_ERR_TIMEOUT;
+
+/// Deadline of a call and the id telling apart calls with the same one.
+type Deadline = (Instant, u64);
+
+#[derive(Default)]
+struct Watches {
+    next_id: u64,
+    /// Connections to interrupt at their deadline and the flag to set when doing so.
+    pending: BTreeMap<Deadline, (libsql::Connection, Arc<AtomicBool>)>,
+}
+
+lazy_static! {
+    // Keyed by the address of the connection, not by its handle.
+    static ref DEFAULT_TIMEOUTS: Mutex<HashMap<usize, Duration>> = Mutex::new(HashMap::new());
+    static ref WATCHES: Mutex<Watches> = Mutex::default();
+    // Signaled when a watch is added, its deadline may be the closest one.
+    static ref WATCHED: Condvar = Condvar::new();
+}
+
+fn watches() -> MutexGuard<'static, Watches> {
+    WATCHES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn watchdog() {
+    let mut watches = watches();
+    loop {
+        let Some((&deadline, _)) = watches.pending.first_key_value() else {
+            watches = WATCHED.wait(watches).unwrap_or_else(|e| e.into_inner());
+            continue;
+        };
+        let left = deadline.0.saturating_duration_since(Instant::now());
+        if !left.is_zero() {
+            watches = WATCHED
+                .wait_timeout(watches, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+            continue;
+        }
+        if let Some((conn, expired)) = watches.pending.remove(&deadline) {
+            expired.store(true, Ordering::SeqCst);
+            // Under the lock, so a call that finished and removed its watch is never interrupted.
+            let _ = conn.interrupt();
+        }
+    }
+}
+
+/// Interrupts its connection at its deadline unless dropped first.
+struct Watch(Deadline);
+
+impl Watch {
+    fn start(timeout: Duration, conn: &libsql::Connection, expired: Arc<AtomicBool>) -> Watch {
+        static START: Once = Once::new();
+        START.call_once(|| {
+            let started = std::thread::Builder::new()
+                .name("libsql-timeout".to_string())
+                .spawn(watchdog);
+            if let Err(e) = started {
+                tracing::warn!(
+                    "Unable to start the timeout thread, statements won't be interrupted: {e}"
+                );
+            }
+        });
+        let mut watches = watches();
+        let deadline = (Instant::now() + timeout, watches.next_id);
+        watches.next_id += 1;
+        watches.pending.insert(deadline, (conn.clone(), expired));
+        drop(watches);
+        WATCHED.notify_one();
+        Watch(deadline)
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        watches().pending.remove(&self.0);
+    }
+}
+
+fn default_timeouts() -> MutexGuard<'static, HashMap<usize, Duration>> {
+    DEFAULT_TIMEOUTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn set_default_timeout(conn: *const libsql::Connection, timeout_ms: u64) {
+    if timeout_ms == 0 {
+        default_timeouts().remove(&(conn as usize));
+    } else {
+        default_timeouts().insert(conn as usize, Duration::from_millis(timeout_ms));
+    }
+}
+
+/// Must be called when the connection is dropped, its address may be reused.
+pub fn clear_default_timeout(conn: *const libsql::Connection) {
+    default_timeouts().remove(&(conn as usize));
+}
+
+/// Timeout of a call on `conn`, `timeout_ms` is the one given to the call or 0 to use the
+/// connection default.
+pub fn call_timeout(conn: *const libsql::Connection, timeout_ms: u64) -> Option<Duration> {
+    match timeout_ms {
+        0 => default_timeouts().get(&(conn as usize)).copied(),
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+fn timeout_error(timeout: Duration) -> libsql_error {
+    libsql_error::new(
+        LIBSQL_ERR_TIMEOUT,
+        format!("Operation timed out after {}ms", timeout.as_millis()),
+    )
+}
+
+/// Runs `fut` failing once `timeout` elapses. `conn` is the connection the operation runs on,
+/// if any, so local statements can be interrupted.
+pub async fn with_timeout<R>(
+    timeout: Option<Duration>,
+    conn: Option<&libsql::Connection>,
+    fut: impl Future<Output = Result<R, libsql_error>>,
+) -> Result<R, libsql_error> {
+    let Some(timeout) = timeout else {
+        return fut.await;
+    };
+    // A local statement blocks the worker polling it, which may also be the only one able to
+    // run a spawned task, so it's interrupted from the watchdog thread instead.
+    let expired = Arc::new(AtomicBool::new(false));
+    let watch = conn.map(|conn| Watch::start(timeout, conn, expired.clone()));
+    let result = tokio::time::timeout(timeout, fut).await;
+    drop(watch);
+    match result {
+        Err(_) => Err(timeout_error(timeout)),
+        // An interrupted statement fails with SQLITE_INTERRUPT, report the timeout.
+        Ok(Err(_)) if expired.load(Ordering::SeqCst) => Err(timeout_error(timeout)),
+        Ok(result) => result,
+    }
+}
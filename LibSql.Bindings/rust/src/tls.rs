@@ -0,0 +1,140 @@
+// TLS settings of the connections to a remote server. Without any of them libsql picks its own
+// connector, otherwise `connector::connector` builds a `hyper_rustls` one from the chosen trust
+// roots, client certificate and HTTP versions.
+
+use std::sync::Arc;
+
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+
+use crate::error::libsql_error;
+
+#[derive(Default)]
+pub struct TlsOptions {
+    pub with_webpki: bool,
+    pub native_roots: bool,
+    root_certs: Vec<CertificateDer<'static>>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    pub http2: bool,
+    pub skip_verify: bool,
+}
+
+fn parse_certs(pem: &[u8], what: &str) -> Result<Vec<CertificateDer<'static>>, libsql_error> {
+    let certs = rustls_pemfile::certs(&mut &*pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| libsql_error::invalid_argument(format!("Wrong {what}: {e}")))?;
+    if certs.is_empty() {
+        return Err(libsql_error::invalid_argument(format!(
+            "No certificate found in the {what}"
+        )));
+    }
+    Ok(certs)
+}
+
+impl TlsOptions {
+    /// Adds the PEM encoded certificates of `pem` to the trusted roots.
+    pub fn add_root_certs(&mut self, pem: &[u8]) -> Result<(), libsql_error> {
+        let certs = parse_certs(pem, "root certificates")?;
+        self.root_certs.extend(certs);
+        Ok(())
+    }
+
+    /// Authenticates with the PEM encoded certificate chain and private key.
+    pub fn set_client_auth(&mut self, cert_pem: &[u8], key_pem: &[u8]) -> Result<(), libsql_error> {
+        let certs = parse_certs(cert_pem, "client certificate")?;
+        let key = rustls_pemfile::private_key(&mut &*key_pem)
+            .map_err(|e| libsql_error::invalid_argument(format!("Wrong client key: {e}")))?
+            .ok_or_else(|| {
+                libsql_error::invalid_argument("No private key found in the client key")
+            })?;
+        self.client_auth = Some((certs, key));
+        Ok(())
+    }
+
+    pub fn is_default(&self) -> bool {
+        !self.with_webpki
+            && !self.native_roots
+            && self.root_certs.is_empty()
+            && self.client_auth.is_none()
+            && !self.http2
+            && !self.skip_verify
+    }
+
+    pub fn client_config(&self) -> Result<ClientConfig, libsql_error> {
+        let builder = if self.skip_verify {
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerification))
+        } else {
+            let mut roots = RootCertStore::empty();
+            if self.with_webpki {
+                roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            }
+            // Only picking a client certificate or HTTP/2 keeps the usual trust store.
+            if self.native_roots || (!self.with_webpki && self.root_certs.is_empty()) {
+                let certs = rustls_native_certs::load_native_certs().map_err(|e| {
+                    libsql_error::invalid_argument(format!("Error loading the native roots: {e}"))
+                })?;
+                roots.add_parsable_certificates(certs);
+            }
+            for cert in &self.root_certs {
+                roots.add(cert.clone()).map_err(|e| {
+                    libsql_error::invalid_argument(format!("Wrong root certificate: {e}"))
+                })?;
+            }
+            ClientConfig::builder().with_root_certificates(roots)
+        };
+        match &self.client_auth {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs.clone(), key.clone_key())
+                .map_err(|e| {
+                    libsql_error::invalid_argument(format!("Wrong client certificate: {e}"))
+                }),
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+}
+
+/// Accepts any server certificate, only meant for local test servers.
+#[derive(Debug)]
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
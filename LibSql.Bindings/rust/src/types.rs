@@ -9,16 +9,200 @@ pub const LIBSQL_TRANSACTION_IMMEDIATE: i8 = 2;
 pub const LIBSQL_TRANSACTION_EXCLUSIVE: i8 = 3;
 pub const LIBSQL_TRANSACTION_READONLY: i8 = 4;
 
+/// Flags of `LibSqlLocalConfig`, same values as their `SQLITE_OPEN_*` counterparts.
+pub const LIBSQL_OPEN_READ_ONLY: std::ffi::c_int = 0x1;
+pub const LIBSQL_OPEN_READ_WRITE: std::ffi::c_int = 0x2;
+pub const LIBSQL_OPEN_CREATE: std::ffi::c_int = 0x4;
+pub const LIBSQL_OPEN_NO_MUTEX: std::ffi::c_int = 0x8000;
+
+pub const LIBSQL_LOG_TRACE: std::ffi::c_int = 0;
+pub const LIBSQL_LOG_DEBUG: std::ffi::c_int = 1;
+pub const LIBSQL_LOG_INFO: std::ffi::c_int = 2;
+pub const LIBSQL_LOG_WARN: std::ffi::c_int = 3;
+pub const LIBSQL_LOG_ERROR: std::ffi::c_int = 4;
+
+pub const LIBSQL_ERR_UNKNOWN: std::ffi::c_int = 1;
+pub const LIBSQL_ERR_INVALID_ARGUMENT: std::ffi::c_int = 2;
+pub const LIBSQL_ERR_SQLITE: std::ffi::c_int = 3;
+pub const LIBSQL_ERR_CONNECTION: std::ffi::c_int = 4;
+pub const LIBSQL_ERR_SYNC: std::ffi::c_int = 5;
+pub const LIBSQL_ERR_NOT_SUPPORTED: std::ffi::c_int = 6;
+pub const LIBSQL_ERR_MISUSE: std::ffi::c_int = 7;
+pub const LIBSQL_ERR_TYPE_MISMATCH: std::ffi::c_int = 8;
+pub const LIBSQL_ERR_OUT_OF_RANGE: std::ffi::c_int = 9;
+pub const LIBSQL_ERR_PANIC: std::ffi::c_int = 10;
+pub const LIBSQL_ERR_POISONED: std::ffi::c_int = 11;
+pub const LIBSQL_ERR_INVALID_HANDLE: std::ffi::c_int = 12;
+pub const LIBSQL_ERR_CANCELLED: std::ffi::c_int = 13;
+pub const LIBSQL_ERR_TIMEOUT: std::ffi::c_int = 14;
+pub const LIBSQL_ERR_BUSY: std::ffi::c_int = 15;
+
+/// Modes of `libsql_close`. FAIL_IF_BUSY refuses to close a database that still has connections,
+/// pools, transactions, statements or rows open, WAIT blocks until they are freed, FORCE closes it
+/// anyway leaving them usable only to be freed, and DEFER invalidates the database handle at
+/// once but only drops the database with its last open handle.
+pub const LIBSQL_CLOSE_FAIL_IF_BUSY: std::ffi::c_int = 0;
+pub const LIBSQL_CLOSE_WAIT: std::ffi::c_int = 1;
+pub const LIBSQL_CLOSE_FORCE: std::ffi::c_int = 2;
+pub const LIBSQL_CLOSE_DEFER: std::ffi::c_int = 3;
+
+/// Operations reported by the update hook, with SQLite's values.
+pub const LIBSQL_UPDATE_DELETE: std::ffi::c_int = 9;
+pub const LIBSQL_UPDATE_INSERT: std::ffi::c_int = 18;
+pub const LIBSQL_UPDATE_UPDATE: std::ffi::c_int = 23;
+
+/// Decisions of the authorizer, with SQLite's values. DENY fails the statement being prepared,
+/// IGNORE reads the column as NULL or skips the operation, depending on the action.
+pub const LIBSQL_AUTH_ALLOW: std::ffi::c_int = 0;
+pub const LIBSQL_AUTH_DENY: std::ffi::c_int = 1;
+pub const LIBSQL_AUTH_IGNORE: std::ffi::c_int = 2;
+
+/// Cipher of an encryption key, given to the configs as a `c_int` where 0 picks the default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum libsql_cipher {
+    /// AES 256 bit CBC, takes a 32 bytes key.
+    Aes256Cbc = 1,
+}
+
+impl libsql_cipher {
+    pub fn from_raw(cipher: std::ffi::c_int) -> Option<Self> {
+        match cipher {
+            0 | 1 => Some(libsql_cipher::Aes256Cbc),
+            _ => None,
+        }
+    }
+
+    pub fn key_len(self) -> usize {
+        match self {
+            libsql_cipher::Aes256Cbc => 32,
+        }
+    }
+}
+
+/// Config structs start with `struct_size`, which the caller sets to the size of the struct it
+/// was built against. New fields are only ever appended, so an older caller gets them zeroed,
+/// which must mean the default, while a newer one is rejected if it sets any field unknown here.
+/// Implementors are `#[repr(C)]`, start with `struct_size: usize` and are valid when zeroed.
+pub unsafe trait Versioned: Sized {
+    /// Size of the first version of the struct, it never changes.
+    const MIN_SIZE: usize;
+}
+
+/// Reads a versioned config struct given by pointer.
+pub unsafe fn read_config<T: Versioned>(config: *const T) -> Result<T, crate::error::libsql_error> {
+    use crate::error::libsql_error;
+
+    if config.is_null() {
+        return Err(libsql_error::invalid_argument("Missing config"));
+    }
+    let size = *(config as *const usize);
+    if size < T::MIN_SIZE {
+        return Err(libsql_error::invalid_argument(format!(
+            "Config struct_size {size} is smaller than the first version ({})",
+            T::MIN_SIZE
+        )));
+    }
+    let known = std::mem::size_of::<T>();
+    if size > known {
+        let extra = std::slice::from_raw_parts((config as *const u8).add(known), size - known);
+        if extra.iter().any(|b| *b != 0) {
+            return Err(libsql_error::new(
+                LIBSQL_ERR_NOT_SUPPORTED,
+                "Config sets options unknown to this version of the library",
+            ));
+        }
+    }
+    let mut read = std::mem::MaybeUninit::<T>::zeroed();
+    std::ptr::copy_nonoverlapping(
+        config as *const u8,
+        read.as_mut_ptr() as *mut u8,
+        size.min(known),
+    );
+    Ok(read.assume_init())
+}
+
 #[derive(Clone, Debug)]
 #[repr(C)]
 pub struct LibSqlConfig {
+    pub struct_size: usize,
     pub db_path: *const std::ffi::c_char,
     pub primary_url: *const std::ffi::c_char,
     pub auth_token: *const std::ffi::c_char,
     pub read_your_writes: std::ffi::c_char,
     pub encryption_key: *const std::ffi::c_char,
+    pub encryption_key_len: std::ffi::c_int,
+    pub cipher: std::ffi::c_int,
     pub sync_interval: std::ffi::c_int,
     pub with_webpki: std::ffi::c_char,
+    /// `http://host:port` of an HTTP CONNECT proxy, null connects directly.
+    pub proxy_url: *const std::ffi::c_char,
+    /// `headers_len` extra "Name: value" headers sent with every request.
+    pub headers: *const *const std::ffi::c_char,
+    pub headers_len: std::ffi::c_int,
+    /// Copy of a replica to seed `db_path` with when it doesn't exist yet, null syncs from
+    /// scratch.
+    pub snapshot_path: *const std::ffi::c_char,
+}
+
+/// Settings of `libsql_open_local_with_config`. `flags` is a set of `LIBSQL_OPEN_*`, 0 opens
+/// for reading and writing creating the file if needed. In memory databases ignore `db_path`.
+/// A null `encryption_key` leaves the database unencrypted, otherwise it's a raw key of
+/// `encryption_key_len` bytes.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct LibSqlLocalConfig {
+    pub struct_size: usize,
+    pub db_path: *const std::ffi::c_char,
+    pub encryption_key: *const std::ffi::c_char,
+    pub encryption_key_len: std::ffi::c_int,
+    pub cipher: std::ffi::c_int,
+    pub flags: std::ffi::c_int,
+    pub in_memory: std::ffi::c_char,
+}
+
+/// Settings of `libsql_runtime_config`, zero/null fields keep the tokio defaults.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct LibSqlRuntimeConfig {
+    pub struct_size: usize,
+    pub worker_threads: std::ffi::c_int,
+    pub max_blocking_threads: std::ffi::c_int,
+    pub thread_name_prefix: *const std::ffi::c_char,
+    pub current_thread: std::ffi::c_char,
+}
+
+/// Settings of `libsql_pool_new`. `max_size` must be at least 1 and `min_size` connections are
+/// kept open. Zero timeouts never expire, a null `health_check` hands out idle connections
+/// without running a query on them first.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct LibSqlPoolConfig {
+    pub struct_size: usize,
+    pub min_size: std::ffi::c_int,
+    pub max_size: std::ffi::c_int,
+    /// Idle connections above `min_size` are disconnected after this long.
+    pub idle_timeout_ms: std::ffi::c_ulonglong,
+    /// Query run on an idle connection before handing it out, it's replaced if it fails.
+    pub health_check: *const std::ffi::c_char,
+    /// How long `libsql_pool_acquire` waits for a connection when `max_size` are acquired.
+    pub acquire_timeout_ms: std::ffi::c_ulonglong,
+}
+
+unsafe impl Versioned for LibSqlConfig {
+    const MIN_SIZE: usize = std::mem::offset_of!(LibSqlConfig, with_webpki) + 1;
+}
+
+unsafe impl Versioned for LibSqlLocalConfig {
+    const MIN_SIZE: usize = std::mem::offset_of!(LibSqlLocalConfig, in_memory) + 1;
+}
+
+unsafe impl Versioned for LibSqlPoolConfig {
+    const MIN_SIZE: usize = std::mem::size_of::<LibSqlPoolConfig>();
+}
+
+unsafe impl Versioned for LibSqlRuntimeConfig {
+    const MIN_SIZE: usize = std::mem::offset_of!(LibSqlRuntimeConfig, current_thread) + 1;
 }
 
 #[derive(Clone, Debug)]
@@ -30,7 +214,161 @@ pub struct blob {
 
 #[repr(C)]
 pub struct replicated {
-    pub frame_no: std::ffi::c_int,
-    pub frames_synced: std::ffi::c_int,
+    pub frame_no: std::ffi::c_ulonglong,
+    pub frames_synced: std::ffi::c_ulonglong,
+    /// 0 when the replica didn't receive any frame yet, `frame_no` is 0 then.
+    pub has_frame_no: std::ffi::c_char,
+}
+
+impl From<libsql::replication::Replicated> for replicated {
+    fn from(replicated: libsql::replication::Replicated) -> Self {
+        replicated {
+            frame_no: replicated.frame_no().unwrap_or(0),
+            frames_synced: replicated.frames_synced() as std::ffi::c_ulonglong,
+            has_frame_no: replicated.frame_no().is_some() as std::ffi::c_char,
+        }
+    }
+}
+
+/// Completion callback of the `*_async` functions, called from a runtime worker thread with the
+/// `user_data` given to the call. On failure `result` is null/zeroed and `err` is set, both are
+/// owned by the host afterwards.
+pub type libsql_callback<T> = unsafe extern "C" fn(
+    user_data: *mut std::ffi::c_void,
+    result: T,
+    err: *const crate::error::libsql_error,
+);
+
+/// Receives the events of `libsql_set_log_callback`, from whatever thread emitted them. `target`
+/// and `message` are only valid during the call.
+pub type libsql_log_callback = unsafe extern "C" fn(
+    level: std::ffi::c_int,
+    target: *const std::ffi::c_char,
+    message: *const std::ffi::c_char,
+    user_data: *mut std::ffi::c_void,
+);
+
+/// Progress of a sync. libsql only tells how many frames were applied once the sync ends, so
+/// while it runs the progress is the `bytes` received from the primary and `frames` is 0. The
+/// last call, with `done` set, has the frames applied.
+#[repr(C)]
+pub struct libsql_sync_progress {
+    pub bytes: std::ffi::c_ulonglong,
+    pub frames: std::ffi::c_ulonglong,
+    pub done: std::ffi::c_char,
+}
+
+/// Called from a runtime worker thread during `libsql_sync_with_progress`, `progress` is only
+/// valid during the call.
+pub type libsql_sync_progress_callback =
+    unsafe extern "C" fn(user_data: *mut std::ffi::c_void, progress: *const libsql_sync_progress);
+
+/// Times are unix milliseconds, 0 when it never happened.
+#[repr(C)]
+pub struct libsql_sync_status {
+    /// 1 while the periodic sync of `sync_interval` is scheduled.
+    pub periodic: std::ffi::c_char,
+    /// 1 while a sync, periodic or not, is running.
+    pub syncing: std::ffi::c_char,
+    pub last_success_ms: std::ffi::c_ulonglong,
+    pub last_error_ms: std::ffi::c_ulonglong,
+    /// Message of the last failed sync, null if none. Freed with `libsql_free_string`.
+    pub last_error: *const std::ffi::c_char,
 }
 
+#[repr(C)]
+pub struct libsql_pool_stats {
+    /// Connections alive, idle or acquired.
+    pub open: std::ffi::c_int,
+    pub idle: std::ffi::c_int,
+    pub acquired: std::ffi::c_int,
+}
+
+/// Argument of a user-defined function, valid only during the call. `value_type` is one of
+/// `LIBSQL_INT`, `LIBSQL_FLOAT`, `LIBSQL_TEXT`, `LIBSQL_BLOB` or `LIBSQL_NULL`, texts are UTF-8
+/// and NUL terminated, their `bytes.len` excludes the terminator.
+#[repr(C)]
+pub struct libsql_value {
+    pub value_type: std::ffi::c_int,
+    pub int_value: std::ffi::c_longlong,
+    pub float_value: std::ffi::c_double,
+    pub bytes: blob,
+}
+
+/// Context of a user-defined function call, the `libsql_result_*` functions set its result.
+pub type libsql_function_context = libsql::ffi::sqlite3_context;
+
+/// Called from the thread running the statement with the arguments of the call, it must set
+/// the result through `ctx` before returning, otherwise the result is NULL.
+pub type libsql_scalar_function_callback = unsafe extern "C" fn(
+    user_data: *mut std::ffi::c_void,
+    ctx: *mut libsql_function_context,
+    argc: std::ffi::c_int,
+    argv: *const libsql_value,
+);
+
+/// Called with the arguments of a row and `state`, the slot of its group's state, to step or,
+/// for window functions, invert the aggregation.
+pub type libsql_aggregate_step_callback = unsafe extern "C" fn(
+    user_data: *mut std::ffi::c_void,
+    ctx: *mut libsql_function_context,
+    state: *mut *mut std::ffi::c_void,
+    argc: std::ffi::c_int,
+    argv: *const libsql_value,
+);
+
+/// Called with the state of a group to set the result of the aggregation through `ctx`.
+pub type libsql_aggregate_result_callback = unsafe extern "C" fn(
+    user_data: *mut std::ffi::c_void,
+    ctx: *mut libsql_function_context,
+    state: *mut std::ffi::c_void,
+);
+
+/// Compares the UTF-8 texts `left` and `right`, which aren't NUL terminated, returning a
+/// negative number, zero or a positive number if `left` sorts before, equal to or after `right`.
+pub type libsql_collation_callback = unsafe extern "C" fn(
+    user_data: *mut std::ffi::c_void,
+    left: *const std::ffi::c_char,
+    left_len: std::ffi::c_int,
+    right: *const std::ffi::c_char,
+    right_len: std::ffi::c_int,
+) -> std::ffi::c_int;
+
+/// Called with the NUL terminated name of a collation that isn't registered.
+pub type libsql_collation_needed_callback =
+    unsafe extern "C" fn(user_data: *mut std::ffi::c_void, name: *const std::ffi::c_char);
+
+/// Called after a row of a rowid table is inserted, updated or deleted, `op` is one of the
+/// `LIBSQL_UPDATE_*` operations and the names are NUL terminated. It must not use the connection.
+pub type libsql_update_hook_callback = unsafe extern "C" fn(
+    user_data: *mut std::ffi::c_void,
+    op: std::ffi::c_int,
+    db_name: *const std::ffi::c_char,
+    table_name: *const std::ffi::c_char,
+    rowid: std::ffi::c_longlong,
+);
+
+/// Called before a transaction commits, returning non-zero rolls it back instead. It must not use
+/// the connection.
+pub type libsql_commit_hook_callback =
+    unsafe extern "C" fn(user_data: *mut std::ffi::c_void) -> std::ffi::c_int;
+
+/// Called when a transaction rolls back, but not when the connection closes with one open.
+pub type libsql_rollback_hook_callback = unsafe extern "C" fn(user_data: *mut std::ffi::c_void);
+
+/// Called while a statement is prepared for each action it would take, `action` being one of
+/// SQLite's authorizer action codes, e.g. `SQLITE_READ` or `SQLITE_ATTACH`. The arguments depend
+/// on the action and may be NULL: for `SQLITE_READ` they're the table and column names. `db_name`
+/// is the database, `trigger_or_view` the innermost trigger or view the action comes from.
+/// Returns one of the `LIBSQL_AUTH_*` decisions. It must not use the connection.
+pub type libsql_authorizer_callback = unsafe extern "C" fn(
+    user_data: *mut std::ffi::c_void,
+    action: std::ffi::c_int,
+    arg1: *const std::ffi::c_char,
+    arg2: *const std::ffi::c_char,
+    db_name: *const std::ffi::c_char,
+    trigger_or_view: *const std::ffi::c_char,
+) -> std::ffi::c_int;
+
+/// Releases the `user_data` of a callback once it can't be called anymore.
+pub type libsql_destroy_callback = unsafe extern "C" fn(user_data: *mut std::ffi::c_void);